@@ -105,6 +105,30 @@ if [ -d "$SCREENSHOTS_DIR" ]; then
     cp -r "$SCREENSHOTS_DIR" "$TEMP_DIR/"
 fi
 
+# Sign the package when a publisher key is given (see docs/WIDGET_PACKAGES.md)
+#   HOMEMAP_SIGNING_KEY  ed25519 private key in PEM format (OpenSSL 3)
+#   HOMEMAP_PUBLISHER    publisher name in the signature (default: manifest author)
+# signature.json is written with jq so names with quotes or backslashes stay valid JSON
+if [ -n "$HOMEMAP_SIGNING_KEY" ]; then
+    echo "Signing package..."
+    if ! command -v jq >/dev/null 2>&1; then
+        echo "Error: signing needs jq"
+        exit 1
+    fi
+    PUBLISHER=${HOMEMAP_PUBLISHER:-$(jq -r '.author // ""' "$MANIFEST")}
+    DIGEST_FILE=$(mktemp)
+    # One "<sha256>  <path>" line per file, sorted by path
+    (cd "$TEMP_DIR" && find . -type f ! -path ./signature.json | sed 's|^\./||' | LC_ALL=C sort | while IFS= read -r f; do
+        printf '%s  %s\n' "$(openssl dgst -sha256 -r "$f" | cut -d' ' -f1)" "$f"
+    done) > "$DIGEST_FILE"
+    PUBLIC_KEY=$(openssl pkey -in "$HOMEMAP_SIGNING_KEY" -pubout -outform DER | tail -c 32 | openssl base64 -A)
+    SIGNATURE=$(openssl pkeyutl -sign -inkey "$HOMEMAP_SIGNING_KEY" -rawin -in "$DIGEST_FILE" | openssl base64 -A)
+    rm -f "$DIGEST_FILE"
+    jq -n --arg publisher "$PUBLISHER" --arg publicKey "$PUBLIC_KEY" --arg signature "$SIGNATURE" \
+        '{publisher: $publisher, publicKey: $publicKey, signature: $signature}' > "$TEMP_DIR/signature.json"
+    echo "  Signed by $PUBLISHER (public key $PUBLIC_KEY)"
+fi
+
 # Create output filename
 PACKAGE_FILE_NAME=$(echo "$PACKAGE_ID" | sed 's/\./-/g')
 OUTPUT_FILE="$OUTPUT_DIR/${PACKAGE_FILE_NAME}-${VERSION}.hwp"
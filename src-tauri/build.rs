@@ -1,3 +1,7 @@
+#[path = "build_embed_assets.rs"]
+mod build_embed_assets;
+
 fn main() {
+    build_embed_assets::embed_template_assets();
     tauri_build::build()
 }
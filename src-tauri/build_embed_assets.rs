@@ -1,39 +1,84 @@
-// Script to generate embedded assets code for Android
+// Packs the default data template into the binary
+// Every file listed in ../homemapdata.example/asset-manifest.json (plus the
+// manifest itself) is written to $OUT_DIR/homemapdata-template.zip, which
+// src/embedded_template.rs includes with include_bytes! on all platforms.
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const TEMPLATE_DIR: &str = "../homemapdata.example";
+const MANIFEST_FILE: &str = "asset-manifest.json";
+const ARCHIVE_NAME: &str = "homemapdata-template.zip";
+
+// Files in the template that are never shipped
+fn is_ignored(name: &str) -> bool {
+    name.starts_with('.') || name == MANIFEST_FILE
+}
+
+// All shippable files under the template, relative with '/' separators
+fn template_files(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_ignored(&name) {
+            continue;
+        }
+        let rel = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+        if entry.path().is_dir() {
+            template_files(&entry.path(), &rel, files);
+        } else {
+            files.push(rel);
+        }
+    }
+}
+
+pub fn embed_template_assets() {
+    let template_dir = PathBuf::from(TEMPLATE_DIR);
+    let manifest_path = template_dir.join(MANIFEST_FILE);
+    println!("cargo:rerun-if-changed={}", TEMPLATE_DIR);
+    println!("cargo:rerun-if-changed={}", manifest_path.display());
 
-fn main() {
-    println!("cargo:rerun-if-changed=../homemapdata.example");
-    
-    let manifest_path = PathBuf::from("../homemapdata.example/asset-manifest.json");
     let manifest_content = fs::read_to_string(&manifest_path)
-        .expect("Failed to read asset manifest");
-    
+        .unwrap_or_else(|e| panic!("Failed to read asset manifest {:?}: {}", manifest_path, e));
     let manifest: serde_json::Value = serde_json::from_str(&manifest_content)
-        .expect("Failed to parse manifest");
-    
-    let files = manifest["files"].as_array()
-        .expect("Manifest must have files array");
-    
-    let out_dir = std::env::var("OUT_DIR").unwrap();
-    let dest_path = PathBuf::from(&out_dir).join("embedded_assets.rs");
-    
-    let mut code = String::from("// Auto-generated embedded assets\n");
-    code.push_str("use std::collections::HashMap;\n\n");
-    code.push_str("pub fn get_embedded_asset(path: &str) -> Option<&'static [u8]> {\n");
-    code.push_str("    match path {\n");
-    
-    for file in files {
-        let file_path = file.as_str().unwrap();
-        let full_path = format!("../homemapdata.example/{}", file_path);
-        code.push_str(&format!("        \"{}\" => Some(include_bytes!(\"{}\")),\n", 
-                              file_path, full_path));
+        .unwrap_or_else(|e| panic!("Failed to parse asset manifest: {}", e));
+    let files: Vec<String> = manifest["files"]
+        .as_array()
+        .expect("Asset manifest must have a files array")
+        .iter()
+        .map(|f| f.as_str().expect("Asset manifest entries must be strings").to_string())
+        .collect();
+
+    // Files added to the template but forgotten in the manifest would silently not ship
+    let mut on_disk = Vec::new();
+    template_files(&template_dir, "", &mut on_disk);
+    for file in on_disk.iter().filter(|f| !files.contains(f)) {
+        println!(
+            "cargo:warning=homemapdata.example/{} is not listed in asset-manifest.json and will not be embedded (run scripts/generate-asset-manifest.sh)",
+            file
+        );
+    }
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    let archive_path = PathBuf::from(out_dir).join(ARCHIVE_NAME);
+    let archive = fs::File::create(&archive_path)
+        .unwrap_or_else(|e| panic!("Failed to create {:?}: {}", archive_path, e));
+    let mut zip = zip::ZipWriter::new(archive);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for file in files.iter().map(String::as_str).chain([MANIFEST_FILE]) {
+        let path = file.split('/').fold(template_dir.clone(), |path, part| path.join(part));
+        let data = fs::read(&path).unwrap_or_else(|e| {
+            panic!("Asset manifest lists {} but it could not be read from {:?}: {}", file, path, e)
+        });
+        zip.start_file(file, options)
+            .unwrap_or_else(|e| panic!("Failed to add {} to template archive: {}", file, e));
+        zip.write_all(&data)
+            .unwrap_or_else(|e| panic!("Failed to write {} to template archive: {}", file, e));
     }
-    
-    code.push_str("        _ => None,\n");
-    code.push_str("    }\n");
-    code.push_str("}\n");
-    
-    fs::write(&dest_path, code).expect("Failed to write embedded_assets.rs");
-    println!("Generated embedded assets for {} files", files.len());
+
+    zip.finish().expect("Failed to finish template archive");
 }
@@ -0,0 +1,153 @@
+// Cached application state
+// Resolving the data directory reads and parses settings.json, asks the vault
+// for the HC3 password and syncs the built-in resources. AppState does that
+// once and keeps the result in Tauri state, so commands don't repeat it (and
+// its logging) on every call. Commands that change the settings, the active
+// profile or the data directory call invalidate(). The parsed config.json is
+// also cached, and re-read when the file changes on disk or after
+// invalidate_config(). Widget files whose lint report has been logged are
+// remembered, so the report is only logged again once the file changes.
+// The signature verdict of each extracted package is kept until it is
+// installed or discarded, so the install commands can refuse tampered ones.
+
+use crate::package_signing::SignatureStatus;
+use crate::settings_layers::ResolvedConfig;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::SystemTime;
+
+#[derive(Clone)]
+struct Resolved {
+    settings: Arc<ResolvedConfig>,
+    data_dir: PathBuf,
+}
+
+struct CachedConfig {
+    path: PathBuf,
+    // Modification time and size when the file was read
+    stamp: (Option<SystemTime>, u64),
+    value: Arc<Value>,
+}
+
+#[derive(Default)]
+pub struct AppState {
+    resolved: RwLock<Option<Resolved>>,
+    config: RwLock<Option<CachedConfig>>,
+    linted: RwLock<HashMap<PathBuf, (Option<SystemTime>, u64)>>,
+    signatures: RwLock<HashMap<PathBuf, SignatureStatus>>,
+}
+
+// A panic while holding the lock leaves nothing half-written worth refusing over
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
+
+fn file_stamp(path: &Path) -> Option<(Option<SystemTime>, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+impl AppState {
+    fn resolved(&self) -> Result<Resolved, String> {
+        if let Some(resolved) = read(&self.resolved).as_ref() {
+            return Ok(resolved.clone());
+        }
+
+        let mut cache = write(&self.resolved);
+        // Another command may have resolved while we waited for the lock
+        if let Some(resolved) = cache.as_ref() {
+            return Ok(resolved.clone());
+        }
+        let settings = crate::resolve_settings()?;
+        let data_dir = crate::resolve_data_dir(&settings)?;
+        let resolved = Resolved {
+            settings: Arc::new(settings),
+            data_dir,
+        };
+        *cache = Some(resolved.clone());
+        Ok(resolved)
+    }
+
+    // Connection settings and data directory from all settings layers
+    pub fn settings(&self) -> Result<Arc<ResolvedConfig>, String> {
+        Ok(self.resolved()?.settings)
+    }
+
+    // The data directory, created and synced with the built-ins on first use
+    pub fn data_dir(&self) -> Result<PathBuf, String> {
+        Ok(self.resolved()?.data_dir)
+    }
+
+    // The parsed config.json of the data directory
+    pub fn config(&self) -> Result<Arc<Value>, String> {
+        let config_file = self.data_dir()?.join("config.json");
+        let Some(stamp) = file_stamp(&config_file) else {
+            return Err(format!("Config file not found at: {:?}", config_file));
+        };
+
+        if let Some(cached) = read(&self.config).as_ref() {
+            if cached.path == config_file && cached.stamp == stamp && stamp.0.is_some() {
+                return Ok(cached.value.clone());
+            }
+        }
+
+        let content = fs::read_to_string(&config_file)
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let value: Value = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse config JSON: {}", e))?;
+        let value = Arc::new(value);
+        *write(&self.config) = Some(CachedConfig {
+            path: config_file,
+            stamp,
+            value: value.clone(),
+        });
+        Ok(value)
+    }
+
+    // Forget everything; the next command resolves settings and paths again
+    pub fn invalidate(&self) {
+        *write(&self.resolved) = None;
+        *write(&self.config) = None;
+        println!("Cleared cached settings and data directory");
+    }
+
+    // Whether `file` changed since the last call for it (true the first time)
+    pub fn needs_lint(&self, file: &Path) -> bool {
+        let stamp = file_stamp(file);
+        if stamp.is_some() && read(&self.linted).get(file) == stamp.as_ref() {
+            return false;
+        }
+        match stamp {
+            Some(stamp) => write(&self.linted).insert(file.to_path_buf(), stamp),
+            None => write(&self.linted).remove(file),
+        };
+        true
+    }
+
+    // Forget the parsed config.json after writing it
+    pub fn invalidate_config(&self) {
+        *write(&self.config) = None;
+    }
+
+    // Remember the signature verdict of a package extracted into `dir`
+    pub fn record_signature(&self, dir: &Path, status: SignatureStatus) {
+        write(&self.signatures).insert(dir.to_path_buf(), status);
+    }
+
+    // The verdict recorded for `dir`, if it was extracted by this process
+    pub fn signature(&self, dir: &Path) -> Option<SignatureStatus> {
+        read(&self.signatures).get(dir).copied()
+    }
+
+    // Once the package is installed or discarded
+    pub fn forget_signature(&self, dir: &Path) {
+        write(&self.signatures).remove(dir);
+    }
+}
@@ -0,0 +1,509 @@
+// Automatic floor/device layout generated from HC3 sections, rooms and devices
+// Sections become floors, rooms become blocks on the floor, devices are laid out
+// in a grid inside their room block. The result is a preview that the frontend
+// can show before it is merged into config.json.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+// Same rules file the frontend auto-discovery uses
+const DEVICE_MAPPINGS: &str = include_str!("../../src/deviceMappings.json");
+
+const FALLBACK_WIDGET: &str = "genericdevice";
+const DEFAULT_FLOOR_IMAGE: &str = "images/default-floor.png";
+const DEFAULT_FLOOR_WIDTH: i64 = 2000;
+const DEFAULT_FLOOR_HEIGHT: i64 = 1500;
+const UNASSIGNED_FLOOR_ID: &str = "hc3-unassigned";
+
+// Layout constants (pixels in floor image coordinates)
+const FLOOR_MARGIN: i64 = 150;
+const ROOM_PADDING: i64 = 60;
+const ROOM_GAP: i64 = 80;
+const DEVICE_SPACING: i64 = 100;
+const DEVICES_PER_ROOM_ROW: usize = 4;
+
+#[derive(Debug, Deserialize)]
+pub struct Hc3Section {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "sortOrder", default)]
+    pub sort_order: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Hc3Room {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "sectionID", default)]
+    pub section_id: i64,
+    #[serde(rename = "sortOrder", default)]
+    pub sort_order: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Hc3Device {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "roomID", default)]
+    pub room_id: i64,
+    #[serde(rename = "type", default)]
+    pub device_type: String,
+    #[serde(default)]
+    pub interfaces: Vec<String>,
+    #[serde(default)]
+    pub properties: Value,
+    #[serde(default = "default_true")]
+    pub visible: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct MappingRule {
+    #[serde(rename = "type")]
+    device_type: String,
+    interface: Option<String>,
+    property: Option<String>,
+    #[serde(rename = "propertyValue")]
+    property_value: Option<Value>,
+    widget: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MappingRules {
+    mappings: Vec<MappingRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Position {
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedFloor {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub width: i64,
+    pub height: i64,
+    #[serde(rename = "sectionId")]
+    pub section_id: Option<i64>,
+    // False when the floor already exists in config.json and is only reused
+    #[serde(rename = "isNew")]
+    pub is_new: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedRoom {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "floorId")]
+    pub floor_id: String,
+    pub origin: Position,
+    pub width: i64,
+    pub height: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedDevice {
+    pub id: i64,
+    pub name: String,
+    // Widget type, same meaning as "type" on config.json devices
+    #[serde(rename = "type")]
+    pub widget: String,
+    #[serde(rename = "floorId")]
+    pub floor_id: String,
+    pub position: Position,
+    #[serde(rename = "roomId")]
+    pub room_id: i64,
+    #[serde(rename = "roomName")]
+    pub room_name: String,
+    #[serde(rename = "hc3Type")]
+    pub hc3_type: String,
+    // False when no mapping rule matched and the generic widget was used
+    pub mapped: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedDevice {
+    pub id: i64,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoMapPreview {
+    pub floors: Vec<ProposedFloor>,
+    pub rooms: Vec<ProposedRoom>,
+    pub devices: Vec<ProposedDevice>,
+    pub skipped: Vec<SkippedDevice>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoMapMergeResult {
+    #[serde(rename = "floorsAdded")]
+    pub floors_added: usize,
+    #[serde(rename = "devicesAdded")]
+    pub devices_added: usize,
+    #[serde(rename = "devicesSkipped")]
+    pub devices_skipped: usize,
+}
+
+pub struct AutoMapOptions {
+    pub include_hidden: bool,
+    // Widget ids that exist in the data directory; rules pointing elsewhere are ignored
+    pub available_widgets: HashSet<String>,
+}
+
+fn load_mapping_rules() -> Result<Vec<MappingRule>, String> {
+    let rules: MappingRules = serde_json::from_str(DEVICE_MAPPINGS)
+        .map_err(|e| format!("Failed to parse deviceMappings.json: {}", e))?;
+    Ok(rules.mappings)
+}
+
+fn rule_matches(device: &Hc3Device, rule: &MappingRule) -> bool {
+    if device.device_type != rule.device_type {
+        return false;
+    }
+
+    if let Some(interface) = &rule.interface {
+        if !device.interfaces.iter().any(|i| i == interface) {
+            return false;
+        }
+    }
+
+    if let Some(property) = &rule.property {
+        let actual = device.properties.get(property).unwrap_or(&Value::Null);
+        let expected = rule.property_value.as_ref().unwrap_or(&Value::Null);
+        if actual != expected {
+            return false;
+        }
+    }
+
+    true
+}
+
+// First matching rule whose widget is installed wins, as in autoMapManager.js
+fn widget_for_device(device: &Hc3Device, rules: &[MappingRule], available: &HashSet<String>) -> Option<String> {
+    rules.iter()
+        .filter(|rule| rule_matches(device, rule))
+        .find(|rule| available.contains(&rule.widget))
+        .map(|rule| rule.widget.clone())
+}
+
+// (device, widget, matched by a rule)
+type PlacedDevice<'a> = (&'a Hc3Device, String, bool);
+// (room id, room name, devices in that room)
+type RoomGroup<'a> = (i64, String, Vec<PlacedDevice<'a>>);
+
+fn section_floor_id(section_id: i64) -> String {
+    format!("hc3-section-{}", section_id)
+}
+
+fn existing_floors(config: &Value) -> HashMap<String, (String, String, i64, i64)> {
+    let mut floors = HashMap::new();
+    if let Some(list) = config.get("floors").and_then(|f| f.as_array()) {
+        for floor in list {
+            if let Some(id) = floor.get("id").and_then(|v| v.as_str()) {
+                floors.insert(id.to_string(), (
+                    floor.get("name").and_then(|v| v.as_str()).unwrap_or(id).to_string(),
+                    floor.get("image").and_then(|v| v.as_str()).unwrap_or(DEFAULT_FLOOR_IMAGE).to_string(),
+                    floor.get("width").and_then(|v| v.as_i64()).unwrap_or(DEFAULT_FLOOR_WIDTH),
+                    floor.get("height").and_then(|v| v.as_i64()).unwrap_or(DEFAULT_FLOOR_HEIGHT),
+                ));
+            }
+        }
+    }
+    floors
+}
+
+fn existing_device_ids(config: &Value) -> HashSet<i64> {
+    config.get("devices")
+        .and_then(|d| d.as_array())
+        .map(|devices| devices.iter()
+            .filter_map(|d| d.get("id").and_then(|id| id.as_i64()))
+            .collect())
+        .unwrap_or_default()
+}
+
+// Lay out room blocks left to right, wrapping to a new band when the floor width is used up
+fn layout_floor(
+    floor: &ProposedFloor,
+    rooms: &[RoomGroup],
+    out_rooms: &mut Vec<ProposedRoom>,
+    out_devices: &mut Vec<ProposedDevice>,
+) {
+    let mut cursor_x = FLOOR_MARGIN;
+    let mut cursor_y = FLOOR_MARGIN;
+    let mut band_height = 0;
+
+    for (room_id, room_name, devices) in rooms {
+        let columns = devices.len().clamp(1, DEVICES_PER_ROOM_ROW) as i64;
+        let rows = devices.len().div_ceil(DEVICES_PER_ROOM_ROW).max(1) as i64;
+        let block_width = ROOM_PADDING * 2 + (columns - 1) * DEVICE_SPACING;
+        let block_height = ROOM_PADDING * 2 + (rows - 1) * DEVICE_SPACING;
+
+        if cursor_x > FLOOR_MARGIN && cursor_x + block_width > floor.width - FLOOR_MARGIN {
+            cursor_x = FLOOR_MARGIN;
+            cursor_y += band_height + ROOM_GAP;
+            band_height = 0;
+        }
+
+        out_rooms.push(ProposedRoom {
+            id: *room_id,
+            name: room_name.clone(),
+            floor_id: floor.id.clone(),
+            origin: Position { x: cursor_x, y: cursor_y },
+            width: block_width,
+            height: block_height,
+        });
+
+        for (index, (device, widget, mapped)) in devices.iter().enumerate() {
+            let col = (index % DEVICES_PER_ROOM_ROW) as i64;
+            let row = (index / DEVICES_PER_ROOM_ROW) as i64;
+            out_devices.push(ProposedDevice {
+                id: device.id,
+                name: device.name.clone(),
+                widget: widget.clone(),
+                floor_id: floor.id.clone(),
+                position: Position {
+                    x: cursor_x + ROOM_PADDING + col * DEVICE_SPACING,
+                    y: cursor_y + ROOM_PADDING + row * DEVICE_SPACING,
+                },
+                room_id: *room_id,
+                room_name: room_name.clone(),
+                hc3_type: device.device_type.clone(),
+                mapped: *mapped,
+            });
+        }
+
+        cursor_x += block_width + ROOM_GAP;
+        band_height = band_height.max(block_height);
+    }
+}
+
+pub fn build_preview(
+    mut sections: Vec<Hc3Section>,
+    mut rooms: Vec<Hc3Room>,
+    devices: Vec<Hc3Device>,
+    config: &Value,
+    options: &AutoMapOptions,
+) -> Result<AutoMapPreview, String> {
+    let rules = load_mapping_rules()?;
+    let known_floors = existing_floors(config);
+    let known_devices = existing_device_ids(config);
+
+    sections.sort_by_key(|s| (s.sort_order, s.id));
+    rooms.sort_by_key(|r| (r.sort_order, r.id));
+
+    let section_ids: HashSet<i64> = sections.iter().map(|s| s.id).collect();
+    let room_lookup: HashMap<i64, &Hc3Room> = rooms.iter().map(|r| (r.id, r)).collect();
+
+    let mut skipped = Vec::new();
+    // room id -> placed devices; room id 0 collects devices without a (known) room
+    let mut devices_by_room: HashMap<i64, Vec<PlacedDevice>> = HashMap::new();
+
+    for device in &devices {
+        if known_devices.contains(&device.id) {
+            skipped.push(SkippedDevice {
+                id: device.id,
+                name: device.name.clone(),
+                reason: "Already in config.json".to_string(),
+            });
+            continue;
+        }
+        if !device.visible && !options.include_hidden {
+            skipped.push(SkippedDevice {
+                id: device.id,
+                name: device.name.clone(),
+                reason: "Hidden on HC3".to_string(),
+            });
+            continue;
+        }
+
+        let matched = widget_for_device(device, &rules, &options.available_widgets);
+        let mapped = matched.is_some();
+        let widget = matched.unwrap_or_else(|| FALLBACK_WIDGET.to_string());
+
+        let room_key = if room_lookup.contains_key(&device.room_id) { device.room_id } else { 0 };
+        devices_by_room.entry(room_key).or_default().push((device, widget, mapped));
+    }
+
+    // floor id -> rooms on that floor, in HC3 sort order
+    let mut floor_rooms: HashMap<String, Vec<RoomGroup>> = HashMap::new();
+    for room in &rooms {
+        if let Some(room_devices) = devices_by_room.remove(&room.id) {
+            let floor_id = if section_ids.contains(&room.section_id) {
+                section_floor_id(room.section_id)
+            } else {
+                UNASSIGNED_FLOOR_ID.to_string()
+            };
+            floor_rooms.entry(floor_id).or_default().push((room.id, room.name.clone(), room_devices));
+        }
+    }
+    if let Some(loose_devices) = devices_by_room.remove(&0) {
+        floor_rooms.entry(UNASSIGNED_FLOOR_ID.to_string())
+            .or_default()
+            .push((0, "No room".to_string(), loose_devices));
+    }
+
+    let mut floor_order: Vec<(String, String, Option<i64>)> = sections.iter()
+        .map(|s| (section_floor_id(s.id), s.name.clone(), Some(s.id)))
+        .collect();
+    floor_order.push((UNASSIGNED_FLOOR_ID.to_string(), "Unassigned".to_string(), None));
+
+    let mut preview = AutoMapPreview {
+        floors: Vec::new(),
+        rooms: Vec::new(),
+        devices: Vec::new(),
+        skipped,
+    };
+
+    for (floor_id, name, section_id) in floor_order {
+        let Some(rooms_on_floor) = floor_rooms.get(&floor_id) else {
+            continue;
+        };
+
+        let floor = match known_floors.get(&floor_id) {
+            Some((existing_name, image, width, height)) => ProposedFloor {
+                id: floor_id.clone(),
+                name: existing_name.clone(),
+                image: image.clone(),
+                width: *width,
+                height: *height,
+                section_id,
+                is_new: false,
+            },
+            None => ProposedFloor {
+                id: floor_id.clone(),
+                name,
+                image: DEFAULT_FLOOR_IMAGE.to_string(),
+                width: DEFAULT_FLOOR_WIDTH,
+                height: DEFAULT_FLOOR_HEIGHT,
+                section_id,
+                is_new: true,
+            },
+        };
+
+        layout_floor(&floor, rooms_on_floor, &mut preview.rooms, &mut preview.devices);
+        preview.floors.push(floor);
+    }
+
+    println!("Auto-map preview: {} floors, {} rooms, {} devices, {} skipped",
+        preview.floors.len(), preview.rooms.len(), preview.devices.len(), preview.skipped.len());
+
+    Ok(preview)
+}
+
+// Merge a (possibly user-edited) preview into config.json content.
+// Existing floors and devices are never modified; only new entries are appended.
+pub fn merge_preview(config: &mut Value, preview: &AutoMapPreview) -> Result<AutoMapMergeResult, String> {
+    let config_obj = config.as_object_mut()
+        .ok_or("Invalid config.json: not a JSON object")?;
+
+    let floors = config_obj.entry("floors").or_insert_with(|| Value::Array(Vec::new()));
+    let floors = floors.as_array_mut()
+        .ok_or("Invalid config.json: 'floors' field is not an array")?;
+    let floor_ids: HashSet<String> = floors.iter()
+        .filter_map(|f| f.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()))
+        .collect();
+
+    let used_floors: HashSet<&str> = preview.devices.iter().map(|d| d.floor_id.as_str()).collect();
+
+    let mut result = AutoMapMergeResult { floors_added: 0, devices_added: 0, devices_skipped: 0 };
+
+    for floor in &preview.floors {
+        if floor_ids.contains(&floor.id) || !used_floors.contains(floor.id.as_str()) {
+            continue;
+        }
+        floors.push(serde_json::json!({
+            "id": floor.id,
+            "name": floor.name,
+            "image": floor.image,
+            "width": floor.width,
+            "height": floor.height
+        }));
+        result.floors_added += 1;
+    }
+
+    let all_floor_ids: HashSet<String> = floors.iter()
+        .filter_map(|f| f.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()))
+        .collect();
+
+    let devices = config_obj.entry("devices").or_insert_with(|| Value::Array(Vec::new()));
+    let devices = devices.as_array_mut()
+        .ok_or("Invalid config.json: 'devices' field is not an array")?;
+    let mut device_ids: HashSet<i64> = devices.iter()
+        .filter_map(|d| d.get("id").and_then(|id| id.as_i64()))
+        .collect();
+
+    for device in &preview.devices {
+        if device_ids.contains(&device.id) || !all_floor_ids.contains(&device.floor_id) {
+            result.devices_skipped += 1;
+            continue;
+        }
+        devices.push(serde_json::json!({
+            "id": device.id,
+            "name": device.name,
+            "type": device.widget,
+            "floor_id": device.floor_id,
+            "position": { "x": device.position.x, "y": device.position.y },
+            "state": {}
+        }));
+        device_ids.insert(device.id);
+        result.devices_added += 1;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn device(id: i64, room_id: i64, device_type: &str, properties: Value) -> Hc3Device {
+        serde_json::from_value(json!({
+            "id": id, "name": format!("Device {}", id), "roomID": room_id, "type": device_type,
+            "interfaces": ["light"], "properties": properties
+        })).unwrap()
+    }
+
+    #[test]
+    fn devices_are_placed_in_their_room_with_the_matching_widget() {
+        let sections = vec![Hc3Section { id: 1, name: "Ground".to_string(), sort_order: 0 }];
+        let rooms = vec![Hc3Room { id: 10, name: "Kitchen".to_string(), section_id: 1, sort_order: 0 }];
+        let devices = vec![
+            device(100, 10, "com.fibaro.binarySwitch", json!({ "isLight": true })),
+            device(101, 10, "com.fibaro.binarySwitch", json!({})),
+            device(102, 99, "com.fibaro.unknown", json!({})),
+            device(103, 10, "com.fibaro.binarySwitch", json!({})),
+        ];
+        let config = json!({ "floors": [], "devices": [{ "id": 103 }] });
+        let options = AutoMapOptions {
+            include_hidden: false,
+            available_widgets: ["light", "binarySwitch"].iter().map(|w| w.to_string()).collect(),
+        };
+
+        let preview = build_preview(sections, rooms, devices, &config, &options).unwrap();
+
+        let placed: Vec<_> = preview.devices.iter()
+            .map(|d| (d.id, d.widget.as_str(), d.floor_id.as_str(), d.room_id, d.mapped))
+            .collect();
+        assert_eq!(placed, vec![
+            (100, "light", "hc3-section-1", 10, true),
+            (101, "binarySwitch", "hc3-section-1", 10, true),
+            (102, FALLBACK_WIDGET, UNASSIGNED_FLOOR_ID, 0, false),
+        ]);
+        let rooms: Vec<_> = preview.rooms.iter().map(|r| (r.id, r.floor_id.as_str())).collect();
+        assert_eq!(rooms, vec![(10, "hc3-section-1"), (0, UNASSIGNED_FLOOR_ID)]);
+        assert_eq!(preview.skipped.iter().map(|s| s.id).collect::<Vec<_>>(), vec![103]);
+
+        let serialized = serde_json::to_value(&preview.devices[0]).unwrap();
+        assert_eq!(serialized["floorId"], "hc3-section-1");
+    }
+}
@@ -0,0 +1,231 @@
+// Built-in widget and icon sync
+// The template ships widgets/built-in and icons/built-in. Every sync records the
+// shipped files and their SHA-256 hashes in <data>/.builtin-manifest.json, so the
+// next sync can tell files we installed from files the user edited since:
+//   - not on disk                 -> installed
+//   - unchanged since last sync   -> updated to the shipped version
+//   - edited by the user          -> kept, or moved to .builtin-backup/<timestamp>/
+//   - no longer shipped           -> removed, unless edited by the user
+// Without a manifest (data from older versions) any file that differs from the
+// shipped one counts as edited. The outcome is saved to .builtin-sync-report.json.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const BUILTIN_DIRS: [&str; 2] = ["widgets/built-in", "icons/built-in"];
+const MANIFEST_FILE: &str = ".builtin-manifest.json";
+const REPORT_FILE: &str = ".builtin-sync-report.json";
+const BACKUP_DIR: &str = ".builtin-backup";
+
+// A shipped file, path relative to the data directory with '/' separators
+pub struct TemplateFile {
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+// What to do with built-in files the user has edited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModifiedPolicy {
+    // Leave the edited file and skip the update
+    Keep,
+    // Back the edited file up under .builtin-backup/ and install the shipped one
+    #[default]
+    MoveAside,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct BuiltinManifest {
+    version: String,
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedFile {
+    pub path: String,
+    pub backup: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub version: String,
+    pub previous_version: Option<String>,
+    pub policy: ModifiedPolicy,
+    pub synced_at: String,
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: usize,
+    // Edited by the user and left alone (policy "keep")
+    pub kept_modified: Vec<String>,
+    // Edited by the user and backed up before installing the shipped version
+    pub moved_aside: Vec<MovedFile>,
+    // No longer shipped and removed
+    pub removed: Vec<String>,
+    // No longer shipped but edited by the user, so left in place
+    pub kept_stale: Vec<String>,
+}
+
+impl SyncReport {
+    // Whether anything happened that the user should be told about
+    pub fn has_changes(&self) -> bool {
+        !(self.added.is_empty()
+            && self.updated.is_empty()
+            && self.kept_modified.is_empty()
+            && self.moved_aside.is_empty()
+            && self.removed.is_empty()
+            && self.kept_stale.is_empty())
+    }
+}
+
+fn hash(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn local_path(root: &Path, rel: &str) -> PathBuf {
+    rel.split('/').fold(root.to_path_buf(), |path, part| path.join(part))
+}
+
+pub fn is_builtin_path(rel: &str) -> bool {
+    BUILTIN_DIRS.iter().any(|dir| rel.starts_with(&format!("{}/", dir)))
+}
+
+fn read_manifest(data_dir: &Path) -> Option<BuiltinManifest> {
+    let json = fs::read_to_string(data_dir.join(MANIFEST_FILE)).ok()?;
+    match serde_json::from_str(&json) {
+        Ok(manifest) => Some(manifest),
+        Err(e) => {
+            println!("Ignoring unreadable built-in manifest: {}", e);
+            None
+        }
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("Failed to serialize {:?}: {}", path.file_name(), e))?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn write_file(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory {:?}: {}", parent, e))?;
+    }
+    fs::write(path, data).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+// Remove directories left empty by removed files, up to the built-in root
+fn remove_empty_parents(path: &Path, data_dir: &Path) {
+    let roots: Vec<PathBuf> = BUILTIN_DIRS.iter().map(|dir| local_path(data_dir, dir)).collect();
+    let mut current = path.parent();
+    while let Some(dir) = current {
+        if roots.iter().any(|root| root == dir) || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+pub fn sync(
+    data_dir: &Path,
+    files: &[TemplateFile],
+    version: &str,
+    policy: ModifiedPolicy,
+) -> Result<SyncReport, String> {
+    let previous = read_manifest(data_dir);
+    let previous_files = previous.as_ref().map(|m| m.files.clone()).unwrap_or_default();
+    let timestamp = chrono::Local::now();
+    let backup_root = data_dir.join(BACKUP_DIR).join(timestamp.format("%Y%m%d-%H%M%S").to_string());
+
+    let mut report = SyncReport {
+        version: version.to_string(),
+        previous_version: previous.map(|m| m.version),
+        policy,
+        synced_at: timestamp.to_rfc3339(),
+        ..Default::default()
+    };
+    let mut manifest = BuiltinManifest {
+        version: version.to_string(),
+        files: BTreeMap::new(),
+    };
+
+    for file in files.iter().filter(|f| is_builtin_path(&f.path)) {
+        let shipped = hash(&file.data);
+        let target = local_path(data_dir, &file.path);
+        manifest.files.insert(file.path.clone(), shipped.clone());
+
+        if !target.exists() {
+            write_file(&target, &file.data)?;
+            report.added.push(file.path.clone());
+            continue;
+        }
+
+        let local = fs::read(&target)
+            .map(|data| hash(&data))
+            .map_err(|e| format!("Failed to read {:?}: {}", target, e))?;
+        if local == shipped {
+            report.unchanged += 1;
+        } else if previous_files.get(&file.path) == Some(&local) {
+            write_file(&target, &file.data)?;
+            report.updated.push(file.path.clone());
+        } else if policy == ModifiedPolicy::Keep {
+            println!("Keeping locally modified built-in: {}", file.path);
+            report.kept_modified.push(file.path.clone());
+        } else {
+            let backup = local_path(&backup_root, &file.path);
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+            }
+            fs::rename(&target, &backup)
+                .map_err(|e| format!("Failed to back up modified {}: {}", file.path, e))?;
+            write_file(&target, &file.data)?;
+            println!("Moved locally modified built-in {} to {:?}", file.path, backup);
+            report.moved_aside.push(MovedFile {
+                path: file.path.clone(),
+                backup: backup.to_string_lossy().to_string(),
+            });
+        }
+    }
+
+    // Built-ins we installed before that are no longer shipped
+    for (path, installed_hash) in &previous_files {
+        if manifest.files.contains_key(path) {
+            continue;
+        }
+        let target = local_path(data_dir, path);
+        if !target.exists() {
+            continue;
+        }
+        let local = fs::read(&target).map(|data| hash(&data)).unwrap_or_default();
+        if &local == installed_hash {
+            fs::remove_file(&target).map_err(|e| format!("Failed to remove {}: {}", path, e))?;
+            remove_empty_parents(&target, data_dir);
+            report.removed.push(path.clone());
+        } else {
+            report.kept_stale.push(path.clone());
+        }
+    }
+
+    write_json(&data_dir.join(MANIFEST_FILE), &manifest)?;
+    write_json(&data_dir.join(REPORT_FILE), &report)?;
+    println!(
+        "Built-in sync: {} added, {} updated, {} unchanged, {} kept, {} moved aside, {} removed",
+        report.added.len(),
+        report.updated.len(),
+        report.unchanged,
+        report.kept_modified.len(),
+        report.moved_aside.len(),
+        report.removed.len()
+    );
+    Ok(report)
+}
+
+// The report of the most recent sync, if any
+pub fn last_report(data_dir: &Path) -> Option<SyncReport> {
+    let json = fs::read_to_string(data_dir.join(REPORT_FILE)).ok()?;
+    serde_json::from_str(&json).ok()
+}
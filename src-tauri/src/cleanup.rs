@@ -0,0 +1,414 @@
+// Storage cleanup
+// - temp/ holds package extractions (package_<ts>) and files staged by
+//   save_temp_file; entries older than an hour are deleted at startup and
+//   whenever a scan runs.
+// - images/ files no floor uses, user icon sets (icons/<set>) no widget or
+//   device uses, and icons/packages/<id> folders of packages that are no longer
+//   installed are listed as candidates with their sizes. Nothing is deleted
+//   until the user confirms; confirmed items are moved to
+//   .trash/<batch>/files/<path> with a trash.json describing the batch, so they
+//   can be restored. Batches older than 30 days are purged at startup.
+// - cache/ holds generated files (icon variants) that are recreated on demand.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const TEMP_DIR: &str = "temp";
+pub const TRASH_DIR: &str = ".trash";
+pub const CACHE_DIR: &str = "cache";
+const TRASH_INFO: &str = "trash.json";
+const STALE_TEMP_AGE: Duration = Duration::from_secs(60 * 60);
+const TRASH_RETENTION_DAYS: u64 = 30;
+// Used by the app itself when a config has no floors
+const PROTECTED_IMAGES: [&str; 1] = ["images/default-floor.png"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupCandidate {
+    // Data-relative path with '/' separators
+    pub path: String,
+    // "image", "icon-set" or "package-icons"
+    pub kind: String,
+    pub size: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanupScan {
+    pub temp_removed: Vec<String>,
+    pub temp_freed: u64,
+    pub candidates: Vec<CleanupCandidate>,
+    pub candidates_size: u64,
+    pub trash_size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashBatch {
+    pub id: String,
+    pub trashed_at: String,
+    pub items: Vec<CleanupCandidate>,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrashRestore {
+    pub restored: Vec<String>,
+    // Items left in the trash because something now exists at their path
+    pub conflicts: Vec<String>,
+}
+
+fn local_path(root: &Path, rel: &str) -> PathBuf {
+    rel.split('/').fold(root.to_path_buf(), |path, part| path.join(part))
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Total size of a file or folder
+pub fn size_of(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// Scratch folders that backups and scans skip
+pub fn is_scratch_path(rel: &Path) -> bool {
+    rel.components()
+        .next()
+        .is_some_and(|first| [TEMP_DIR, TRASH_DIR, CACHE_DIR].iter().any(|dir| first.as_os_str() == *dir))
+}
+
+// Delete temp/ entries last modified longer than max_age ago
+pub fn remove_stale_temp(data_dir: &Path, max_age: Duration) -> (Vec<String>, u64) {
+    let mut removed = Vec::new();
+    let mut freed = 0;
+    let Ok(entries) = fs::read_dir(data_dir.join(TEMP_DIR)) else {
+        return (removed, freed);
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let age = entry.metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age < max_age {
+            continue;
+        }
+        let size = size_of(&path);
+        match remove_path(&path) {
+            Ok(()) => {
+                freed += size;
+                removed.push(relative(data_dir, &path));
+            }
+            Err(e) => println!("Failed to remove stale temp entry {:?}: {}", path, e),
+        }
+    }
+    if !removed.is_empty() {
+        println!("Removed {} stale temp entries ({} bytes)", removed.len(), freed);
+    }
+    (removed, freed)
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+// Floor images used by config.json, as data-relative paths
+fn referenced_images(data_dir: &Path, config: &Value) -> HashSet<String> {
+    config.get("floors")
+        .and_then(|f| f.as_array())
+        .map(|floors| floors.iter()
+            .filter_map(|floor| floor.get("image").and_then(|i| i.as_str()))
+            .map(|image| {
+                let path = Path::new(image);
+                if path.is_absolute() {
+                    relative(data_dir, path)
+                } else {
+                    image.trim_start_matches("./").to_string()
+                }
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+// Last path component of an icon set reference ("icons/built-in/dimLight" -> "dimLight")
+fn icon_set_name(reference: &str) -> &str {
+    reference.trim_end_matches('/').rsplit('/').next().unwrap_or(reference)
+}
+
+// Icon set names used by any widget definition or device
+fn referenced_icon_sets(data_dir: &Path, config: &Value) -> HashSet<String> {
+    let mut sets = HashSet::new();
+    let mut add = |value: Option<&Value>| {
+        if let Some(reference) = value.and_then(|v| v.as_str()) {
+            sets.insert(icon_set_name(reference).to_string());
+        }
+    };
+
+    for entry in walkdir::WalkDir::new(data_dir.join("widgets")).into_iter().filter_map(|e| e.ok()) {
+        if entry.path().extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        if let Some(widget) = read_json(entry.path()) {
+            add(widget.get("iconSet"));
+            add(widget.get("render").and_then(|r| r.get("icon")).and_then(|i| i.get("set")));
+            // "iconSetMap": { "<key>": { "iconSet": "..." } } or { "<key>": "..." }
+            for entry in widget.get("iconSetMap").and_then(|m| m.as_object()).into_iter().flat_map(|m| m.values()) {
+                add(entry.get("iconSet").or(Some(entry)));
+            }
+        }
+    }
+
+    for device in config.get("devices").and_then(|d| d.as_array()).into_iter().flatten() {
+        add(device.get("params").and_then(|p| p.get("iconSet")));
+    }
+    sets
+}
+
+fn installed_packages(data_dir: &Path) -> HashSet<String> {
+    read_json(&data_dir.join("installed-packages.json"))
+        .and_then(|r| r.get("packages").and_then(|p| p.as_object()).map(|p| p.keys().cloned().collect()))
+        .unwrap_or_default()
+}
+
+// Everything that could be moved to the trash right now
+pub fn find_candidates(data_dir: &Path) -> Vec<CleanupCandidate> {
+    let mut candidates = Vec::new();
+    let Some(config) = read_json(&data_dir.join("config.json")) else {
+        // Without a readable config every image would look unused
+        println!("Skipping unused asset scan: config.json is missing or invalid");
+        return candidates;
+    };
+
+    let images = referenced_images(data_dir, &config);
+    for entry in walkdir::WalkDir::new(data_dir.join("images")).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() || entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let rel = relative(data_dir, entry.path());
+        if images.contains(&rel) || PROTECTED_IMAGES.contains(&rel.as_str()) {
+            continue;
+        }
+        candidates.push(CleanupCandidate {
+            size: size_of(entry.path()),
+            path: rel,
+            kind: "image".to_string(),
+            reason: "Not used by any floor".to_string(),
+        });
+    }
+
+    let icon_sets = referenced_icon_sets(data_dir, &config);
+    let packages = installed_packages(data_dir);
+    let icons_dir = data_dir.join("icons");
+    let subdirs = |dir: &Path| -> Vec<PathBuf> {
+        fs::read_dir(dir)
+            .map(|entries| entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_dir() && !p.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')))
+                .collect())
+            .unwrap_or_default()
+    };
+
+    for dir in subdirs(&icons_dir) {
+        let name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if name == "built-in" || name == "packages" || icon_sets.contains(&name) {
+            continue;
+        }
+        candidates.push(CleanupCandidate {
+            size: size_of(&dir),
+            path: relative(data_dir, &dir),
+            kind: "icon-set".to_string(),
+            reason: "Not used by any widget or device".to_string(),
+        });
+    }
+
+    for dir in subdirs(&icons_dir.join("packages")) {
+        let name = dir.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        if packages.contains(&name) {
+            continue;
+        }
+        candidates.push(CleanupCandidate {
+            size: size_of(&dir),
+            path: relative(data_dir, &dir),
+            kind: "package-icons".to_string(),
+            reason: "Left behind by a package that is no longer installed".to_string(),
+        });
+    }
+
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+    candidates
+}
+
+// Remove stale temp entries and list what else could be cleaned up
+pub fn scan(data_dir: &Path) -> CleanupScan {
+    let (temp_removed, temp_freed) = remove_stale_temp(data_dir, STALE_TEMP_AGE);
+    let candidates = find_candidates(data_dir);
+    CleanupScan {
+        temp_removed,
+        temp_freed,
+        candidates_size: candidates.iter().map(|c| c.size).sum(),
+        candidates,
+        trash_size: size_of(&data_dir.join(TRASH_DIR)),
+    }
+}
+
+fn write_batch_info(dir: &Path, batch: &TrashBatch) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(batch)
+        .map_err(|e| format!("Failed to serialize trash info: {}", e))?;
+    fs::write(dir.join(TRASH_INFO), json).map_err(|e| format!("Failed to write trash info: {}", e))
+}
+
+// Move confirmed candidates to a new trash batch. Paths that are not (or no
+// longer) candidates are refused, so nothing in use can be trashed.
+pub fn move_to_trash(data_dir: &Path, paths: &[String]) -> Result<TrashBatch, String> {
+    let candidates = find_candidates(data_dir);
+    let mut selected = Vec::new();
+    for path in paths {
+        match candidates.iter().find(|c| &c.path == path) {
+            Some(candidate) => selected.push(candidate.clone()),
+            None => return Err(format!("{} is not an unused item and was not moved", path)),
+        }
+    }
+    if selected.is_empty() {
+        return Err("Nothing selected to move to the trash".to_string());
+    }
+
+    let now = chrono::Local::now();
+    let id = now.format("%Y%m%d-%H%M%S%3f").to_string();
+    let batch_dir = data_dir.join(TRASH_DIR).join(&id);
+    let files_dir = batch_dir.join("files");
+
+    let mut batch = TrashBatch {
+        id,
+        trashed_at: now.to_rfc3339(),
+        items: Vec::new(),
+        size: 0,
+    };
+    for item in selected {
+        let source = local_path(data_dir, &item.path);
+        let target = local_path(&files_dir, &item.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create trash folder: {}", e))?;
+        }
+        if let Err(e) = fs::rename(&source, &target) {
+            // Keep the record of what already moved so it can be restored
+            write_batch_info(&batch_dir, &batch)?;
+            return Err(format!("Failed to move {} to the trash: {}", item.path, e));
+        }
+        println!("Moved {} to trash batch {}", item.path, batch.id);
+        batch.size += item.size;
+        batch.items.push(item);
+    }
+    write_batch_info(&batch_dir, &batch)?;
+    Ok(batch)
+}
+
+// Trash batches, newest first
+pub fn list_trash(data_dir: &Path) -> Vec<TrashBatch> {
+    let mut batches: Vec<TrashBatch> = fs::read_dir(data_dir.join(TRASH_DIR))
+        .map(|entries| entries.flatten()
+            .filter_map(|e| read_json(&e.path().join(TRASH_INFO)))
+            .filter_map(|info| serde_json::from_value(info).ok())
+            .collect())
+        .unwrap_or_default();
+    batches.sort_by(|a, b| b.id.cmp(&a.id));
+    batches
+}
+
+// Put a batch back where it came from; the batch is removed once it is empty
+pub fn restore_from_trash(data_dir: &Path, batch_id: &str) -> Result<TrashRestore, String> {
+    // The id comes from the webview and names a folder directly inside the trash
+    let mut components = Path::new(batch_id).components();
+    if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+        return Err(format!("Invalid trash batch id: {}", batch_id));
+    }
+    let batch_dir = data_dir.join(TRASH_DIR).join(batch_id);
+    let batch: TrashBatch = read_json(&batch_dir.join(TRASH_INFO))
+        .and_then(|info| serde_json::from_value(info).ok())
+        .ok_or_else(|| format!("Trash batch not found: {}", batch_id))?;
+
+    let mut result = TrashRestore::default();
+    let mut remaining = Vec::new();
+    for item in batch.items.iter().cloned() {
+        let source = local_path(&batch_dir.join("files"), &item.path);
+        let target = local_path(data_dir, &item.path);
+        if target.exists() {
+            result.conflicts.push(item.path.clone());
+            remaining.push(item);
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        fs::rename(&source, &target).map_err(|e| format!("Failed to restore {}: {}", item.path, e))?;
+        result.restored.push(item.path);
+    }
+
+    if remaining.is_empty() {
+        fs::remove_dir_all(&batch_dir).map_err(|e| format!("Failed to remove trash batch: {}", e))?;
+    } else {
+        let size = remaining.iter().map(|i| i.size).sum();
+        write_batch_info(&batch_dir, &TrashBatch { items: remaining, size, ..batch })?;
+    }
+    println!("Restored {} items from trash batch {}", result.restored.len(), batch_id);
+    Ok(result)
+}
+
+// Permanently delete trash batches older than the given number of days (all when None).
+// Returns the number of bytes freed.
+pub fn empty_trash(data_dir: &Path, older_than_days: Option<u64>) -> Result<u64, String> {
+    let cutoff = older_than_days.map(|days| chrono::Local::now() - chrono::Duration::days(days as i64));
+    let mut freed = 0;
+    let Ok(entries) = fs::read_dir(data_dir.join(TRASH_DIR)) else {
+        return Ok(0);
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let Some(cutoff) = cutoff {
+            let trashed_at = read_json(&path.join(TRASH_INFO))
+                .and_then(|info| info.get("trashed_at").and_then(|t| t.as_str()).map(|t| t.to_string()))
+                .and_then(|t| chrono::DateTime::parse_from_rfc3339(&t).ok());
+            // Batches without readable info are kept unless the whole trash is emptied
+            if trashed_at.is_none_or(|t| t > cutoff) {
+                continue;
+            }
+        }
+        let size = size_of(&path);
+        remove_path(&path).map_err(|e| format!("Failed to delete {:?}: {}", path, e))?;
+        freed += size;
+    }
+    Ok(freed)
+}
+
+// Housekeeping run once at startup
+pub fn run_startup_cleanup(data_dir: &Path) {
+    remove_stale_temp(data_dir, STALE_TEMP_AGE);
+    match empty_trash(data_dir, Some(TRASH_RETENTION_DAYS)) {
+        Ok(freed) if freed > 0 => println!("Purged trash older than {} days ({} bytes)", TRASH_RETENTION_DAYS, freed),
+        Ok(_) => {}
+        Err(e) => println!("Failed to purge old trash: {}", e),
+    }
+}
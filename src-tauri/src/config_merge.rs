@@ -0,0 +1,527 @@
+// Structural diff and selective merge between two HomeMap config.json files
+// Floors are matched by "id" and devices by "id". Device placements are compared
+// per floor, supporting both the single-floor (floor_id/position) and the
+// multi-floor (floors array) device formats. A device that left one floor and
+// appeared on another is a single device-moved item covering both floors.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffKind {
+    FloorAdded,
+    FloorRemoved,
+    FloorChanged,
+    DeviceAdded,
+    DeviceRemoved,
+    DeviceChanged,
+    DeviceMoved,
+    PlacementAdded,
+    PlacementRemoved,
+}
+
+impl DiffKind {
+    fn key_prefix(&self) -> &'static str {
+        match self {
+            DiffKind::FloorAdded => "floor-added",
+            DiffKind::FloorRemoved => "floor-removed",
+            DiffKind::FloorChanged => "floor-changed",
+            DiffKind::DeviceAdded => "device-added",
+            DiffKind::DeviceRemoved => "device-removed",
+            DiffKind::DeviceChanged => "device-changed",
+            DiffKind::DeviceMoved => "device-moved",
+            DiffKind::PlacementAdded => "placement-added",
+            DiffKind::PlacementRemoved => "placement-removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffItem {
+    // Stable identifier used in MergeSelection.items
+    pub key: String,
+    pub kind: DiffKind,
+    pub id: String,
+    pub name: String,
+    // Floors this item touches (the floor itself, or the device placements involved;
+    // for device-moved the new floor first, then the old one when it changed)
+    #[serde(rename = "floorIds")]
+    pub floor_ids: Vec<String>,
+    // Field names that differ (floor-changed / device-changed)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub other: Option<Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigDiff {
+    pub items: Vec<DiffItem>,
+    #[serde(rename = "floorsAdded")]
+    pub floors_added: usize,
+    #[serde(rename = "floorsRemoved")]
+    pub floors_removed: usize,
+    #[serde(rename = "floorImagesChanged")]
+    pub floor_images_changed: usize,
+    #[serde(rename = "devicesAdded")]
+    pub devices_added: usize,
+    #[serde(rename = "devicesRemoved")]
+    pub devices_removed: usize,
+    #[serde(rename = "devicesMoved")]
+    pub devices_moved: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeSelection {
+    // Individual diff item keys
+    #[serde(default)]
+    pub items: Vec<String>,
+    // Floor ids: selects every diff item on these floors, restricted to these floors
+    #[serde(default)]
+    pub floors: Vec<String>,
+}
+
+// A selected diff item that could not be applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedItem {
+    pub id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub applied: Vec<String>,
+    pub skipped: Vec<SkippedItem>,
+    // Floor image paths (relative to the data directory) taken from the other config
+    #[serde(rename = "imagesNeeded")]
+    pub images_needed: Vec<String>,
+}
+
+type Placements = BTreeMap<String, Value>;
+
+fn id_key(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn item_name(value: &Value, fallback: &str) -> String {
+    value.get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or(fallback)
+        .to_string()
+}
+
+fn entries_by_id(config: &Value, field: &str) -> BTreeMap<String, Value> {
+    config.get(field)
+        .and_then(|v| v.as_array())
+        .map(|list| list.iter()
+            .filter_map(|entry| entry.get("id").and_then(id_key).map(|id| (id, entry.clone())))
+            .collect())
+        .unwrap_or_default()
+}
+
+// floor_id -> position for both device formats
+pub(crate) fn placements(device: &Value) -> Placements {
+    let mut result = BTreeMap::new();
+    if let Some(floors) = device.get("floors").and_then(|f| f.as_array()) {
+        for placement in floors {
+            if let Some(floor_id) = placement.get("floor_id").and_then(|f| f.as_str()) {
+                let position = placement.get("position").cloned().unwrap_or(Value::Null);
+                result.insert(floor_id.to_string(), position);
+            }
+        }
+    } else if let Some(floor_id) = device.get("floor_id").and_then(|f| f.as_str()) {
+        let position = device.get("position").cloned().unwrap_or(Value::Null);
+        result.insert(floor_id.to_string(), position);
+    }
+    result
+}
+
+// Write placements back using the single-floor format when there is only one
+fn set_placements(device: &mut Value, placements: &Placements) {
+    let Some(obj) = device.as_object_mut() else {
+        return;
+    };
+    obj.remove("floors");
+    obj.remove("floor_id");
+    obj.remove("position");
+
+    if placements.len() == 1 {
+        let (floor_id, position) = placements.iter().next().unwrap();
+        obj.insert("floor_id".to_string(), Value::String(floor_id.clone()));
+        obj.insert("position".to_string(), position.clone());
+    } else if !placements.is_empty() {
+        let floors = placements.iter()
+            .map(|(floor_id, position)| serde_json::json!({ "floor_id": floor_id, "position": position }))
+            .collect();
+        obj.insert("floors".to_string(), Value::Array(floors));
+    }
+}
+
+// Non-placement fields that differ between two versions of the same entry.
+// "state" is runtime data and is ignored.
+fn changed_fields(base: &Value, other: &Value, ignore: &[&str]) -> Vec<String> {
+    let empty = Map::new();
+    let base_obj = base.as_object().unwrap_or(&empty);
+    let other_obj = other.as_object().unwrap_or(&empty);
+
+    let keys: HashSet<&String> = base_obj.keys().chain(other_obj.keys()).collect();
+    let mut fields: Vec<String> = keys.into_iter()
+        .filter(|k| !ignore.contains(&k.as_str()))
+        .filter(|k| base_obj.get(*k) != other_obj.get(*k))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields
+}
+
+const PLACEMENT_FIELDS: &[&str] = &["floors", "floor_id", "position", "state"];
+
+impl DiffItem {
+    fn new(kind: DiffKind, id: &str, name: String, floor_ids: Vec<String>) -> Self {
+        let key = match kind {
+            DiffKind::DeviceMoved | DiffKind::PlacementAdded | DiffKind::PlacementRemoved => {
+                format!("{}:{}@{}", kind.key_prefix(), id, floor_ids.first().map(|s| s.as_str()).unwrap_or(""))
+            }
+            _ => format!("{}:{}", kind.key_prefix(), id),
+        };
+        DiffItem { key, kind, id: id.to_string(), name, floor_ids, fields: Vec::new(), base: None, other: None }
+    }
+
+    fn values(mut self, base: Option<Value>, other: Option<Value>) -> Self {
+        self.base = base;
+        self.other = other;
+        self
+    }
+
+    fn fields(mut self, fields: Vec<String>) -> Self {
+        self.fields = fields;
+        self
+    }
+}
+
+// Compare `base` (usually the current config) against `other` (backup or another tablet)
+pub fn diff_configs(base: &Value, other: &Value) -> ConfigDiff {
+    let mut diff = ConfigDiff::default();
+
+    let base_floors = entries_by_id(base, "floors");
+    let other_floors = entries_by_id(other, "floors");
+
+    for (id, floor) in &other_floors {
+        let name = item_name(floor, id);
+        match base_floors.get(id) {
+            None => {
+                diff.items.push(DiffItem::new(DiffKind::FloorAdded, id, name, vec![id.clone()])
+                    .values(None, Some(floor.clone())));
+                diff.floors_added += 1;
+            }
+            Some(base_floor) => {
+                let fields = changed_fields(base_floor, floor, &[]);
+                if fields.is_empty() {
+                    continue;
+                }
+                if fields.iter().any(|f| f == "image") {
+                    diff.floor_images_changed += 1;
+                }
+                diff.items.push(DiffItem::new(DiffKind::FloorChanged, id, name, vec![id.clone()])
+                    .fields(fields)
+                    .values(Some(base_floor.clone()), Some(floor.clone())));
+            }
+        }
+    }
+    for (id, floor) in &base_floors {
+        if !other_floors.contains_key(id) {
+            diff.items.push(DiffItem::new(DiffKind::FloorRemoved, id, item_name(floor, id), vec![id.clone()])
+                .values(Some(floor.clone()), None));
+            diff.floors_removed += 1;
+        }
+    }
+
+    let base_devices = entries_by_id(base, "devices");
+    let other_devices = entries_by_id(other, "devices");
+
+    for (id, device) in &other_devices {
+        let name = item_name(device, id);
+        let other_placements = placements(device);
+        let other_floor_ids: Vec<String> = other_placements.keys().cloned().collect();
+
+        let Some(base_device) = base_devices.get(id) else {
+            diff.items.push(DiffItem::new(DiffKind::DeviceAdded, id, name, other_floor_ids)
+                .values(None, Some(device.clone())));
+            diff.devices_added += 1;
+            continue;
+        };
+
+        let fields = changed_fields(base_device, device, PLACEMENT_FIELDS);
+        if !fields.is_empty() {
+            diff.items.push(DiffItem::new(DiffKind::DeviceChanged, id, name.clone(), other_floor_ids)
+                .fields(fields)
+                .values(Some(base_device.clone()), Some(device.clone())));
+        }
+
+        let base_placements = placements(base_device);
+        let added: Vec<&String> = other_placements.keys().filter(|f| !base_placements.contains_key(*f)).collect();
+        let removed: Vec<&String> = base_placements.keys().filter(|f| !other_placements.contains_key(*f)).collect();
+        if let ([to], [from]) = (added.as_slice(), removed.as_slice()) {
+            // Moved from one floor to another
+            diff.items.push(DiffItem::new(DiffKind::DeviceMoved, id, name.clone(), vec![(*to).clone(), (*from).clone()])
+                .values(base_placements.get(*from).cloned(), other_placements.get(*to).cloned()));
+            diff.devices_moved += 1;
+        }
+        let floor_change = added.len() == 1 && removed.len() == 1;
+        for (floor_id, position) in &other_placements {
+            match base_placements.get(floor_id) {
+                None if floor_change => {}
+                None => {
+                    diff.items.push(DiffItem::new(DiffKind::PlacementAdded, id, name.clone(), vec![floor_id.clone()])
+                        .values(None, Some(position.clone())));
+                }
+                Some(base_position) if base_position != position => {
+                    diff.items.push(DiffItem::new(DiffKind::DeviceMoved, id, name.clone(), vec![floor_id.clone()])
+                        .values(Some(base_position.clone()), Some(position.clone())));
+                    diff.devices_moved += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        for (floor_id, position) in &base_placements {
+            if !floor_change && !other_placements.contains_key(floor_id) {
+                diff.items.push(DiffItem::new(DiffKind::PlacementRemoved, id, name.clone(), vec![floor_id.clone()])
+                    .values(Some(position.clone()), None));
+            }
+        }
+    }
+    for (id, device) in &base_devices {
+        if !other_devices.contains_key(id) {
+            let floor_ids = placements(device).keys().cloned().collect();
+            diff.items.push(DiffItem::new(DiffKind::DeviceRemoved, id, item_name(device, id), floor_ids)
+                .values(Some(device.clone()), None));
+            diff.devices_removed += 1;
+        }
+    }
+
+    diff
+}
+
+fn array_field<'a>(config: &'a mut Value, field: &str) -> Result<&'a mut Vec<Value>, String> {
+    let obj = config.as_object_mut()
+        .ok_or("Invalid config.json: not a JSON object")?;
+    obj.entry(field)
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or_else(|| format!("Invalid config.json: '{}' field is not an array", field))
+}
+
+fn position_of(list: &[Value], id: &str) -> Option<usize> {
+    list.iter().position(|entry| entry.get("id").and_then(id_key).as_deref() == Some(id))
+}
+
+// Apply the selected diff items from `other` onto a copy of `base`
+pub fn apply_selection(base: &Value, other: &Value, selection: &MergeSelection) -> Result<(Value, MergeReport), String> {
+    let diff = diff_configs(base, other);
+    let mut merged = base.clone();
+    let mut report = MergeReport::default();
+
+    let explicit: HashSet<&str> = selection.items.iter().map(|s| s.as_str()).collect();
+    let scoped_floors: HashSet<&str> = selection.floors.iter().map(|s| s.as_str()).collect();
+
+    for key in &explicit {
+        if !diff.items.iter().any(|item| item.key == *key) {
+            report.skipped.push(SkippedItem {
+                id: key.to_string(),
+                reason: "No such difference".to_string(),
+            });
+        }
+    }
+
+    // Floors first so device placements can refer to them
+    let mut ordered: Vec<&DiffItem> = diff.items.iter().collect();
+    ordered.sort_by_key(|item| !matches!(item.kind, DiffKind::FloorAdded | DiffKind::FloorChanged));
+
+    for item in ordered {
+        let explicitly_selected = explicit.contains(item.key.as_str());
+        let floor_scope: Vec<&String> = item.floor_ids.iter()
+            .filter(|f| scoped_floors.contains(f.as_str()))
+            .collect();
+        if !explicitly_selected && floor_scope.is_empty() {
+            continue;
+        }
+        // An explicit selection applies to the whole item; a floor selection only to those floors
+        let restrict: Option<HashSet<&str>> = if explicitly_selected {
+            None
+        } else {
+            Some(floor_scope.iter().map(|f| f.as_str()).collect())
+        };
+
+        let outcome = apply_item(&mut merged, item, restrict.as_ref(), &mut report);
+        match outcome {
+            Ok(()) => report.applied.push(item.key.clone()),
+            Err(reason) => report.skipped.push(SkippedItem { id: item.key.clone(), reason }),
+        }
+    }
+
+    Ok((merged, report))
+}
+
+fn apply_item(
+    merged: &mut Value,
+    item: &DiffItem,
+    restrict: Option<&HashSet<&str>>,
+    report: &mut MergeReport,
+) -> Result<(), String> {
+    let in_scope = |floor_id: &str| restrict.map(|r| r.contains(floor_id)).unwrap_or(true);
+
+    match item.kind {
+        DiffKind::FloorAdded | DiffKind::FloorChanged => {
+            let floor = item.other.clone().ok_or("Missing floor data")?;
+            if let Some(image) = floor.get("image").and_then(|i| i.as_str()) {
+                report.images_needed.push(image.to_string());
+            }
+            let floors = array_field(merged, "floors")?;
+            match position_of(floors, &item.id) {
+                Some(index) => floors[index] = floor,
+                None => floors.push(floor),
+            }
+        }
+        DiffKind::FloorRemoved => {
+            let floors = array_field(merged, "floors")?;
+            if let Some(index) = position_of(floors, &item.id) {
+                floors.remove(index);
+            }
+            // Drop placements on the removed floor; devices left without a floor go too
+            let devices = array_field(merged, "devices")?;
+            devices.retain_mut(|device| {
+                let mut current = placements(device);
+                if current.remove(&item.id).is_some() {
+                    set_placements(device, &current);
+                    !current.is_empty()
+                } else {
+                    true
+                }
+            });
+        }
+        DiffKind::DeviceAdded => {
+            let mut device = item.other.clone().ok_or("Missing device data")?;
+            let floor_ids = floor_ids(merged);
+            let kept: Placements = placements(&device).into_iter()
+                .filter(|(floor_id, _)| floor_ids.contains(floor_id) && in_scope(floor_id))
+                .collect();
+            if kept.is_empty() {
+                return Err("None of the device's floors exist in the merged config".to_string());
+            }
+            set_placements(&mut device, &kept);
+            array_field(merged, "devices")?.push(device);
+        }
+        DiffKind::DeviceRemoved => {
+            let devices = array_field(merged, "devices")?;
+            let index = position_of(devices, &item.id).ok_or("Device no longer present")?;
+            let mut current = placements(&devices[index]);
+            current.retain(|floor_id, _| !in_scope(floor_id));
+            if current.is_empty() {
+                devices.remove(index);
+            } else {
+                set_placements(&mut devices[index], &current);
+            }
+        }
+        DiffKind::DeviceChanged => {
+            let source = item.other.as_ref().ok_or("Missing device data")?;
+            let devices = array_field(merged, "devices")?;
+            let index = position_of(devices, &item.id).ok_or("Device no longer present")?;
+            let target = devices[index].as_object_mut().ok_or("Invalid device entry")?;
+            for field in &item.fields {
+                match source.get(field) {
+                    Some(value) => target.insert(field.clone(), value.clone()),
+                    None => target.remove(field),
+                };
+            }
+        }
+        DiffKind::DeviceMoved | DiffKind::PlacementAdded => {
+            let floor_id = item.floor_ids.first().ok_or("Missing floor id")?;
+            if !floor_ids(merged).contains(floor_id) {
+                return Err(format!("Floor '{}' does not exist in the merged config", floor_id));
+            }
+            let position = item.other.clone().ok_or("Missing position")?;
+            let devices = array_field(merged, "devices")?;
+            let index = position_of(devices, &item.id).ok_or("Device no longer present")?;
+            let mut current = placements(&devices[index]);
+            current.insert(floor_id.clone(), position);
+            // A move between floors also leaves the old floor, whichever of the two is in scope
+            if let Some(from) = item.floor_ids.get(1) {
+                current.remove(from);
+            }
+            set_placements(&mut devices[index], &current);
+        }
+        DiffKind::PlacementRemoved => {
+            let floor_id = item.floor_ids.first().ok_or("Missing floor id")?;
+            let devices = array_field(merged, "devices")?;
+            let index = position_of(devices, &item.id).ok_or("Device no longer present")?;
+            let mut current = placements(&devices[index]);
+            current.remove(floor_id);
+            if current.is_empty() {
+                return Err("Device must stay on at least one floor".to_string());
+            }
+            set_placements(&mut devices[index], &current);
+        }
+    }
+
+    Ok(())
+}
+
+fn floor_ids(config: &Value) -> HashSet<String> {
+    entries_by_id(config, "floors").into_keys().collect()
+}
+
+// Load a config either from a config.json file or from a HomeMap backup zip
+// (config.json at the root or nested one folder deep, as restore_homemap_data accepts)
+pub fn read_config_source(source: &Path) -> Result<Value, String> {
+    let is_zip = source.extension().map(|e| e.eq_ignore_ascii_case("zip")).unwrap_or(false);
+
+    let content = if is_zip {
+        let bytes = read_zip_entry(source, "config.json")?
+            .ok_or("Backup does not contain a config.json")?;
+        String::from_utf8(bytes).map_err(|e| format!("config.json is not valid UTF-8: {}", e))?
+    } else {
+        fs::read_to_string(source)
+            .map_err(|e| format!("Failed to read config file: {}", e))?
+    };
+
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse config JSON: {}", e))
+}
+
+// Read a data-directory relative file from a backup zip, tolerating one level of nesting
+pub fn read_zip_entry(zip_path: &Path, relative: &str) -> Result<Option<Vec<u8>>, String> {
+    let file = fs::File::open(zip_path)
+        .map_err(|e| format!("Failed to open backup file: {}", e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("Failed to read backup archive: {}", e))?;
+
+    let nested_suffix = format!("/{}", relative);
+    let name = archive.file_names()
+        .filter(|name| *name == relative
+            || (name.ends_with(&nested_suffix) && name.matches('/').count() == relative.matches('/').count() + 1))
+        .filter(|name| !name.starts_with("__MACOSX"))
+        .min_by_key(|name| name.len())
+        .map(|name| name.to_string());
+
+    let Some(name) = name else {
+        return Ok(None);
+    };
+
+    let mut entry = archive.by_name(&name)
+        .map_err(|e| format!("Failed to read {} from archive: {}", name, e))?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read {} from archive: {}", name, e))?;
+    Ok(Some(data))
+}
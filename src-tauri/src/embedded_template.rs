@@ -0,0 +1,94 @@
+// Default data template embedded in the binary
+// build_embed_assets.rs packs every file listed in
+// homemapdata.example/asset-manifest.json into a deflate-compressed zip at
+// build time. Desktop, iOS and Android all initialize and sync their data
+// directory from this archive, so nothing depends on where (or whether) the
+// bundler placed the template folder.
+
+use crate::builtin_sync::{self, TemplateFile};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+static ARCHIVE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/homemapdata-template.zip"));
+const MANIFEST_FILE: &str = "asset-manifest.json";
+
+fn archive() -> Result<zip::ZipArchive<Cursor<&'static [u8]>>, String> {
+    zip::ZipArchive::new(Cursor::new(ARCHIVE))
+        .map_err(|e| format!("Failed to open embedded template: {}", e))
+}
+
+fn read_entry(name: &str, file: &mut impl Read) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read embedded {}: {}", name, e))?;
+    Ok(data)
+}
+
+// Read one template file, path relative to the template root with '/' separators
+pub fn read_asset(path: &str) -> Result<Vec<u8>, String> {
+    let mut archive = archive()?;
+    let mut file = archive
+        .by_name(path.trim_start_matches('/'))
+        .map_err(|_| format!("Asset not found in embedded template: {}", path))?;
+    read_entry(path, &mut file)
+}
+
+// The shipped widgets/built-in and icons/built-in files
+pub fn builtin_files() -> Result<Vec<TemplateFile>, String> {
+    let mut archive = archive()?;
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read embedded template: {}", e))?;
+        if file.is_file() && builtin_sync::is_builtin_path(file.name()) {
+            let path = file.name().to_string();
+            let data = read_entry(&path, &mut file)?;
+            files.push(TemplateFile { path, data });
+        }
+    }
+    Ok(files)
+}
+
+// Write the template files selected by `include` into dest_dir. Existing files
+// are left alone unless overwrite is set. Returns the number of files written.
+pub fn extract_matching(
+    dest_dir: &Path,
+    overwrite: bool,
+    include: impl Fn(&str) -> bool,
+) -> Result<usize, String> {
+    let mut archive = archive()?;
+    let mut written = 0;
+    for i in 0..archive.len() {
+        let mut file = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read embedded template: {}", e))?;
+        if !file.is_file() || file.name() == MANIFEST_FILE || !include(file.name()) {
+            continue;
+        }
+        let Some(rel) = file.enclosed_name() else {
+            println!("Skipping unsafe path in embedded template: {}", file.name());
+            continue;
+        };
+        let target: PathBuf = dest_dir.join(&rel);
+        if target.exists() && !overwrite {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory {:?}: {}", parent, e))?;
+        }
+        let data = read_entry(&rel.to_string_lossy(), &mut file)?;
+        fs::write(&target, data).map_err(|e| format!("Failed to write {:?}: {}", target, e))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+// Populate a data directory with the whole template, keeping existing files
+pub fn extract_to(dest_dir: &Path) -> Result<usize, String> {
+    let written = extract_matching(dest_dir, false, |_| true)?;
+    println!("Extracted {} files from embedded template to {:?}", written, dest_dir);
+    Ok(written)
+}
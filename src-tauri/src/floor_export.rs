@@ -0,0 +1,444 @@
+// Headless floor plan export
+// Composes a floor image and the current icon of every device placed on that
+// floor into an SVG document (optionally with a numbered legend) and rasterizes
+// it to PNG with resvg. Icons are resolved the same way the webview does:
+// widget definition -> iconSet (or device.params.iconSet) -> render.icon rules.
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config_merge::placements;
+use crate::icon_variants;
+use crate::widget_computed;
+use crate::widget_resolve;
+use crate::widget_runtime;
+
+pub const BUILT_IN_PACKAGE: &str = "com.fibaro.built-in";
+pub const ICON_EXTENSIONS: [&str; 4] = ["svg", "png", "jpg", "jpeg"];
+const LEGEND_ROW_HEIGHT: f64 = 28.0;
+const LEGEND_COLUMN_WIDTH: f64 = 320.0;
+const LEGEND_PADDING: f64 = 20.0;
+// Largest PNG export (about 128 MB of RGBA while rendering); bigger exports are scaled down
+const MAX_PNG_PIXELS: f64 = 32_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub legend: bool,
+    // Icon size in floor image pixels
+    #[serde(rename = "iconSize")]
+    pub icon_size: Option<f64>,
+    // Output scale factor for PNG (1.0 = floor image resolution)
+    pub scale: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportResult {
+    pub path: String,
+    pub format: ExportFormat,
+    pub width: u32,
+    pub height: u32,
+    #[serde(rename = "devicesRendered")]
+    pub devices_rendered: usize,
+    // Devices drawn with a placeholder because no icon could be resolved
+    #[serde(rename = "devicesWithoutIcon")]
+    pub devices_without_icon: Vec<String>,
+}
+
+// A widget definition together with the package it was loaded from
+pub struct DeviceWidget {
+    pub definition: Value,
+    pub package: String,
+}
+
+// Composed SVG document plus what ended up on it
+pub struct ComposedFloor {
+    pub svg: String,
+    pub width: u32,
+    pub height: u32,
+    pub devices_rendered: usize,
+    pub devices_without_icon: Vec<String>,
+}
+
+struct PlacedIcon {
+    number: usize,
+    name: String,
+    id: String,
+    x: f64,
+    y: f64,
+    icon: Option<(String, Vec<u8>)>,
+}
+
+pub fn id_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Resolve a device's widget definition (see widget_resolve for the order)
+pub fn resolve_device_widget(data_path: &Path, device: &Value) -> Option<DeviceWidget> {
+    let device_type = device.get("type").and_then(|t| t.as_str());
+    let explicit = device.get("widget").and_then(|w| w.as_str());
+    widget_resolve::resolve(data_path, device_type, explicit).map(|resolved| DeviceWidget {
+        definition: resolved.definition,
+        package: resolved.package,
+    })
+}
+
+// Widget default state overlaid with the state stored on the device and any
+// live values, plus the widget's computed properties
+pub fn effective_state(widget: &Value, device: &Value, live: Option<&Map<String, Value>>) -> Map<String, Value> {
+    let mut state = widget.get("state").and_then(|s| s.as_object()).cloned().unwrap_or_default();
+    if let Some(stored) = device.get("state").and_then(|s| s.as_object()) {
+        state.extend(stored.clone());
+    }
+    if let Some(live) = live {
+        state.extend(live.clone());
+    }
+    widget_computed::apply(widget, &mut state);
+    state
+}
+
+// (property, api path, value path) for every getter, with ${id} substituted
+pub fn getter_requests(widget: &Value, device_id: &str) -> Vec<(String, String, String)> {
+    widget.get("getters")
+        .and_then(|g| g.as_object())
+        .map(|getters| getters.iter()
+            .filter_map(|(property, getter)| {
+                let api = getter.get("api")?.as_str()?.replace("${id}", device_id);
+                let path = getter.get("path").and_then(|p| p.as_str()).unwrap_or(property).to_string();
+                Some((property.clone(), api, path))
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+// Locate an icon set folder following the webview's lookup order
+pub fn icon_set_dir(data_path: &Path, icon_set: &str, package: Option<&str>) -> Option<PathBuf> {
+    let icons = data_path.join("icons");
+    // Legacy full-path format from backups: "icons/built-in/dimLight"
+    let relative = icon_set.strip_prefix("icons/").unwrap_or(icon_set);
+    let candidates = match package {
+        Some(pkg) if pkg != BUILT_IN_PACKAGE && pkg != "legacy" => vec![
+            icons.join("packages").join(pkg).join(relative),
+        ],
+        Some(_) => vec![icons.join("built-in").join(relative), icons.join(relative)],
+        None => vec![icons.join(relative), icons.join("built-in").join(relative)],
+    };
+    candidates.into_iter().find(|dir| dir.is_dir())
+}
+
+fn find_icon_file(dir: &Path, icon_name: &str) -> Option<PathBuf> {
+    ICON_EXTENSIONS.iter()
+        .map(|ext| dir.join(format!("{}.{}", icon_name, ext)))
+        .find(|path| path.exists())
+}
+
+// Any icon from the set, so that devices without a matching condition are still shown
+fn fallback_icon_file(dir: &Path) -> Option<PathBuf> {
+    for preferred in ["off", "icon", "default"] {
+        if let Some(path) = find_icon_file(dir, preferred) {
+            return Some(path);
+        }
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| ICON_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false))
+        .collect();
+    files.sort();
+    files.into_iter().next()
+}
+
+// Resolve the icon file a device would currently show
+pub fn resolve_device_icon(data_path: &Path, device: &Value, widget: &DeviceWidget, state: &Map<String, Value>) -> Option<PathBuf> {
+    let params = device.get("params");
+    let (icon_set, package) = match params.and_then(|p| p.get("iconSet")).and_then(|s| s.as_str()) {
+        Some(set) => (set.to_string(), params.and_then(|p| p.get("iconPackage")).and_then(|p| p.as_str()).map(|s| s.to_string())),
+        None => {
+            let set = widget.definition.get("iconSet")
+                .or_else(|| widget.definition.get("render").and_then(|r| r.get("icon")).and_then(|i| i.get("set")))
+                .and_then(|s| s.as_str())?;
+            // Composed widgets name the package an inherited icon set belongs to
+            let package = widget.definition.get("iconPackage").and_then(|p| p.as_str()).unwrap_or(&widget.package);
+            (set.to_string(), Some(package.to_string()))
+        }
+    };
+
+    let dir = icon_set_dir(data_path, &icon_set, package.as_deref())?;
+    let icon = match widget_runtime::icon_name(&widget.definition, state) {
+        Some(name) => find_icon_file(&dir, &name).or_else(|| fallback_icon_file(&dir)),
+        None => fallback_icon_file(&dir),
+    }?;
+    // Tinted or badged SVG icons are drawn from their cached variant
+    let is_svg = icon.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("svg"));
+    match widget_runtime::icon_variant(&widget.definition, state).filter(|_| is_svg) {
+        Some(variant) => match icon_variants::variant_file(data_path, &icon, &variant) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                println!("Warning: using plain icon {:?}: {}", icon, e);
+                Some(icon)
+            }
+        },
+        None => Some(icon),
+    }
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("svg") => "image/svg+xml",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+fn data_uri(mime: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime, general_purpose::STANDARD.encode(bytes))
+}
+
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn position_of(value: &Value) -> Option<(f64, f64)> {
+    Some((value.get("x")?.as_f64()?, value.get("y")?.as_f64()?))
+}
+
+// Devices placed on a floor, in config order
+pub fn devices_on_floor<'a>(config: &'a Value, floor_id: &str) -> Vec<(&'a Value, (f64, f64))> {
+    config.get("devices")
+        .and_then(|d| d.as_array())
+        .map(|devices| devices.iter()
+            .filter_map(|device| placements(device)
+                .get(floor_id)
+                .and_then(position_of)
+                .map(|position| (device, position)))
+            .collect())
+        .unwrap_or_default()
+}
+
+// Build the SVG document for one floor.
+// `live_states` maps device id -> freshly fetched state values.
+pub fn compose_floor_svg(
+    data_path: &Path,
+    config: &Value,
+    floor_id: &str,
+    options: &ExportOptions,
+    live_states: &HashMap<String, Map<String, Value>>,
+) -> Result<ComposedFloor, String> {
+    let floor = config.get("floors")
+        .and_then(|f| f.as_array())
+        .and_then(|floors| floors.iter().find(|f| f.get("id").map(id_string).as_deref() == Some(floor_id)))
+        .ok_or_else(|| format!("Floor '{}' not found in config", floor_id))?;
+    let floor_name = floor.get("name").and_then(|n| n.as_str()).unwrap_or(floor_id);
+
+    // Floor image; positions are in the image's natural pixel space
+    let image = floor.get("image").and_then(|i| i.as_str()).map(|i| data_path.join(i));
+    let image_bytes = match &image {
+        Some(path) => Some(fs::read(path)
+            .map_err(|e| format!("Failed to read floor image {}: {}", path.display(), e))?),
+        None => None,
+    };
+    let (width, height) = match image_bytes.as_ref().and_then(|bytes| imagesize::blob_size(bytes).ok()) {
+        Some(size) => (size.width as f64, size.height as f64),
+        None => (
+            floor.get("width").and_then(|w| w.as_f64()).unwrap_or(1000.0),
+            floor.get("height").and_then(|h| h.as_f64()).unwrap_or(800.0),
+        ),
+    };
+
+    let icon_size = options.icon_size.unwrap_or_else(|| (width.max(height) / 40.0).max(32.0));
+
+    let mut placed = Vec::new();
+    for (index, (device, (x, y))) in devices_on_floor(config, floor_id).into_iter().enumerate() {
+        let id = device.get("id").map(id_string).unwrap_or_default();
+        let name = device.get("name").and_then(|n| n.as_str()).unwrap_or(&id).to_string();
+        let icon = resolve_device_widget(data_path, device)
+            .and_then(|widget| {
+                let state = effective_state(&widget.definition, device, live_states.get(&id));
+                resolve_device_icon(data_path, device, &widget, &state)
+            })
+            .and_then(|path| fs::read(&path).ok().map(|bytes| (mime_type(&path).to_string(), bytes)));
+        placed.push(PlacedIcon { number: index + 1, name, id, x, y, icon });
+    }
+
+    // Legend below the floor image, flowing into as many columns as fit
+    let columns = ((width - 2.0 * LEGEND_PADDING) / LEGEND_COLUMN_WIDTH).floor().max(1.0) as usize;
+    let legend_rows = (options.legend && !placed.is_empty()).then(|| placed.len().div_ceil(columns));
+    let legend_height = legend_rows
+        .map(|rows| 2.0 * LEGEND_PADDING + LEGEND_ROW_HEIGHT * (rows as f64 + 1.0))
+        .unwrap_or(0.0);
+    let total_height = height + legend_height;
+
+    let mut svg = String::new();
+    svg.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+        w = width, h = total_height
+    ));
+    svg.push_str(&format!("<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"#ffffff\"/>\n", width, total_height));
+    if let (Some(bytes), Some(path)) = (&image_bytes, &image) {
+        svg.push_str(&format!(
+            "<image x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" xlink:href=\"{}\"/>\n",
+            width, height, data_uri(mime_type(path), bytes)
+        ));
+    }
+
+    let mut without_icon = Vec::new();
+    let badge_radius = (icon_size * 0.28).max(9.0);
+    for item in &placed {
+        let left = item.x - icon_size / 2.0;
+        let top = item.y - icon_size / 2.0;
+        match &item.icon {
+            Some((mime, bytes)) => svg.push_str(&format!(
+                "<image x=\"{}\" y=\"{}\" width=\"{s}\" height=\"{s}\" xlink:href=\"{}\"><title>{}</title></image>\n",
+                left, top, data_uri(mime, bytes), xml_escape(&item.name), s = icon_size
+            )),
+            None => {
+                without_icon.push(item.id.clone());
+                svg.push_str(&format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#9e9e9e\" stroke=\"#333333\" stroke-width=\"2\"><title>{}</title></circle>\n",
+                    item.x, item.y, icon_size / 2.5, xml_escape(&item.name)
+                ));
+            }
+        }
+        if options.legend {
+            let cx = item.x + icon_size / 2.0;
+            let cy = item.y - icon_size / 2.0;
+            svg.push_str(&format!(
+                "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#1565c0\" stroke=\"#ffffff\" stroke-width=\"2\"/>\n",
+                cx, cy, badge_radius
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" font-weight=\"bold\" fill=\"#ffffff\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+                cx, cy, badge_radius * 1.1, item.number
+            ));
+        }
+    }
+
+    if let Some(rows) = legend_rows {
+        let top = height + LEGEND_PADDING;
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"20\" font-weight=\"bold\" fill=\"#000000\">{}</text>\n",
+            LEGEND_PADDING, top + 18.0, xml_escape(floor_name)
+        ));
+        for (index, item) in placed.iter().enumerate() {
+            let column = index / rows;
+            let row = index % rows;
+            let x = LEGEND_PADDING + column as f64 * LEGEND_COLUMN_WIDTH;
+            let y = top + LEGEND_ROW_HEIGHT * (row as f64 + 1.0) + 18.0;
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"16\" fill=\"#000000\">{}. {} (ID: {})</text>\n",
+                x, y, item.number, xml_escape(&item.name), xml_escape(&item.id)
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(ComposedFloor {
+        svg,
+        width: width.round() as u32,
+        height: total_height.round() as u32,
+        devices_rendered: placed.len(),
+        devices_without_icon: without_icon,
+    })
+}
+
+// Rasterize an SVG document to PNG bytes, scaled down to at most MAX_PNG_PIXELS
+pub fn render_png(svg: &str, scale: f64) -> Result<(Vec<u8>, u32, u32), String> {
+    let mut options = resvg::usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+
+    let tree = resvg::usvg::Tree::from_str(svg, &options)
+        .map_err(|e| format!("Failed to parse floor SVG: {}", e))?;
+    let size = tree.size();
+    let pixels = size.width() as f64 * size.height() as f64 * scale * scale;
+    let scale = if pixels > MAX_PNG_PIXELS {
+        let capped = scale * (MAX_PNG_PIXELS / pixels).sqrt();
+        println!("PNG export of {}x{} at scale {} is too large, using scale {:.3}", size.width(), size.height(), scale, capped);
+        capped
+    } else {
+        scale
+    };
+    let width = (size.width() as f64 * scale).ceil() as u32;
+    let height = (size.height() as f64 * scale).ceil() as u32;
+
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| format!("Failed to allocate {}x{} image", width, height))?;
+    resvg::render(&tree, resvg::tiny_skia::Transform::from_scale(scale as f32, scale as f32), &mut pixmap.as_mut());
+
+    let png = pixmap.encode_png()
+        .map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok((png, width, height))
+}
+
+// Export one floor to `output_path` as SVG or PNG
+pub fn export_floor(
+    data_path: &Path,
+    config: &Value,
+    floor_id: &str,
+    output_path: &Path,
+    options: &ExportOptions,
+    live_states: &HashMap<String, Map<String, Value>>,
+) -> Result<ExportResult, String> {
+    let composed = compose_floor_svg(data_path, config, floor_id, options, live_states)?;
+
+    let (bytes, width, height) = match options.format {
+        ExportFormat::Svg => (composed.svg.into_bytes(), composed.width, composed.height),
+        ExportFormat::Png => render_png(&composed.svg, options.scale.unwrap_or(1.0).clamp(0.1, 8.0))?,
+    };
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
+    }
+    fs::write(output_path, bytes)
+        .map_err(|e| format!("Failed to write export file: {}", e))?;
+
+    println!("Exported floor {} to {} ({}x{}, {} devices)", floor_id, output_path.display(), width, height, composed.devices_rendered);
+
+    Ok(ExportResult {
+        path: output_path.to_string_lossy().to_string(),
+        format: options.format,
+        width,
+        height,
+        devices_rendered: composed.devices_rendered,
+        devices_without_icon: composed.devices_without_icon,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_png_exports_are_scaled_down() {
+        let svg = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"20000\" height=\"10000\"></svg>";
+        let (_, width, height) = render_png(svg, 8.0).unwrap();
+        assert!((width as f64) * (height as f64) <= MAX_PNG_PIXELS * 1.001);
+        assert_eq!(width / height, 2);
+
+        let svg = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"100\"></svg>";
+        let (_, width, height) = render_png(svg, 2.0).unwrap();
+        assert_eq!((width, height), (400, 200));
+    }
+}
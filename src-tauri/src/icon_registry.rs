@@ -0,0 +1,236 @@
+// Icon set registry
+// Lists every icon set folder: icons/built-in/<set>, user sets in icons/<set>
+// and icons/packages/<package>/<set>. For each set it reports the state names
+// (file names without extension), the formats and the size of every icon, and
+// checks that SVG and PNG files decode and JPEG files have a valid header. It
+// also checks the widgets in the catalogue: every icon named in render.icon
+// must exist in the widget's icon set.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::floor_export::{self, BUILT_IN_PACKAGE, ICON_EXTENSIONS};
+use crate::widget_catalog;
+use crate::widget_compose;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IconFile {
+    // State name, i.e. the file name without extension
+    pub name: String,
+    pub format: String,
+    // Data-relative path
+    pub file: String,
+    pub bytes: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    // Why the file can't be displayed, if it can't
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IconSetEntry {
+    pub name: String,
+    // "built-in", "user" or "package: <id>", as in the icon set pickers
+    pub location: String,
+    // None for user sets in the icons/ root
+    pub package_id: Option<String>,
+    // Data-relative folder
+    pub path: String,
+    pub states: Vec<String>,
+    pub formats: Vec<String>,
+    pub icons: Vec<IconFile>,
+    pub errors: usize,
+    // Widget references whose default icons come from this set
+    pub used_by: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MissingIcon {
+    // Widget reference, as in the widget catalogue
+    pub widget: String,
+    pub icon_set: String,
+    // None when the icon set itself is missing
+    pub icon: Option<String>,
+    // JSON pointer in the widget definition
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IconRegistry {
+    pub sets: Vec<IconSetEntry>,
+    pub missing_icons: Vec<MissingIcon>,
+}
+
+fn relative(data_path: &Path, path: &Path) -> String {
+    path.strip_prefix(data_path).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+fn sub_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_dir())
+            .collect())
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+// Dimensions of a displayable icon, or why it isn't one
+fn check_icon(path: &Path, format: &str) -> Result<(u32, u32), String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    if bytes.is_empty() {
+        return Err("File is empty".to_string());
+    }
+    match format {
+        "svg" => {
+            let text = std::str::from_utf8(&bytes).map_err(|_| "SVG is not valid UTF-8".to_string())?;
+            let tree = resvg::usvg::Tree::from_str(text, &resvg::usvg::Options::default())
+                .map_err(|e| format!("Invalid SVG: {}", e))?;
+            let size = tree.size();
+            Ok((size.width().round() as u32, size.height().round() as u32))
+        }
+        "png" => {
+            let pixmap = resvg::tiny_skia::Pixmap::decode_png(&bytes).map_err(|e| format!("Invalid PNG: {}", e))?;
+            Ok((pixmap.width(), pixmap.height()))
+        }
+        _ => {
+            let size = imagesize::blob_size(&bytes).map_err(|e| format!("Invalid image: {:?}", e))?;
+            Ok((size.width as u32, size.height as u32))
+        }
+    }
+}
+
+fn icon_set(data_path: &Path, dir: &Path, location: String, package_id: Option<String>) -> IconSetEntry {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok().map(|e| e.path())).collect())
+        .unwrap_or_default();
+    files.sort();
+
+    let mut icons = Vec::new();
+    for file in files {
+        let Some(format) = file.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()) else {
+            continue;
+        };
+        if !file.is_file() || !ICON_EXTENSIONS.contains(&format.as_str()) {
+            continue;
+        }
+        let checked = check_icon(&file, &format);
+        icons.push(IconFile {
+            name: file.file_stem().and_then(|s| s.to_str()).unwrap_or_default().to_string(),
+            file: relative(data_path, &file),
+            bytes: fs::metadata(&file).map(|m| m.len()).unwrap_or(0),
+            width: checked.as_ref().ok().map(|size| size.0),
+            height: checked.as_ref().ok().map(|size| size.1),
+            error: checked.err(),
+            format,
+        });
+    }
+
+    let states: BTreeSet<String> = icons.iter().map(|icon| icon.name.clone()).collect();
+    let formats: BTreeSet<String> = icons.iter().map(|icon| icon.format.clone()).collect();
+    IconSetEntry {
+        name: dir.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string(),
+        location,
+        package_id,
+        path: relative(data_path, dir),
+        states: states.into_iter().collect(),
+        formats: formats.into_iter().collect(),
+        errors: icons.iter().filter(|icon| icon.error.is_some()).count(),
+        icons,
+        used_by: Vec::new(),
+    }
+}
+
+fn icon_sets(data_path: &Path) -> Vec<IconSetEntry> {
+    let icons = data_path.join("icons");
+    let mut sets = Vec::new();
+    for dir in sub_dirs(&icons.join("built-in")) {
+        sets.push(icon_set(data_path, &dir, "built-in".to_string(), Some(BUILT_IN_PACKAGE.to_string())));
+    }
+    for dir in sub_dirs(&icons) {
+        if !matches!(dir.file_name().and_then(|n| n.to_str()), Some("built-in") | Some("packages")) {
+            sets.push(icon_set(data_path, &dir, "user".to_string(), None));
+        }
+    }
+    for package_dir in sub_dirs(&icons.join("packages")) {
+        let package = package_dir.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        for dir in sub_dirs(&package_dir) {
+            sets.push(icon_set(data_path, &dir, format!("package: {}", package), Some(package.clone())));
+        }
+    }
+    sets
+}
+
+// Icon names used by render.icon, with their JSON pointers
+fn referenced_icons(definition: &Value) -> Vec<(String, String)> {
+    let Some(icon) = definition.get("render").and_then(|r| r.get("icon")) else {
+        return Vec::new();
+    };
+    let mut names = Vec::new();
+    if let Some(name) = icon.get("icon").and_then(|i| i.as_str()) {
+        names.push((name.to_string(), "/render/icon/icon".to_string()));
+    }
+    for (index, condition) in icon.get("conditions").and_then(|c| c.as_array()).into_iter().flatten().enumerate() {
+        if let Some(name) = condition.get("icon").and_then(|i| i.as_str()) {
+            names.push((name.to_string(), format!("/render/icon/conditions/{}/icon", index)));
+        }
+    }
+    names
+}
+
+// Every icon set and the widget icons that can't be found
+pub fn icon_registry(data_path: &Path) -> IconRegistry {
+    let mut sets = icon_sets(data_path);
+    let by_path: HashMap<String, usize> = sets.iter().enumerate().map(|(index, set)| (set.path.clone(), index)).collect();
+    let mut missing_icons = Vec::new();
+
+    for widget in widget_catalog::list_widgets(data_path) {
+        let file = data_path.join(&widget.file);
+        let Some(definition) = fs::read_to_string(&file)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            .and_then(|definition| widget_compose::compose(data_path, &widget.package, Some(&file), &definition).ok())
+            .map(|composition| composition.definition) else {
+            continue;
+        };
+        let Some(set_name) = widget.icon_set.clone() else {
+            continue;
+        };
+        let package = definition.get("iconPackage").and_then(|p| p.as_str()).unwrap_or(&widget.package);
+        let set_pointer = if definition.get("iconSet").is_some() { "/iconSet" } else { "/render/icon/set" };
+
+        let set = floor_export::icon_set_dir(data_path, &set_name, Some(package))
+            .and_then(|dir| by_path.get(&relative(data_path, &dir)).copied());
+        let Some(set) = set else {
+            missing_icons.push(MissingIcon {
+                widget: widget.reference.clone(),
+                icon_set: set_name,
+                icon: None,
+                path: set_pointer.to_string(),
+            });
+            continue;
+        };
+
+        sets[set].used_by.push(widget.reference.clone());
+        for (icon, path) in referenced_icons(&definition) {
+            if !sets[set].states.contains(&icon) {
+                missing_icons.push(MissingIcon {
+                    widget: widget.reference.clone(),
+                    icon_set: set_name.clone(),
+                    icon: Some(icon),
+                    path,
+                });
+            }
+        }
+    }
+
+    IconRegistry { sets, missing_icons }
+}
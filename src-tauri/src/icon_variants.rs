@@ -0,0 +1,317 @@
+// Icon variants
+// Generates tinted and badged versions of SVG icons so widgets can colour an
+// icon from parameters instead of shipping extra files:
+//   tint   replaces every fill, stroke and stop-color except none, dark
+//          outline colours and white highlights with one colour
+//   badge  draws a coloured dot (optionally with a short text) in a corner
+// Variants are written once to cache/icon-variants/<hash>.svg; the hash covers
+// the source file (path, size and modification time) and the variant, so an
+// edited icon gets a new variant. Tints can come from device state, so the
+// cache keeps at most MAX_CACHED_VARIANTS files. A cache hit updates the
+// file's modification time, so the least recently used are deleted first and
+// are generated again if needed.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cleanup::CACHE_DIR;
+use crate::floor_export::xml_escape;
+
+// Colours darker than this are outlines and keep their colour
+const OUTLINE_LUMINANCE: f64 = 0.15;
+// Colours lighter than this are highlights and keep their colour
+const HIGHLIGHT_LUMINANCE: f64 = 0.95;
+// Badge diameter relative to the icon's shorter side
+const BADGE_SIZE: f64 = 0.45;
+pub const BADGE_POSITIONS: [&str; 4] = ["top-right", "top-left", "bottom-right", "bottom-left"];
+const PAINT_PROPERTIES: [&str; 3] = ["fill", "stroke", "stop-color"];
+const MAX_CACHED_VARIANTS: usize = 500;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct IconVariant {
+    // CSS colour: #rgb, #rrggbb, rgb(r, g, b) or a basic colour name
+    pub tint: Option<String>,
+    pub badge: Option<Badge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Badge {
+    pub color: String,
+    // A few characters shown in the badge, e.g. "!" or "3"
+    pub text: Option<String>,
+    // "top-right" (default), "top-left", "bottom-right" or "bottom-left"
+    pub position: Option<String>,
+}
+
+impl IconVariant {
+    pub fn is_empty(&self) -> bool {
+        self.tint.is_none() && self.badge.is_none()
+    }
+}
+
+pub fn parse_color(text: &str) -> Option<(u8, u8, u8)> {
+    let text = text.trim().to_lowercase();
+    if let Some(hex) = text.strip_prefix('#') {
+        let digits: Vec<u8> = hex.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect::<Option<_>>()?;
+        return match digits.len() {
+            3 => Some((digits[0] * 17, digits[1] * 17, digits[2] * 17)),
+            6 => Some((digits[0] * 16 + digits[1], digits[2] * 16 + digits[3], digits[4] * 16 + digits[5])),
+            _ => None,
+        };
+    }
+    if let Some(args) = text.strip_prefix("rgb(").and_then(|t| t.strip_suffix(')')) {
+        let parts: Vec<u8> = args
+            .split(',')
+            .map(|p| p.trim().parse::<f64>().ok().map(|n| n.clamp(0.0, 255.0).round() as u8))
+            .collect::<Option<_>>()?;
+        return (parts.len() == 3).then(|| (parts[0], parts[1], parts[2]));
+    }
+    match text.as_str() {
+        "black" => Some((0, 0, 0)),
+        "white" => Some((255, 255, 255)),
+        "red" => Some((255, 0, 0)),
+        "green" => Some((0, 128, 0)),
+        "blue" => Some((0, 0, 255)),
+        "yellow" => Some((255, 255, 0)),
+        "orange" => Some((255, 165, 0)),
+        "gray" | "grey" => Some((128, 128, 128)),
+        _ => None,
+    }
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Relative luminance (0 black .. 1 white)
+fn luminance((r, g, b): (u8, u8, u8)) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    0.2126 * channel(r) + 0.7152 * channel(g) + 0.0722 * channel(b)
+}
+
+// Replace the paint colours in attributes (fill="..."), style attributes and
+// <style> rules (fill: ...) with `tint`
+fn recolor(svg: &str, tint: (u8, u8, u8)) -> String {
+    let tint = hex(tint);
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg;
+    'scan: while !rest.is_empty() {
+        for property in PAINT_PROPERTIES {
+            let Some(after) = rest.strip_prefix(property) else {
+                continue;
+            };
+            let preceded = out.chars().last().is_none_or(|c| !(c.is_alphanumeric() || c == '-'));
+            let trimmed = after.trim_start();
+            let (value_start, terminators): (&str, &[char]) = match trimmed.chars().next() {
+                Some('=') => {
+                    let quoted = trimmed[1..].trim_start();
+                    match quoted.chars().next() {
+                        Some(q @ ('"' | '\'')) => (&quoted[1..], if q == '"' { &['"'] } else { &['\''] }),
+                        _ => continue,
+                    }
+                }
+                Some(':') => (trimmed[1..].trim_start(), &[';', '"', '\'', '}', '<']),
+                _ => continue,
+            };
+            if !preceded {
+                continue;
+            }
+            // Whitespace before the terminator stays where it is
+            let end = value_start.find(terminators).unwrap_or(value_start.len());
+            let end = value_start[..end].trim_end().len();
+            let value = &value_start[..end];
+            let keep = parse_color(value).is_none_or(|color| {
+                let light = luminance(color);
+                !(OUTLINE_LUMINANCE..=HIGHLIGHT_LUMINANCE).contains(&light)
+            });
+            let prefix_len = rest.len() - value_start.len();
+            out.push_str(&rest[..prefix_len]);
+            out.push_str(if keep { value } else { &tint });
+            rest = &value_start[end..];
+            continue 'scan;
+        }
+        let next = rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+        out.push_str(&rest[..next]);
+        rest = &rest[next..];
+    }
+    out
+}
+
+// The SVG without its XML declaration and doctype, so it can be nested
+fn strip_prolog(svg: &str) -> &str {
+    let mut rest = svg.trim_start();
+    while rest.starts_with("<?") || rest.starts_with("<!DOCTYPE") || rest.starts_with("<!--") {
+        let close = if rest.starts_with("<!--") { "-->" } else { ">" };
+        match rest.find(close) {
+            Some(end) => rest = rest[end + close.len()..].trim_start(),
+            None => break,
+        }
+    }
+    rest
+}
+
+// Nest the icon in a new document of the same size and draw the badge on top
+fn add_badge(svg: &str, badge: &Badge) -> Result<String, String> {
+    let color = parse_color(&badge.color).ok_or_else(|| format!("Invalid badge colour '{}'", badge.color))?;
+    let tree = resvg::usvg::Tree::from_str(svg, &resvg::usvg::Options::default())
+        .map_err(|e| format!("Invalid SVG: {}", e))?;
+    let (width, height) = (tree.size().width() as f64, tree.size().height() as f64);
+
+    let radius = width.min(height) * BADGE_SIZE / 2.0;
+    let (cx, cy) = match badge.position.as_deref().unwrap_or("top-right") {
+        "top-left" => (radius, radius),
+        "bottom-left" => (radius, height - radius),
+        "bottom-right" => (width - radius, height - radius),
+        "top-right" => (width - radius, radius),
+        other => return Err(format!("Invalid badge position '{}'", other)),
+    };
+    let text_color = if luminance(color) > 0.5 { "#000000" } else { "#ffffff" };
+    let text = badge.text.as_deref().filter(|t| !t.is_empty()).map(|text| format!(
+        r#"<text x="{cx}" y="{cy}" fill="{text_color}" font-family="sans-serif" font-weight="bold" font-size="{size}" text-anchor="middle" dominant-baseline="central">{}</text>"#,
+        xml_escape(text),
+        size = radius * 1.3,
+    )).unwrap_or_default();
+
+    Ok(format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">{icon}<circle cx="{cx}" cy="{cy}" r="{radius}" fill="{fill}" stroke="#ffffff" stroke-width="{outline}"/>{text}</svg>"##,
+        icon = strip_prolog(svg),
+        fill = hex(color),
+        outline = radius * 0.2,
+    ))
+}
+
+// Apply a variant to SVG source
+pub fn apply(svg: &str, variant: &IconVariant) -> Result<String, String> {
+    let mut result = svg.to_string();
+    if let Some(tint) = &variant.tint {
+        let color = parse_color(tint).ok_or_else(|| format!("Invalid tint colour '{}'", tint))?;
+        result = recolor(&result, color);
+    }
+    if let Some(badge) = &variant.badge {
+        result = add_badge(&result, badge)?;
+    }
+    resvg::usvg::Tree::from_str(&result, &resvg::usvg::Options::default())
+        .map_err(|e| format!("Failed to build icon variant: {}", e))?;
+    Ok(result)
+}
+
+// Path of the variant of an SVG icon, generating it if it isn't cached yet
+pub fn variant_file(data_path: &Path, icon: &Path, variant: &IconVariant) -> Result<PathBuf, String> {
+    if icon.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() != Some("svg") {
+        return Err(format!("Only SVG icons have variants: {:?}", icon));
+    }
+    let metadata = fs::metadata(icon).map_err(|e| format!("Failed to read icon {:?}: {}", icon, e))?;
+    let modified = metadata.modified().ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let spec = serde_json::to_string(variant).map_err(|e| format!("Failed to serialize icon variant: {}", e))?;
+    let key = format!("{}\n{}\n{}\n{}", icon.strip_prefix(data_path).unwrap_or(icon).display(), metadata.len(), modified, spec);
+    let hash: String = Sha256::digest(key.as_bytes()).iter().take(12).map(|b| format!("{:02x}", b)).collect();
+
+    let dir = data_path.join(CACHE_DIR).join("icon-variants");
+    let file = dir.join(format!("{}.svg", hash));
+    if file.exists() {
+        if let Err(e) = touch(&file) {
+            println!("Failed to update {:?}: {}", file, e);
+        }
+        return Ok(file);
+    }
+    let source = fs::read_to_string(icon).map_err(|e| format!("Failed to read icon {:?}: {}", icon, e))?;
+    let svg = apply(&source, variant)?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    fs::write(&file, svg).map_err(|e| format!("Failed to write icon variant {:?}: {}", file, e))?;
+    println!("Generated icon variant {:?} from {:?}", file, icon);
+    evict(&dir, &file, MAX_CACHED_VARIANTS);
+    Ok(file)
+}
+
+// Mark a cached variant as just used
+fn touch(file: &Path) -> std::io::Result<()> {
+    fs::File::options().write(true).open(file)?.set_modified(SystemTime::now())
+}
+
+// Delete the least recently used cached variants other than `current` until at
+// most `keep` are left
+fn evict(dir: &Path, current: &Path, keep: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|e| e.path() != current)
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    // `current` is one of the files kept
+    if files.len() < keep {
+        return;
+    }
+    files.sort();
+    let excess = files.len() + 1 - keep;
+    for (_, path) in files.into_iter().take(excess) {
+        if let Err(e) = fs::remove_file(&path) {
+            println!("Failed to remove cached icon variant {:?}: {}", path, e);
+        }
+    }
+    println!("Removed {} old icon variants from the cache", excess);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: (u8, u8, u8) = (255, 0, 0);
+
+    #[test]
+    fn recolors_attributes() {
+        let svg = r##"<path fill="#4a90d9" stroke='#808080'/>"##;
+        assert_eq!(recolor(svg, RED), r##"<path fill="#ff0000" stroke='#ff0000'/>"##);
+    }
+
+    #[test]
+    fn recolors_style_attributes() {
+        let svg = r##"<rect style="fill:#4a90d9; stroke: rgb(128, 128, 128);opacity:0.5"/>"##;
+        assert_eq!(recolor(svg, RED), r##"<rect style="fill:#ff0000; stroke: #ff0000;opacity:0.5"/>"##);
+    }
+
+    #[test]
+    fn recolors_style_elements() {
+        let svg = r##"<style>.a { fill: #4a90d9 } .b{stop-color:orange}</style>"##;
+        assert_eq!(recolor(svg, RED), r##"<style>.a { fill: #ff0000 } .b{stop-color:#ff0000}</style>"##);
+    }
+
+    #[test]
+    fn leaves_similar_names_alone() {
+        let svg = r##"<g id="fill" fill-opacity="0.5" data-fill="#4a90d9"><path class="fill"/></g>"##;
+        assert_eq!(recolor(svg, RED), svg);
+    }
+
+    #[test]
+    fn keeps_none_outlines_and_highlights() {
+        let svg = r##"<path fill="none" stroke="#000000"/><path fill="#ffffff" stroke="#222"/><path fill="url(#g)"/>"##;
+        assert_eq!(recolor(svg, RED), svg);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_variants() {
+        let dir = std::env::temp_dir().join(format!("icon-variants-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 0..4 {
+            fs::write(dir.join(format!("{}.svg", i)), "<svg/>").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        // The oldest file was used since, so the next oldest goes instead
+        touch(&dir.join("0.svg")).unwrap();
+        evict(&dir, &dir.join("3.svg"), 3);
+        let mut left: Vec<String> = fs::read_dir(&dir).unwrap().flatten().map(|e| e.file_name().to_string_lossy().to_string()).collect();
+        left.sort();
+        assert_eq!(left, vec!["0.svg", "2.svg", "3.svg"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
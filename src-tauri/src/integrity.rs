@@ -0,0 +1,399 @@
+// Data directory integrity check
+// Walks a homemapdata folder against config.json, installed-packages.json and
+// widget-mappings.json and reports everything that would leave the map half
+// working: missing folders, floors without an image, devices whose widget or
+// icon set cannot be found, registry entries whose files are gone, and so on.
+// Each issue carries a suggested fix. Repair mode only applies fixes that never
+// touch user content: creating missing folders and registry files, restoring
+// missing built-ins from the embedded template, and dropping registry or
+// mapping entries that point at files that no longer exist. config.json is
+// never modified.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::builtin_sync;
+use crate::config_merge::placements;
+use crate::embedded_template;
+use crate::floor_export::{self, id_string};
+
+const REQUIRED_DIRS: [&str; 7] = [
+    "widgets",
+    "widgets/built-in",
+    "widgets/packages",
+    "icons",
+    "icons/built-in",
+    "icons/packages",
+    "images",
+];
+const PACKAGES_FILE: &str = "installed-packages.json";
+const MAPPINGS_FILE: &str = "widget-mappings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    // Something the user will see as broken
+    Error,
+    // Inconsistent but harmless, or only affects looks
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityIssue {
+    // Machine-readable kind, e.g. "missing-floor-image"
+    pub code: String,
+    pub severity: Severity,
+    // Data-relative path (or config entry) the issue is about
+    pub subject: String,
+    pub message: String,
+    pub suggestion: String,
+    // A safe automatic fix exists
+    pub repairable: bool,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub data_dir: String,
+    pub checked_at: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub repaired: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+// Safe fixes, applied only in repair mode
+enum Fix {
+    CreateDir(PathBuf),
+    WriteDefault(PathBuf, Value),
+    RestoreTemplateFile(String),
+    RestoreBuiltins,
+    RemovePackageEntry(String),
+    RemoveMapping(String),
+}
+
+struct Check {
+    data_dir: PathBuf,
+    issues: Vec<(IntegrityIssue, Option<Fix>)>,
+}
+
+impl Check {
+    fn report(&mut self, severity: Severity, code: &str, subject: impl Into<String>, message: String, suggestion: &str, fix: Option<Fix>) {
+        self.issues.push((
+            IntegrityIssue {
+                code: code.to_string(),
+                severity,
+                subject: subject.into(),
+                message,
+                suggestion: suggestion.to_string(),
+                repairable: fix.is_some(),
+                repaired: false,
+            },
+            fix,
+        ));
+    }
+
+    // Data-relative path with '/' separators; absolute paths are used as they are
+    fn path(&self, rel: &str) -> PathBuf {
+        if Path::new(rel).is_absolute() {
+            return PathBuf::from(rel);
+        }
+        rel.split('/').fold(self.data_dir.clone(), |path, part| path.join(part))
+    }
+
+    // Read a JSON file, reporting it when missing or unreadable
+    fn read_json(&mut self, rel: &str, default: Option<Value>) -> Option<Value> {
+        let path = self.path(rel);
+        if !path.exists() {
+            let fix = match default {
+                Some(value) => Some(Fix::WriteDefault(path, value)),
+                None => Some(Fix::RestoreTemplateFile(rel.to_string())),
+            };
+            self.report(Severity::Error, "missing-file", rel, format!("{} is missing", rel),
+                "Recreate it (an empty registry, or the example config.json)", fix);
+            return None;
+        }
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<Value>(&content).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.report(Severity::Error, "invalid-json", rel, format!("{} cannot be read: {}", rel, e),
+                    "Fix the JSON by hand or restore the file from a backup", None);
+                None
+            }
+        }
+    }
+}
+
+fn default_packages() -> Value {
+    serde_json::json!({ "version": "1.0", "packages": {} })
+}
+
+fn default_mappings() -> Value {
+    serde_json::json!({ "version": "1.0", "mappings": {}, "defaults": {} })
+}
+
+fn check_directories(check: &mut Check) {
+    for dir in REQUIRED_DIRS {
+        let path = check.path(dir);
+        if !path.is_dir() {
+            check.report(Severity::Error, "missing-directory", dir, format!("Folder {} is missing", dir),
+                "Create the folder", Some(Fix::CreateDir(path)));
+        }
+    }
+}
+
+fn check_builtins(check: &mut Check) {
+    let files = match embedded_template::builtin_files() {
+        Ok(files) => files,
+        Err(e) => {
+            println!("Integrity check: cannot read embedded template: {}", e);
+            return;
+        }
+    };
+    let missing: Vec<String> = files.iter()
+        .map(|f| f.path.clone())
+        .filter(|path| !check.path(path).exists())
+        .collect();
+    if !missing.is_empty() {
+        check.report(Severity::Warning, "missing-builtin", missing.join(", "),
+            format!("{} built-in widget or icon files are missing", missing.len()),
+            "Restore them from the copy shipped with the app", Some(Fix::RestoreBuiltins));
+    }
+}
+
+fn check_floors(check: &mut Check, config: &Value) -> HashSet<String> {
+    let mut floor_ids = HashSet::new();
+    let floors = config.get("floors").and_then(|f| f.as_array()).cloned().unwrap_or_default();
+    if floors.is_empty() {
+        check.report(Severity::Warning, "no-floors", "config.json", "config.json has no floors".to_string(),
+            "Add a floor in Floor Management", None);
+    }
+    for floor in &floors {
+        let id = floor.get("id").map(id_string).unwrap_or_default();
+        let name = floor.get("name").and_then(|n| n.as_str()).unwrap_or(&id).to_string();
+        if id.is_empty() {
+            check.report(Severity::Error, "floor-without-id", format!("floor '{}'", name),
+                format!("Floor '{}' has no id", name), "Give the floor an id in config.json", None);
+            continue;
+        }
+        if !floor_ids.insert(id.clone()) {
+            check.report(Severity::Error, "duplicate-floor-id", format!("floor '{}'", id),
+                format!("More than one floor uses the id '{}'", id),
+                "Rename one of the floors' ids in config.json", None);
+        }
+        match floor.get("image").and_then(|i| i.as_str()) {
+            Some(image) if !image.is_empty() => {
+                if !check.path(image).exists() {
+                    check.report(Severity::Error, "missing-floor-image", image,
+                        format!("Floor '{}' uses image {} which does not exist", name, image),
+                        "Pick a new image for the floor in Floor Management, or put the file back", None);
+                }
+            }
+            _ => check.report(Severity::Error, "floor-without-image", format!("floor '{}'", id),
+                format!("Floor '{}' has no image", name), "Pick an image for the floor in Floor Management", None),
+        }
+    }
+    floor_ids
+}
+
+fn check_devices(check: &mut Check, config: &Value, floor_ids: &HashSet<String>) {
+    let devices = config.get("devices").and_then(|d| d.as_array()).cloned().unwrap_or_default();
+    let mut device_ids = HashSet::new();
+    for device in &devices {
+        let id = device.get("id").map(id_string).unwrap_or_default();
+        let name = device.get("name").and_then(|n| n.as_str()).unwrap_or(&id).to_string();
+        let subject = format!("device {} '{}'", id, name);
+
+        if !device_ids.insert(id.clone()) {
+            check.report(Severity::Warning, "duplicate-device", subject.clone(),
+                format!("Device {} appears more than once in config.json", id),
+                "Remove the extra entry in Device Management", None);
+        }
+
+        let device_placements = placements(device);
+        if device_placements.is_empty() {
+            check.report(Severity::Warning, "unplaced-device", subject.clone(),
+                format!("Device '{}' is not placed on any floor", name),
+                "Drag it onto a floor or remove it in Device Management", None);
+        }
+        for floor_id in device_placements.keys().filter(|f| !floor_ids.contains(*f)) {
+            check.report(Severity::Error, "unknown-floor", subject.clone(),
+                format!("Device '{}' is placed on floor '{}' which does not exist", name, floor_id),
+                "Move the device to an existing floor in Device Management", None);
+        }
+
+        if let Some(reference) = device.get("widget").and_then(|w| w.as_str()) {
+            let exists = reference.split_once('/')
+                .map(|(package, widget)| check.path(&format!("widgets/packages/{}/{}.json", package, widget)).exists())
+                .unwrap_or(false);
+            if !exists {
+                check.report(Severity::Error, "missing-widget", subject.clone(),
+                    format!("Device '{}' uses widget {} which is not installed", name, reference),
+                    "Install the package that provides it, or pick another widget for the device", None);
+            }
+        }
+
+        let device_type = device.get("type").and_then(|t| t.as_str()).unwrap_or_default();
+        let Some(widget) = floor_export::resolve_device_widget(&check.data_dir, device) else {
+            check.report(Severity::Error, "unknown-widget-type", subject.clone(),
+                format!("No widget found for device '{}' of type '{}'", name, device_type),
+                "Install a package with this widget type, or change the device type in Device Management", None);
+            continue;
+        };
+
+        let state = floor_export::effective_state(&widget.definition, device, None);
+        if floor_export::resolve_device_icon(&check.data_dir, device, &widget, &state).is_none() {
+            check.report(Severity::Warning, "missing-icon-set", subject,
+                format!("No icon could be found for device '{}' (type '{}')", name, device_type),
+                "Check the widget's icon set, or choose another icon set for the device", None);
+        }
+    }
+}
+
+fn check_packages(check: &mut Check, registry: &Value) {
+    let packages = registry.get("packages").and_then(|p| p.as_object()).cloned().unwrap_or_default();
+    for (id, info) in &packages {
+        let package_dir = format!("widgets/packages/{}", id);
+        if !check.path(&package_dir).is_dir() {
+            check.report(Severity::Error, "package-files-missing", package_dir,
+                format!("Package {} is registered but its folder is gone", id),
+                "Remove the package from the registry (reinstall it to get it back)",
+                Some(Fix::RemovePackageEntry(id.clone())));
+            continue;
+        }
+        let files = info.get("files");
+        for kind in ["widgets", "icons"] {
+            let listed = files.and_then(|f| f.get(kind)).and_then(|w| w.as_array()).cloned().unwrap_or_default();
+            for rel in listed.iter().filter_map(|f| f.as_str()) {
+                if !check.path(rel.trim_end_matches('/')).exists() {
+                    check.report(Severity::Error, "package-file-missing", rel,
+                        format!("{} of package {} is missing", rel, id),
+                        "Reinstall the package", None);
+                }
+            }
+        }
+    }
+
+    // Package folders nobody registered
+    for dir in ["widgets/packages", "icons/packages"] {
+        let Ok(entries) = fs::read_dir(check.path(dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() && !name.starts_with('.') && !packages.contains_key(&name) {
+                check.report(Severity::Warning, "unregistered-package", format!("{}/{}", dir, name),
+                    format!("{}/{} is not in {}", dir, name, PACKAGES_FILE),
+                    "Reinstall the package, or delete the folder if it is no longer used", None);
+            }
+        }
+    }
+}
+
+fn check_mappings(check: &mut Check, mappings: &Value) {
+    let entries = mappings.get("mappings").and_then(|m| m.as_object()).cloned().unwrap_or_default();
+    for (device_type, mapping) in &entries {
+        let package = mapping.get("package").and_then(|p| p.as_str()).unwrap_or_default();
+        let widget = mapping.get("widget").and_then(|w| w.as_str()).unwrap_or(device_type);
+        let rel = format!("widgets/packages/{}/{}.json", package, widget);
+        if !check.path(&rel).exists() {
+            check.report(Severity::Warning, "stale-mapping", format!("{} -> {}", device_type, rel),
+                format!("Widget mapping for '{}' points at {}, which does not exist", device_type, rel),
+                "Remove the mapping so the default widget is used again",
+                Some(Fix::RemoveMapping(device_type.clone())));
+        }
+    }
+}
+
+fn update_json(path: &Path, update: impl FnOnce(&mut Value)) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let mut value: Value = serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+    update(&mut value);
+    let json = serde_json::to_string_pretty(&value).map_err(|e| format!("Failed to serialize {:?}: {}", path, e))?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+}
+
+fn apply(data_dir: &Path, fix: &Fix) -> Result<(), String> {
+    match fix {
+        Fix::CreateDir(path) => fs::create_dir_all(path)
+            .map_err(|e| format!("Failed to create {:?}: {}", path, e)),
+        Fix::WriteDefault(path, value) => {
+            let json = serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize: {}", e))?;
+            fs::write(path, json).map_err(|e| format!("Failed to write {:?}: {}", path, e))
+        }
+        Fix::RestoreTemplateFile(rel) => {
+            embedded_template::extract_matching(data_dir, false, |path| path == rel).map(|_| ())
+        }
+        Fix::RestoreBuiltins => {
+            embedded_template::extract_matching(data_dir, false, builtin_sync::is_builtin_path).map(|_| ())
+        }
+        Fix::RemovePackageEntry(id) => update_json(&data_dir.join(PACKAGES_FILE), |registry| {
+            if let Some(packages) = registry.get_mut("packages").and_then(|p| p.as_object_mut()) {
+                packages.remove(id);
+            }
+        }),
+        Fix::RemoveMapping(device_type) => update_json(&data_dir.join(MAPPINGS_FILE), |mappings| {
+            if let Some(entries) = mappings.get_mut("mappings").and_then(|m| m.as_object_mut()) {
+                entries.remove(device_type);
+            }
+        }),
+    }
+}
+
+pub fn check_data_dir(data_dir: &Path, repair: bool) -> Result<IntegrityReport, String> {
+    if !data_dir.is_dir() {
+        return Err(format!("Data directory does not exist: {:?}", data_dir));
+    }
+    let mut check = Check {
+        data_dir: data_dir.to_path_buf(),
+        issues: Vec::new(),
+    };
+
+    check_directories(&mut check);
+    check_builtins(&mut check);
+    if let Some(config) = check.read_json("config.json", None) {
+        let floor_ids = check_floors(&mut check, &config);
+        check_devices(&mut check, &config, &floor_ids);
+    }
+    if let Some(registry) = check.read_json(PACKAGES_FILE, Some(default_packages())) {
+        check_packages(&mut check, &registry);
+    }
+    if let Some(mappings) = check.read_json(MAPPINGS_FILE, Some(default_mappings())) {
+        check_mappings(&mut check, &mappings);
+    }
+
+    let mut issues = Vec::new();
+    for (mut issue, fix) in check.issues {
+        if let (true, Some(fix)) = (repair, &fix) {
+            match apply(data_dir, fix) {
+                Ok(()) => {
+                    println!("Repaired {}: {}", issue.code, issue.subject);
+                    issue.repaired = true;
+                }
+                Err(e) => println!("Failed to repair {} ({}): {}", issue.code, issue.subject, e),
+            }
+        }
+        issues.push(issue);
+    }
+
+    let count = |severity: Severity| issues.iter().filter(|i| i.severity == severity && !i.repaired).count();
+    let report = IntegrityReport {
+        data_dir: data_dir.to_string_lossy().to_string(),
+        checked_at: chrono::Local::now().to_rfc3339(),
+        errors: count(Severity::Error),
+        warnings: count(Severity::Warning),
+        repaired: issues.iter().filter(|i| i.repaired).count(),
+        issues,
+    };
+    println!(
+        "Integrity check of {:?}: {} errors, {} warnings, {} repaired",
+        data_dir, report.errors, report.warnings, report.repaired
+    );
+    Ok(report)
+}
@@ -2,15 +2,40 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod websocket;
+mod app_state;
+mod automap;
+mod builtin_sync;
+mod cleanup;
+mod config_merge;
+mod embedded_template;
+mod floor_export;
+mod icon_registry;
+mod icon_variants;
+mod integrity;
+mod package_extract;
+mod package_install;
+mod package_signing;
+mod portable;
+mod relocate;
+mod schemas;
+mod settings_layers;
+mod vault;
+mod widget_catalog;
+mod widget_compose;
+mod widget_computed;
+mod widget_eval;
+mod widget_lint;
+mod widget_resolve;
+mod widget_runtime;
 
 use tauri::Manager;
+use app_state::AppState;
 #[cfg(not(any(target_os = "ios", target_os = "android")))]
 use tauri::menu::{Menu, MenuItemBuilder, PredefinedMenuItem, SubmenuBuilder};
-#[cfg(not(any(target_os = "ios", target_os = "android")))]
 use tauri::Emitter;
 use serde::{Deserialize, Serialize};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use std::collections::HashMap;
 use base64::Engine;
@@ -82,6 +107,8 @@ async fn http_fetch_insecure(
 struct HC3Config {
     host: String,
     user: String,
+    // Only used by the backend when talking to the HC3; never sent to the webview
+    #[serde(skip_serializing, default)]
     password: String,
     protocol: String,
 }
@@ -99,93 +126,198 @@ fn is_mobile_platform() -> bool {
 }
 
 #[tauri::command]
-fn get_hc3_config() -> Result<HC3Config, String> {
-    // First priority: Try to load from settings.json
-    if let Ok(Some(settings)) = load_app_settings() {
-        if !settings.hc3_host.is_empty() {
-            println!("Using HC3 config from settings.json");
-            return Ok(HC3Config {
-                host: settings.hc3_host,
-                user: settings.hc3_user,
-                password: settings.hc3_password,
-                protocol: settings.hc3_protocol,
-            });
-        }
+fn get_hc3_config(state: tauri::State<'_, AppState>) -> Result<HC3Config, String> {
+    Ok(hc3_config(&*state.settings()?))
+}
+
+fn hc3_config(resolved: &settings_layers::ResolvedConfig) -> HC3Config {
+    HC3Config {
+        host: resolved.value(settings_layers::HC3_HOST),
+        user: resolved.value(settings_layers::HC3_USER),
+        password: resolved.value(settings_layers::HC3_PASSWORD),
+        protocol: resolved.value(settings_layers::HC3_PROTOCOL),
+    }
+}
+
+// Relative paths in settings.json (as written by portable installs) are relative to its folder
+fn resolve_settings_relative(path: &str) -> Result<String, String> {
+    if path.is_empty() || std::path::Path::new(path).is_absolute() {
+        return Ok(path.to_string());
     }
+    Ok(settings_dir()?.join(path).to_string_lossy().to_string())
+}
+
+// Resolve the connection settings and data directory from all layers
+// (defaults, .env files, environment, settings.json, command line)
+fn resolve_settings() -> Result<settings_layers::ResolvedConfig, String> {
+    let mut layers = vec![settings_layers::defaults_layer(&default_homemap_path()?)];
+    layers.extend(settings_layers::env_file_layers());
+    layers.push(settings_layers::process_env_layer());
     
-    // Second priority: Try to load from .env file in the app's directory
-    if let Ok(exe_path) = env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            let env_path = exe_dir.join(".env");
-            if env_path.exists() {
-                println!("Loaded .env from: {:?}", env_path);
-                let _ = dotenvy::from_path(&env_path);
+    if let Ok(Some(settings)) = load_app_settings() {
+        let profile = settings.effective();
+        let password = match vault_password(&profile.name) {
+            Ok(Some(password)) => password,
+            // Not migrated yet (e.g. the vault is still locked)
+            Ok(None) => profile.hc3_password.clone(),
+            Err(e) => {
+                println!("Failed to read HC3 password from vault: {}", e);
+                profile.hc3_password.clone()
             }
-        }
+        };
+        layers.push(settings_layers::Layer::new(format!("settings.json (profile '{}')", profile.name))
+            .with(settings_layers::HC3_HOST, profile.hc3_host)
+            .with(settings_layers::HC3_USER, profile.hc3_user)
+            .with(settings_layers::HC3_PASSWORD, password)
+            .with(settings_layers::HC3_PROTOCOL, profile.hc3_protocol)
+            .with(settings_layers::HOMEMAP_PATH, resolve_settings_relative(&profile.homemap_path)?)
+            .with(settings_layers::PROFILE, profile.name));
+    }
+    
+    layers.push(settings_layers::cli_layer(settings_layers::cli_args()));
+    let resolved = settings_layers::ResolvedConfig::resolve(&layers);
+    // Logged here rather than per command, so only when the settings are re-resolved
+    println!("Using HC3 config: host from {}, user from {}, password from {}",
+        resolved.source(settings_layers::HC3_HOST).unwrap_or("-"),
+        resolved.source(settings_layers::HC3_USER).unwrap_or("-"),
+        resolved.source(settings_layers::HC3_PASSWORD).unwrap_or("-"));
+    Ok(resolved)
+}
+
+// GET a JSON document from the HC3 REST API (accepts self-signed certificates like http_fetch_insecure)
+async fn hc3_get_json(config: &HC3Config, api_path: &str) -> Result<serde_json::Value, String> {
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let url = format!("{}://{}{}", config.protocol, config.host, api_path);
+    let response = client.get(&url)
+        .basic_auth(&config.user, Some(&config.password))
+        .send()
+        .await
+        .map_err(|e| format!("HC3 request {} failed: {}", api_path, e))?;
+    
+    if !response.status().is_success() {
+        return Err(format!("HC3 request {} failed with status {}", api_path, response.status().as_u16()));
+    }
+    
+    response.json::<serde_json::Value>().await
+        .map_err(|e| format!("Failed to parse HC3 response from {}: {}", api_path, e))
+}
+
+// Proxy a request to the configured HC3. The webview only passes the API path;
+// the host and credentials come from the active profile and the vault.
+#[tauri::command]
+async fn hc3_request(
+    path: String,
+    method: Option<String>,
+    headers: Option<HashMap<String, String>>,
+    body: Option<String>,
+    timeout_ms: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<HttpFetchResponse, String> {
+    if !path.starts_with('/') {
+        return Err(format!("HC3 request path must start with '/': {}", path));
     }
     
-    // Also try home directory
-    if let Some(home_dir) = dirs::home_dir() {
-        let home_env = home_dir.join(".env");
-        println!("Trying to load .env from home: {:?}", home_env);
-        if home_env.exists() {
-            let _ = dotenvy::from_path(&home_env);
-            println!("Successfully loaded .env from home directory");
+    let config = hc3_config(&*state.settings()?);
+    let mut builder = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true);
+    if let Some(timeout) = timeout_ms {
+        builder = builder.timeout(std::time::Duration::from_millis(timeout));
+    }
+    let client = builder.build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    
+    let url = format!("{}://{}{}", config.protocol, config.host, path);
+    let method = method.unwrap_or_else(|| "GET".to_string());
+    let mut request = match method.to_uppercase().as_str() {
+        "GET" => client.get(&url),
+        "POST" => client.post(&url),
+        "PUT" => client.put(&url),
+        "DELETE" => client.delete(&url),
+        _ => return Err(format!("Unsupported HTTP method: {}", method)),
+    };
+    
+    for (key, value) in headers.unwrap_or_default() {
+        // Authorization is always set from the vault
+        if !key.eq_ignore_ascii_case("authorization") {
+            request = request.header(key, value);
         }
     }
+    request = request.basic_auth(&config.user, Some(&config.password));
+    
+    if let Some(body_data) = body {
+        request = request.body(body_data);
+    }
     
-    let host = env::var("HC3_HOST").unwrap_or_else(|_| "192.168.1.1".to_string());
-    let user = env::var("HC3_USER").unwrap_or_else(|_| "admin".to_string());
-    let password = env::var("HC3_PASSWORD").unwrap_or_else(|_| "admin".to_string());
-    let protocol = env::var("HC3_PROTOCOL").unwrap_or_else(|_| "http".to_string());
+    let response = request.send().await
+        .map_err(|e| format!("HC3 request {} failed: {}", path, e))?;
     
-    println!("Reading HC3 config:");
-    println!("  HC3_HOST: {}", if env::var("HC3_HOST").is_ok() { "set" } else { "NOT SET" });
-    println!("  HC3_USER: {}", if env::var("HC3_USER").is_ok() { "set" } else { "NOT SET" });
-    println!("  HC3_PASSWORD: {}", if env::var("HC3_PASSWORD").is_ok() { "set" } else { "NOT SET" });
-    println!("  HC3_PROTOCOL: {}", if env::var("HC3_PROTOCOL").is_ok() { "set" } else { "NOT SET" });
+    let status = response.status().as_u16();
+    let ok = response.status().is_success();
+    let body = response.text().await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
     
-    Ok(HC3Config {
-        host,
-        user,
-        password,
-        protocol,
+    Ok(HttpFetchResponse {
+        ok,
+        status,
+        body,
     })
 }
 
-fn get_homemap_data_path() -> Result<PathBuf, String> {
-    // First, try to load from saved settings
-    if let Ok(Some(settings)) = load_app_settings() {
-        if !settings.homemap_path.is_empty() {
-            let path_buf = PathBuf::from(&settings.homemap_path);
-            if path_buf.exists() {
-                println!("Using homemap_path from settings: {:?}", path_buf);
-                // Always sync built-in resources on startup
-                sync_builtin_resources(&path_buf)?;
-                return Ok(path_buf);
-            } else {
-                println!("Warning: Configured homemap_path does not exist: {:?}", path_buf);
-                // On Android, try the alternate path (/data/user/0 vs /data/data)
-                #[cfg(target_os = "android")]
-                {
-                    let path_str = path_buf.to_string_lossy().to_string();
-                    let alternate_path = if path_str.contains("/data/data/") {
-                        PathBuf::from(path_str.replace("/data/data/", "/data/user/0/"))
-                    } else if path_str.contains("/data/user/0/") {
-                        PathBuf::from(path_str.replace("/data/user/0/", "/data/data/"))
-                    } else {
-                        path_buf.clone()
-                    };
-                    if alternate_path != path_buf && alternate_path.exists() {
-                        println!("Using alternate Android path: {:?}", alternate_path);
-                        sync_builtin_resources(&alternate_path)?;
-                        return Ok(alternate_path);
-                    }
+// Data directory for the resolved settings. Commands use the cached AppState::data_dir().
+fn resolve_data_dir(resolved: &settings_layers::ResolvedConfig) -> Result<PathBuf, String> {
+    let source = resolved.source(settings_layers::HOMEMAP_PATH).unwrap_or(settings_layers::SOURCE_DEFAULT);
+    
+    // --data-dir is used as given, and initialized if needed
+    if source == settings_layers::SOURCE_CLI {
+        let data_dir = PathBuf::from(resolved.value(settings_layers::HOMEMAP_PATH));
+        println!("Using homemapdata from command line: {:?}", data_dir);
+        ensure_data_dir(&data_dir)?;
+        return Ok(data_dir);
+    }
+    
+    // Next, try the path from saved settings
+    if source != settings_layers::SOURCE_DEFAULT {
+        let path_buf = PathBuf::from(resolved.value(settings_layers::HOMEMAP_PATH));
+        if path_buf.exists() {
+            println!("Using homemap_path from {}: {:?}", source, path_buf);
+            // Always sync built-in resources on startup
+            sync_builtin_resources(&path_buf)?;
+            return Ok(path_buf);
+        } else {
+            println!("Warning: Configured homemap_path does not exist: {:?}", path_buf);
+            // On Android, try the alternate path (/data/user/0 vs /data/data)
+            #[cfg(target_os = "android")]
+            {
+                let path_str = path_buf.to_string_lossy().to_string();
+                let alternate_path = if path_str.contains("/data/data/") {
+                    PathBuf::from(path_str.replace("/data/data/", "/data/user/0/"))
+                } else if path_str.contains("/data/user/0/") {
+                    PathBuf::from(path_str.replace("/data/user/0/", "/data/data/"))
+                } else {
+                    path_buf.clone()
+                };
+                if alternate_path != path_buf && alternate_path.exists() {
+                    println!("Using alternate Android path: {:?}", alternate_path);
+                    sync_builtin_resources(&alternate_path)?;
+                    return Ok(alternate_path);
                 }
             }
         }
     }
     
+    // Portable installs keep their data next to the executable
+    if let Some(data_dir) = portable::data_dir() {
+        ensure_data_dir(&data_dir)?;
+        println!("Using portable homemapdata: {:?}", data_dir);
+        return Ok(data_dir);
+    }
+    
     // Create default location in app data directory
     #[cfg(target_os = "android")]
     let data_dir = {
@@ -219,118 +351,50 @@ fn get_homemap_data_path() -> Result<PathBuf, String> {
         .join("HomeMap")
         .join("homemapdata");
     
+    ensure_data_dir(&data_dir)?;
+    
+    println!("Using default homemapdata: {:?}", data_dir);
+    Ok(data_dir)
+}
+
+// Create and initialize a data directory, or sync built-in resources into an existing one
+fn ensure_data_dir(data_dir: &Path) -> Result<(), String> {
     // Create the directory if it doesn't exist
     if !data_dir.exists() {
-        println!("Creating default homemapdata directory: {:?}", data_dir);
-        fs::create_dir_all(&data_dir)
+        println!("Creating homemapdata directory: {:?}", data_dir);
+        fs::create_dir_all(data_dir)
             .map_err(|e| format!("Failed to create homemapdata directory: {}", e))?;
         
         // Create default config.json
-        initialize_default_config(&data_dir)?;
+        initialize_default_config(data_dir)?;
     } else {
         // Directory exists - check if it needs initialization (e.g., after uninstall on Android)
         let config_file = data_dir.join("config.json");
         if !config_file.exists() {
             println!("config.json missing in existing directory - reinitializing...");
-            initialize_default_config(&data_dir)?;
+            initialize_default_config(data_dir)?;
         } else {
             // Config exists, just sync built-in resources
-            sync_builtin_resources(&data_dir)?;
+            sync_builtin_resources(data_dir)?;
         }
     }
     
-    println!("Using default homemapdata: {:?}", data_dir);
-    Ok(data_dir)
+    Ok(())
 }
 
-fn initialize_default_config(data_dir: &PathBuf) -> Result<(), String> {
+fn initialize_default_config(data_dir: &Path) -> Result<(), String> {
     let config_file = data_dir.join("config.json");
     let is_new_install = !config_file.exists();
     
     if is_new_install {
-        println!("Initializing homemapdata folder from template...");
+        println!("Initializing homemapdata folder from embedded template...");
+        embedded_template::extract_to(data_dir)?;
         
-        // Try to find homemapdata.example in project directory (dev mode) or app resources
-        match find_template_directory() {
-            Ok(template_dir) => {
-                // Copy template contents to data directory
-                copy_dir_recursive(&template_dir, data_dir)
-                    .map_err(|e| format!("Failed to copy template: {}", e))?;
-                
-                println!("Successfully initialized homemapdata from template");
-            }
-            Err(e) => {
-                println!("Warning: Could not find template directory: {}", e);
-                
-                #[cfg(not(target_os = "android"))]
-                {
-                    println!("Creating minimal config.json...");
-                    // Create a minimal config.json with default floor if template not found
-                    let minimal_config = serde_json::json!({
-                        "name": "HomeMap",
-                        "icon": "🏠",
-                        "floors": [{
-                            "id": "default-floor",
-                            "name": "Main Floor",
-                            "image": "images/default-floor.png"
-                        }],
-                        "devices": []
-                    });
-                    
-                    fs::write(&config_file, serde_json::to_string_pretty(&minimal_config).unwrap())
-                        .map_err(|e| format!("Failed to create minimal config.json: {}", e))?;
-                }
-                
-                #[cfg(target_os = "android")]
-                {
-                    println!("On Android - skipping config.json creation, will be handled by JavaScript asset copy");
-                    // DO NOT create config.json on Android - let JavaScript handle asset extraction
-                }
-                
-                // Create directory structure
-                fs::create_dir_all(data_dir.join("widgets").join("built-in"))
-                    .map_err(|e| format!("Failed to create widgets directory: {}", e))?;
-                fs::create_dir_all(data_dir.join("widgets").join("packages"))
-                    .map_err(|e| format!("Failed to create widgets/packages directory: {}", e))?;
-                fs::create_dir_all(data_dir.join("icons").join("built-in"))
-                    .map_err(|e| format!("Failed to create icons directory: {}", e))?;
-                fs::create_dir_all(data_dir.join("icons").join("packages"))
-                    .map_err(|e| format!("Failed to create icons/packages directory: {}", e))?;
-                fs::create_dir_all(data_dir.join("images"))
-                    .map_err(|e| format!("Failed to create images directory: {}", e))?;
-                
-                // On mobile, embed the default floor image at compile time
-                let default_floor_path = data_dir.join("images").join("default-floor.png");
-
-                if !default_floor_path.exists() {
-                    println!("Creating default floor image from embedded resource");
-                    
-                    #[cfg(any(target_os = "android", target_os = "ios"))]
-                    {
-                        // Embed the actual default floor image from homemapdata.example
-                        let embedded_floor = include_bytes!("../../homemapdata.example/images/default-floor.png");
-                        fs::write(&default_floor_path, embedded_floor)
-                            .map_err(|e| format!("Failed to write embedded floor image: {}", e))?;
-                        println!("Wrote embedded floor image ({} bytes)", embedded_floor.len());
-                    }
-                    
-                    #[cfg(not(any(target_os = "android", target_os = "ios")))]
-                    {
-                        // Desktop doesn't need embedded - it uses template directory
-                        println!("Skipping floor image on desktop (uses template)");
-                    }
-                }
-                
-                // On mobile, JavaScript will copy bundled assets from asset:// protocol
-                // No need to embed widgets here anymore
-                #[cfg(any(target_os = "android", target_os = "ios"))]
-                {
-                    println!("Mobile platform - widgets will be copied via asset extraction");
-                }
-                
-                println!("Created minimal homemapdata structure");
-            }
-        }
+        // Package folders are not part of the template
+        fs::create_dir_all(data_dir.join("widgets").join("packages"))
+            .map_err(|e| format!("Failed to create widgets/packages directory: {}", e))?;
+        fs::create_dir_all(data_dir.join("icons").join("packages"))
+            .map_err(|e| format!("Failed to create icons/packages directory: {}", e))?;
         
         // Create installed-packages.json and widget-mappings.json
         let packages_file = data_dir.join("installed-packages.json");
@@ -355,341 +419,95 @@ fn initialize_default_config(data_dir: &PathBuf) -> Result<(), String> {
         }
     }
     
-    // Try to sync built-in widgets and icons from template (on every startup)
-    // If template not found, just skip the sync
-    if let Ok(template_dir) = find_template_directory() {
-        sync_builtin_resources_from_template(&template_dir, data_dir)?;
-    } else {
-        println!("Skipping built-in resource sync - template not found");
-    }
-    
-    Ok(())
+    // Record the built-in manifest and version for later syncs
+    sync_builtin_resources(data_dir)
 }
 
-fn sync_builtin_resources(data_dir: &PathBuf) -> Result<(), String> {
-    sync_builtin_resources_with_app(None, data_dir)
+fn sync_builtin_resources(data_dir: &Path) -> Result<(), String> {
+    sync_builtin_resources_if_needed(data_dir, false, builtin_sync::ModifiedPolicy::default())
+        .map(|_| ())
 }
 
-fn sync_builtin_resources_with_app(app: Option<&tauri::AppHandle>, data_dir: &PathBuf) -> Result<(), String> {
+// Sync built-ins when the app version changed (or always with force).
+// Returns the sync report, or None when nothing was synced.
+fn sync_builtin_resources_if_needed(
+    data_dir: &Path,
+    force: bool,
+    policy: builtin_sync::ModifiedPolicy,
+) -> Result<Option<builtin_sync::SyncReport>, String> {
     // Check if we need to sync based on version
     let current_version = env!("CARGO_PKG_VERSION");
     let version_file = data_dir.join(".builtin-version");
     
-    let mut should_sync = false;
-    
-    // Read stored version
-    if let Ok(stored_version) = fs::read_to_string(&version_file) {
+    let should_sync = if force {
+        println!("Forced sync of built-in resources");
+        true
+    } else if let Ok(stored_version) = fs::read_to_string(&version_file) {
         let stored_version = stored_version.trim();
         if stored_version != current_version {
             println!("App version changed from {} to {} - will sync built-in resources", stored_version, current_version);
-            should_sync = true;
+            true
         } else {
             println!("Built-in resources already synced for version {}", current_version);
+            false
         }
     } else {
         println!("No version file found - will sync built-in resources");
-        should_sync = true;
+        true
+    };
+    
+    if !should_sync {
+        return Ok(None);
     }
     
-    if should_sync {
-        // Try to find template directory
-        let mut found_template = false;
-        
-        // First, try using app handle to find bundled resources (works on all platforms including mobile)
-        if let Some(app_handle) = app {
-            if let Ok(resource_dir) = app_handle.path().resource_dir() {
-                println!("Resource directory from app handle: {:?}", resource_dir);
-                
-                // Try direct path to homemapdata.example
-                let template = resource_dir.join("homemapdata.example");
-                if template.exists() {
-                    println!("Found template at: {:?}", template);
-                    sync_builtin_resources_from_template(&template, data_dir)?;
-                    found_template = true;
-                } else {
-                    // Try _up_/homemapdata.example (Tauri mobile bundling pattern)
-                    let template_up = resource_dir.join("_up_").join("homemapdata.example");
-                    println!("Trying _up_ path: {:?}", template_up);
-                    if template_up.exists() {
-                        println!("Found template in _up_: {:?}", template_up);
-                        sync_builtin_resources_from_template(&template_up, data_dir)?;
-                        found_template = true;
-                    }
-                }
-            }
-        }
-        
-        // Fallback to find_template_directory if app handle didn't work
-        if !found_template {
-            if let Ok(template_dir) = find_template_directory() {
-                sync_builtin_resources_from_template(&template_dir, data_dir)?;
-                found_template = true;
-            } else {
-                println!("WARNING: Could not find template directory - widgets will not be available!");
-            }
-        }
-        
-        if found_template {
-            // Update version file after successful sync
-            fs::write(&version_file, current_version)
-                .map_err(|e| format!("Failed to write version file: {}", e))?;
-            println!("Updated built-in version to {}", current_version);
-        }
+    // Schemas are generated from the app's types, so refresh them with every version
+    if let Err(e) = schemas::write_schemas(data_dir) {
+        println!("Warning: failed to write JSON schemas: {}", e);
     }
     
-    Ok(())
+    let report = sync_builtin_resources_from_template(data_dir, policy)?;
+    
+    // Update version file after successful sync
+    fs::write(&version_file, current_version)
+        .map_err(|e| format!("Failed to write version file: {}", e))?;
+    println!("Updated built-in version to {}", current_version);
+    
+    Ok(report)
 }
 
-fn sync_builtin_resources_from_template(template_dir: &PathBuf, data_dir: &PathBuf) -> Result<(), String> {
-    
-    // Skip sync if data_dir is the same as template_dir parent (dev mode)
-    // In dev mode, data_dir would be something like /path/to/project/homemapdata
-    // and template_dir would be /path/to/project/homemapdata.example
-    if let Some(template_parent) = template_dir.parent() {
-        if let Some(data_parent) = data_dir.parent() {
-            if template_parent == data_parent {
-                // We're in the same directory as the template (dev mode)
-                // Check if data_dir name is "homemapdata" (the dev folder)
-                if let Some(dir_name) = data_dir.file_name() {
-                    if dir_name == "homemapdata" {
-                        println!("Dev mode - skipping folder sync");
-                        return Ok(());
-                    }
-                }
-            }
-        }
+fn sync_builtin_resources_from_template(
+    data_dir: &Path,
+    policy: builtin_sync::ModifiedPolicy,
+) -> Result<Option<builtin_sync::SyncReport>, String> {
+    
+    // Skip sync for the dev folder next to the template in the project
+    // (/path/to/project/homemapdata beside /path/to/project/homemapdata.example)
+    if data_dir.file_name().is_some_and(|name| name == "homemapdata")
+        && data_dir.with_file_name("homemapdata.example").exists()
+    {
+        println!("Dev mode - skipping folder sync");
+        return Ok(None);
     }
     
     println!("Syncing built-in widgets and icons...");
     
     // Copy config.json if it doesn't exist (don't overwrite user's config)
-    let template_config = template_dir.join("config.json");
-    let user_config = data_dir.join("config.json");
-    
-    if template_config.exists() && !user_config.exists() {
-        fs::copy(&template_config, &user_config)
-            .map_err(|e| format!("Failed to copy config.json: {}", e))?;
+    if !data_dir.join("config.json").exists() {
+        embedded_template::extract_matching(data_dir, false, |path| path == "config.json")?;
         println!("Copied config.json from template");
     }
     
     // Copy images folder if it doesn't exist (example floor plans)
-    let template_images = template_dir.join("images");
-    let user_images = data_dir.join("images");
-    
-    if template_images.exists() && !user_images.exists() {
-        copy_dir_recursive(&template_images, &user_images)
-            .map_err(|e| format!("Failed to copy images: {}", e))?;
+    if !data_dir.join("images").exists() {
+        embedded_template::extract_matching(data_dir, false, |path| path.starts_with("images/"))?;
         println!("Copied images folder from template");
     }
     
-    // Sync widgets/built-in
-    let template_widgets = template_dir.join("widgets").join("built-in");
-    let user_widgets = data_dir.join("widgets").join("built-in");
-    
-    if template_widgets.exists() {
-        println!("Template widgets found at: {:?}", template_widgets);
-        
-        // Don't remove old built-ins - just overwrite/update
-        // Create parent directory if needed
-        if let Some(parent) = user_widgets.parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create widgets directory: {}", e))?;
-        }
-        
-        match copy_dir_recursive(&template_widgets, &user_widgets) {
-            Ok(_) => {
-                println!("Synced built-in widgets successfully");
-            },
-            Err(e) => {
-                println!("WARNING: Failed to sync built-in widgets: {}", e);
-                // Don't fail the whole sync if widgets fail
-            }
-        }
-    } else {
-        println!("WARNING: Template widgets not found at: {:?}", template_widgets);
-    }
-    
-    // Sync icons/built-in
-    let template_icons = template_dir.join("icons").join("built-in");
-    let user_icons = data_dir.join("icons").join("built-in");
-    
-    if template_icons.exists() {
-        println!("Template icons found at: {:?}", template_icons);
-        
-        // Don't remove old built-ins - just overwrite/update
-        // Create parent directory if needed
-        if let Some(parent) = user_icons.parent() {
-            fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create icons directory: {}", e))?;
-        }
-        
-        match copy_dir_recursive(&template_icons, &user_icons) {
-            Ok(_) => println!("Synced built-in icons successfully"),
-            Err(e) => {
-                println!("WARNING: Failed to sync built-in icons: {}", e);
-                // Don't fail the whole sync if icons fail
-            }
-        }
-    } else {
-        println!("WARNING: Template icons not found at: {:?}", template_icons);
-    }
-    
-    Ok(())
-}
-
-// Extract bundled resources on Android/iOS from APK assets  
-#[cfg(any(target_os = "android", target_os = "ios"))]
-fn extract_bundled_template(_app: &tauri::AppHandle, dest_dir: &PathBuf) -> Result<(), String> {
-    println!("Attempting to extract bundled template resources...");
-    
-    let resolver = _app.path();
-    
-    // Get the resource directory (will be asset:// URL on Android)
-    if let Ok(resource_dir) = resolver.resource_dir() {
-        println!("Resource directory: {:?}", resource_dir);
-        
-        #[cfg(target_os = "android")]
-        {
-            // On Android, we need to copy files from asset:// to filesystem
-            // The assets are at asset://localhost/homemapdata.example/*
-            // JavaScript will handle copying via fetch + write_file_base64
-            
-            println!("Android: Assets will be copied by JavaScript using asset:// protocol");
-            let _ = dest_dir; // Suppress unused warning
-            return Err("Android asset extraction handled by JavaScript".to_string());
-        }
-        
-        #[cfg(target_os = "ios")]
-        {
-            let template_in_resources = resource_dir.join("homemapdata.example");
-            println!("Looking for template at: {:?}", template_in_resources);
-            
-            if template_in_resources.exists() {
-                println!("Found template in resources, copying to {:?}", dest_dir);
-                copy_dir_recursive(&template_in_resources, dest_dir)
-                    .map_err(|e| format!("Failed to copy template from resources: {}", e))?;
-                println!("Successfully extracted template from app resources");
-                return Ok(());
-            }
-        }
-    }
-    
-    Err("Could not extract bundled template".to_string())
-}
-
-fn find_template_directory() -> Result<PathBuf, String> {
-    println!("Searching for homemapdata.example template...");
-    
-    // On Android/iOS, check if we can access via resource directory
-    // Tauri bundles resources in assets/_up_/ on Android
-    #[cfg(target_os = "android")]
-    {
-        // Try common Android asset extraction paths
-        let android_paths = [
-            // Where Tauri might extract assets
-            "/data/data/com.gabrielsson.homemap/cache/_up_/homemapdata.example",
-            "/data/data/com.gabrielsson.homemap/files/_up_/homemapdata.example",
-            // Direct APK assets (won't work but try anyway)
-            "/data/app/com.gabrielsson.homemap/base.apk/assets/_up_/homemapdata.example",
-        ];
-        
-        for path in &android_paths {
-            let template = PathBuf::from(path);
-            println!("Checking Android path: {:?}", template);
-            if template.exists() {
-                println!("Found template at: {:?}", template);
-                return Ok(template);
-            }
-        }
-        
-        // On Android, assets are in the APK and need to be extracted
-        // For now, this will fail and we'll use the fallback
-        println!("Template not found in Android paths, will use minimal fallback");
-    }
-    
-    // First try exe directory (for built app)
-    if let Ok(exe_path) = env::current_exe() {
-        println!("Executable path: {:?}", exe_path);
-        
-        if let Some(exe_dir) = exe_path.parent() {
-            // Try next to executable (Windows typically)
-            let template = exe_dir.join("homemapdata.example");
-            println!("Checking: {:?}", template);
-            if template.exists() {
-                println!("Found template at: {:?}", template);
-                return Ok(template);
-            }
-            
-            // Try _up_ directory next to exe (Windows with Tauri bundling)
-            let up_dir = exe_dir.join("_up_").join("homemapdata.example");
-            println!("Checking _up_ next to exe: {:?}", up_dir);
-            if up_dir.exists() {
-                println!("Found template in _up_: {:?}", up_dir);
-                return Ok(up_dir);
-            }
-            
-            // Also check in Resources (macOS bundle)
-            let resources = exe_dir.parent()
-                .and_then(|p| Some(p.join("Resources").join("homemapdata.example")));
-            if let Some(res_template) = resources {
-                println!("Checking: {:?}", res_template);
-                if res_template.exists() {
-                    println!("Found template in Resources: {:?}", res_template);
-                    return Ok(res_template);
-                }
-            }
-            
-            // Check in ../Resources from MacOS folder (macOS app bundle structure)
-            let macos_resources = exe_dir.join("..").join("Resources").join("homemapdata.example");
-            println!("Checking: {:?}", macos_resources);
-            if let Ok(canonical) = macos_resources.canonicalize() {
-                if canonical.exists() {
-                    println!("Found template in MacOS Resources: {:?}", canonical);
-                    return Ok(canonical);
-                }
-            }
-            
-            // Check _up_ directory in Resources (macOS Tauri resource bundling pattern)
-            let up_dir_macos = exe_dir.join("..").join("Resources").join("_up_").join("homemapdata.example");
-            println!("Checking _up_ in Resources: {:?}", up_dir_macos);
-            if let Ok(canonical) = up_dir_macos.canonicalize() {
-                if canonical.exists() {
-                    println!("Found template in _up_: {:?}", canonical);
-                    return Ok(canonical);
-                }
-            }
-        }
-    }
-    
-    // Try to find in project directory (dev mode)
-    if let Ok(exe_path) = env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            let mut current = exe_dir;
-            for _ in 0..5 {
-                if let Some(parent) = current.parent() {
-                    let template = parent.join("homemapdata.example");
-                    if template.exists() {
-                        println!("Found template in project: {:?}", template);
-                        return Ok(template);
-                    }
-                    current = parent;
-                } else {
-                    break;
-                }
-            }
-        }
-    }
-    
-    // Try current working directory
-    if let Ok(current_dir) = env::current_dir() {
-        let template = current_dir.join("homemapdata.example");
-        if template.exists() {
-            println!("Found template in cwd: {:?}", template);
-            return Ok(template);
-        }
-    }
+    // Sync widgets/built-in and icons/built-in against the manifest of shipped files
+    let files = embedded_template::builtin_files()?;
+    let report = builtin_sync::sync(data_dir, &files, env!("CARGO_PKG_VERSION"), policy)?;
     
-    Err("Could not find homemapdata.example template folder".to_string())
+    Ok(Some(report))
 }
 
 fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> std::io::Result<()> {
@@ -728,26 +546,13 @@ fn copy_dir_recursive(src: &PathBuf, dst: &PathBuf) -> std::io::Result<()> {
 }
 
 #[tauri::command]
-fn get_homemap_config() -> Result<serde_json::Value, String> {
-    let data_path = get_homemap_data_path()?;
-    let config_file = data_path.join("config.json");
-    
-    if !config_file.exists() {
-        return Err(format!("Config file not found at: {:?}", config_file));
-    }
-    
-    let content = fs::read_to_string(&config_file)
-        .map_err(|e| format!("Failed to read config file: {}", e))?;
-    
-    let config: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse config JSON: {}", e))?;
-    
-    Ok(config)
+fn get_homemap_config(state: tauri::State<'_, AppState>) -> Result<serde_json::Value, String> {
+    Ok(state.config()?.as_ref().clone())
 }
 
 #[tauri::command]
-fn get_data_path() -> Result<String, String> {
-    get_homemap_data_path()
+fn get_data_path(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    state.data_dir()
         .map(|p| p.to_string_lossy().to_string())
 }
 
@@ -757,12 +562,31 @@ fn get_app_version() -> String {
 }
 
 #[tauri::command]
-fn sync_resources(app: tauri::AppHandle) -> Result<(), String> {
-    let data_path = get_homemap_data_path()?;
-    sync_builtin_resources_with_app(Some(&app), &data_path)
+fn sync_resources(state: tauri::State<'_, AppState>) -> Result<Option<builtin_sync::SyncReport>, String> {
+    let data_path = state.data_dir()?;
+    // Only hand back a report worth showing (e.g. after an upgrade)
+    let report = sync_builtin_resources_if_needed(&data_path, false, builtin_sync::ModifiedPolicy::default())?;
+    Ok(report.filter(|r| r.has_changes()))
 }
 
+// Sync built-ins now regardless of version, choosing what happens to edited files
 #[tauri::command]
+fn resync_builtin_resources(
+    policy: Option<builtin_sync::ModifiedPolicy>,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<builtin_sync::SyncReport>, String> {
+    let data_path = state.data_dir()?;
+    sync_builtin_resources_if_needed(&data_path, true, policy.unwrap_or_default())
+}
+
+#[tauri::command]
+fn get_builtin_sync_report(state: tauri::State<'_, AppState>) -> Result<Option<builtin_sync::SyncReport>, String> {
+    let data_path = state.data_dir()?;
+    Ok(builtin_sync::last_report(&data_path))
+}
+
+#[tauri::command]
+#[cfg_attr(not(target_os = "android"), allow(unused_variables, unused_mut, clippy::needless_return))]
 async fn read_image_as_base64(app: tauri::AppHandle, image_path: String) -> Result<String, String> {
     // On Android, content:// URIs need to be read through ContentResolver using JNI
     #[cfg(target_os = "android")]
@@ -970,33 +794,26 @@ fn read_file_as_text(file_path: String) -> Result<String, String> {
 }
 
 #[tauri::command]
-fn read_widget_json(widget_type: String) -> Result<String, String> {
-    let homemap_path = get_homemap_data_path()?;
-    
-    // Try built-in folder first
-    let widget_path_builtin = homemap_path.join("widgets").join("built-in").join(format!("{}.json", widget_type));
-    if widget_path_builtin.exists() {
-        let content = fs::read_to_string(&widget_path_builtin)
-            .map_err(|e| format!("Failed to read widget file: {}", e))?;
-        return Ok(content);
-    }
-    
-    // Fallback to root widgets folder (legacy)
-    let widget_path = homemap_path.join("widgets").join(format!("{}.json", widget_type));
-    let content = fs::read_to_string(&widget_path)
-        .map_err(|e| format!("Failed to read widget file: {}", e))?;
-    
-    Ok(content)
+fn read_widget_json(widget_type: String, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let homemap_path = state.data_dir()?;
+    
+    // Built-in widget only (built-in/, then the legacy widgets/ root); mappings
+    // and packages are applied by resolve_widget
+    let resolved = widget_resolve::built_in_widget(&homemap_path, &widget_type)
+        .ok_or_else(|| format!("Failed to read widget file: no built-in widget {}", widget_type))?;
+    serde_json::to_string(&resolved.definition)
+        .map_err(|e| format!("Failed to serialize widget: {}", e))
 }
 
 #[tauri::command]
-fn list_directory(path: String) -> Result<Vec<String>, String> {
+#[cfg_attr(not(any(target_os = "ios", target_os = "android")), allow(unused_variables))]
+fn list_directory(path: String, state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
     let path_buf = PathBuf::from(&path);
     
     // Security check: on mobile platforms, ensure path is within homemap data directory
     #[cfg(any(target_os = "ios", target_os = "android"))]
     {
-        let homemap_path = get_homemap_data_path()?;
+        let homemap_path = state.data_dir()?;
         if !path_buf.starts_with(&homemap_path) {
             return Err("Access denied: path must be within homemap data directory".to_string());
         }
@@ -1036,13 +853,13 @@ fn list_directory(path: String) -> Result<Vec<String>, String> {
 }
 
 #[tauri::command]
-fn save_config(file_path: String, content: String) -> Result<(), String> {
+fn save_config(file_path: String, content: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
     println!("[DEBUG save_config] file_path: {}", file_path);
     
     // Validate path is within homemap data directory (mobile platforms only)
     #[cfg(any(target_os = "ios", target_os = "android"))]
     {
-        let homemap_path = get_homemap_data_path()?;
+        let homemap_path = state.data_dir()?;
         let target_path = PathBuf::from(&file_path);
         
         println!("[DEBUG save_config] homemap_path: {:?}", homemap_path);
@@ -1065,14 +882,15 @@ fn save_config(file_path: String, content: String) -> Result<(), String> {
     
     fs::write(&target_path, content)
         .map_err(|e| format!("Failed to write file: {}", e))?;
+    state.invalidate_config();
     
     println!("Saved file to: {:?}", target_path);
     Ok(())
 }
 
 #[tauri::command]
-fn discover_built_in_widgets() -> Result<Vec<String>, String> {
-    let homemap_path = get_homemap_data_path()?;
+fn discover_built_in_widgets(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    let homemap_path = state.data_dir()?;
     let widgets_path = homemap_path.join("widgets").join("built-in");
     
     #[cfg(target_os = "android")]
@@ -1081,27 +899,23 @@ fn discover_built_in_widgets() -> Result<Vec<String>, String> {
     // Try using walkdir which might have better Android support
     let mut found_widgets = Vec::new();
     
-    match walkdir::WalkDir::new(&widgets_path)
+    let entries = walkdir::WalkDir::new(&widgets_path)
         .max_depth(1)
         .into_iter()
         .filter_map(|e| e.ok())
-        .collect::<Vec<_>>()
-    {
-        entries => {
-            #[cfg(target_os = "android")]
-            println!("[Rust] walkdir found {} entries", entries.len());
-            
-            for entry in entries {
-                if entry.path() != widgets_path {
-                    if let Some(file_name) = entry.path().file_name() {
-                        if let Some(name_str) = file_name.to_str() {
-                            if name_str.ends_with(".json") {
-                                let widget_id = name_str.trim_end_matches(".json");
-                                found_widgets.push(widget_id.to_string());
-                                #[cfg(target_os = "android")]
-                                println!("[Rust] Found widget via walkdir: {}", widget_id);
-                            }
-                        }
+        .collect::<Vec<_>>();
+    #[cfg(target_os = "android")]
+    println!("[Rust] walkdir found {} entries", entries.len());
+    
+    for entry in entries {
+        if entry.path() != widgets_path {
+            if let Some(file_name) = entry.path().file_name() {
+                if let Some(name_str) = file_name.to_str() {
+                    if name_str.ends_with(".json") {
+                        let widget_id = name_str.trim_end_matches(".json");
+                        found_widgets.push(widget_id.to_string());
+                        #[cfg(target_os = "android")]
+                        println!("[Rust] Found widget via walkdir: {}", widget_id);
                     }
                 }
             }
@@ -1111,6 +925,22 @@ fn discover_built_in_widgets() -> Result<Vec<String>, String> {
     #[cfg(target_os = "android")]
     println!("[Rust] discover_built_in_widgets: found {} widgets via walkdir", found_widgets.len());
     
+    // Widget files that aren't even valid JSON can't be used. Problems in the
+    // others are logged once per version of the file.
+    let icon_sets = widget_icon_sets(homemap_path.clone(), floor_export::BUILT_IN_PACKAGE.to_string());
+    found_widgets.retain(|widget_id| {
+        let file = widgets_path.join(format!("{}.json", widget_id));
+        let valid = fs::read_to_string(&file)
+            .is_ok_and(|content| serde_json::from_str::<serde_json::Value>(&content).is_ok());
+        if state.needs_lint(&file) {
+            let compose = |definition: &serde_json::Value| {
+                widget_compose::compose(&homemap_path, floor_export::BUILT_IN_PACKAGE, Some(&file), definition)
+            };
+            widget_lint::log_report(&widget_lint::lint_file(widget_id, &file, Some(&compose), &icon_sets));
+        }
+        valid
+    });
+    
     if !found_widgets.is_empty() {
         return Ok(found_widgets);
     }
@@ -1123,7 +953,7 @@ fn discover_built_in_widgets() -> Result<Vec<String>, String> {
 }
 
 #[tauri::command]
-fn create_config_folder(app: tauri::AppHandle, destination_path: String) -> Result<String, String> {
+fn create_config_folder(destination_path: String) -> Result<String, String> {
     let dest = PathBuf::from(&destination_path);
     let homemapdata_dest = dest.join("homemapdata");
     
@@ -1132,117 +962,15 @@ fn create_config_folder(app: tauri::AppHandle, destination_path: String) -> Resu
         return Err("A 'homemapdata' folder already exists at this location.".to_string());
     }
     
-    // Find the template (homemapdata.example)
-    // First try to resolve from bundled resources (production)
-    let template_path = if let Some(resource_path) = app.path().resource_dir().ok() {
-        // Try direct path first (if we change tauri.conf.json later)
-        let template = resource_path.join("homemapdata.example");
-        
-        if template.exists() {
-            template
-        } else {
-            // Try _up_ folder (current structure with ../homemapdata.example in config)
-            let template = resource_path.join("_up_").join("homemapdata.example");
-            
-            if template.exists() {
-                template
-            } else {
-                // Fallback to development mode - look in parent directories
-                if let Ok(exe_path) = env::current_exe() {
-                    if let Some(exe_dir) = exe_path.parent() {
-                        let mut current = exe_dir;
-                        let mut found = None;
-                        for _ in 0..5 {
-                            if let Some(parent) = current.parent() {
-                                let template = parent.join("homemapdata.example");
-                                if template.exists() {
-                                    found = Some(template);
-                                    break;
-                                }
-                                current = parent;
-                            }
-                        }
-                        found.ok_or("Could not find homemapdata.example template")?
-                    } else {
-                        return Err("Could not determine executable directory".to_string());
-                    }
-                } else {
-                    return Err("Could not determine executable path".to_string());
-                }
-            }
-        }
-    } else {
-        return Err("Could not access resource directory".to_string());
-    };
-    
-    println!("Copying template from: {:?}", template_path);
-    println!("Copying template to: {:?}", homemapdata_dest);
-    
-    // Copy the template
-    copy_dir_recursive(&template_path, &homemapdata_dest)
-        .map_err(|e| format!("Failed to copy template: {}", e))?;
+    println!("Extracting template to: {:?}", homemapdata_dest);
+    embedded_template::extract_to(&homemapdata_dest)?;
     
     // Create package directory structure
-    let widgets_builtin = homemapdata_dest.join("widgets").join("built-in");
-    let widgets_packages = homemapdata_dest.join("widgets").join("packages");
-    let icons_builtin = homemapdata_dest.join("icons").join("built-in");
-    let icons_packages = homemapdata_dest.join("icons").join("packages");
-    
-    fs::create_dir_all(&widgets_builtin)
-        .map_err(|e| format!("Failed to create widgets/built-in: {}", e))?;
-    fs::create_dir_all(&widgets_packages)
+    fs::create_dir_all(homemapdata_dest.join("widgets").join("packages"))
         .map_err(|e| format!("Failed to create widgets/packages: {}", e))?;
-    fs::create_dir_all(&icons_builtin)
-        .map_err(|e| format!("Failed to create icons/built-in: {}", e))?;
-    fs::create_dir_all(&icons_packages)
+    fs::create_dir_all(homemapdata_dest.join("icons").join("packages"))
         .map_err(|e| format!("Failed to create icons/packages: {}", e))?;
     
-    // Move existing widgets to built-in (if any)
-    let widgets_dir = homemapdata_dest.join("widgets");
-    if let Ok(entries) = fs::read_dir(&widgets_dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                let file_name = entry.file_name();
-                
-                // Skip the built-in and packages directories we just created
-                if file_name == "built-in" || file_name == "packages" {
-                    continue;
-                }
-                
-                // Move JSON files to built-in
-                if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("json") {
-                    let dest = widgets_builtin.join(&file_name);
-                    fs::rename(&path, &dest)
-                        .map_err(|e| format!("Failed to move widget to built-in: {}", e))?;
-                }
-            }
-        }
-    }
-    
-    // Move existing icon sets to built-in (if any)
-    let icons_dir = homemapdata_dest.join("icons");
-    if let Ok(entries) = fs::read_dir(&icons_dir) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                let file_name = entry.file_name();
-                
-                // Skip the built-in and packages directories we just created
-                if file_name == "built-in" || file_name == "packages" {
-                    continue;
-                }
-                
-                // Move directories to built-in
-                if path.is_dir() {
-                    let dest = icons_builtin.join(&file_name);
-                    fs::rename(&path, &dest)
-                        .map_err(|e| format!("Failed to move icon set to built-in: {}", e))?;
-                }
-            }
-        }
-    }
-    
     // Create temp directory for package installation
     let temp_dir = homemapdata_dest.join("temp");
     fs::create_dir_all(&temp_dir)
@@ -1253,13 +981,96 @@ fn create_config_folder(app: tauri::AppHandle, destination_path: String) -> Resu
     Ok(homemapdata_dest.to_string_lossy().to_string())
 }
 
+const DEFAULT_PROFILE_NAME: &str = "Home";
+
 #[derive(Debug, Serialize, Deserialize)]
 struct AppSettings {
     hc3_host: String,
     hc3_user: String,
+    // Passwords live in the credential vault. They are read from older
+    // settings files (and from the settings dialog) but never written out.
+    #[serde(default, skip_serializing)]
     hc3_password: String,
     hc3_protocol: String,
     homemap_path: String,
+    // Named homes, each with its own HC3 connection and data directory.
+    // The fields above always mirror the active profile.
+    #[serde(default)]
+    profiles: Vec<HomeProfile>,
+    #[serde(default)]
+    active_profile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HomeProfile {
+    name: String,
+    hc3_host: String,
+    hc3_user: String,
+    #[serde(default, skip_serializing)]
+    hc3_password: String,
+    hc3_protocol: String,
+    homemap_path: String,
+}
+
+// Profile info for the picker (no credentials)
+#[derive(Debug, Serialize, Deserialize)]
+struct ProfileSummary {
+    name: String,
+    hc3_host: String,
+    homemap_path: String,
+    active: bool,
+}
+
+impl AppSettings {
+    // The active profile, or the top-level fields when no profiles have been created yet
+    fn active(&self) -> HomeProfile {
+        self.active_profile.as_ref()
+            .and_then(|name| self.profiles.iter().find(|p| &p.name == name))
+            .cloned()
+            .unwrap_or_else(|| self.top_level_profile())
+    }
+    
+    // The profile in use: the one named with --profile, otherwise the active one
+    fn effective(&self) -> HomeProfile {
+        if let Some(name) = &settings_layers::cli_args().profile {
+            if let Some(profile) = self.profiles.iter().find(|p| &p.name == name) {
+                return profile.clone();
+            }
+            println!("Profile '{}' from --profile not found, using the active profile", name);
+        }
+        self.active()
+    }
+    
+    fn top_level_profile(&self) -> HomeProfile {
+        HomeProfile {
+            name: self.active_profile.clone().unwrap_or_else(|| DEFAULT_PROFILE_NAME.to_string()),
+            hc3_host: self.hc3_host.clone(),
+            hc3_user: self.hc3_user.clone(),
+            hc3_password: self.hc3_password.clone(),
+            hc3_protocol: self.hc3_protocol.clone(),
+            homemap_path: self.homemap_path.clone(),
+        }
+    }
+    
+    // Store the top-level fields into the active profile, creating it on first use
+    fn store_active_profile(&mut self) {
+        let profile = self.top_level_profile();
+        self.active_profile = Some(profile.name.clone());
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+    
+    // Make a profile active by copying it into the top-level fields
+    fn apply_profile(&mut self, profile: &HomeProfile) {
+        self.hc3_host = profile.hc3_host.clone();
+        self.hc3_user = profile.hc3_user.clone();
+        self.hc3_password = profile.hc3_password.clone();
+        self.hc3_protocol = profile.hc3_protocol.clone();
+        self.homemap_path = profile.homemap_path.clone();
+        self.active_profile = Some(profile.name.clone());
+    }
 }
 
 // UI preferences that should be backed up and restored
@@ -1279,7 +1090,29 @@ fn get_app_settings() -> Result<AppSettings, String> {
     }
     
     // Return defaults if no saved settings
-    // On mobile platforms, use the same path as get_homemap_data_path
+    let settings = AppSettings {
+        hc3_host: String::new(),
+        hc3_user: String::new(),
+        hc3_password: String::new(),
+        hc3_protocol: "http".to_string(),
+        homemap_path: default_homemap_path()?,
+        profiles: Vec::new(),
+        active_profile: None,
+    };
+    
+    Ok(settings)
+}
+
+fn default_homemap_path() -> Result<String, String> {
+    match portable::data_dir() {
+        Some(dir) => Ok(dir.to_string_lossy().to_string()),
+        None => installed_homemap_path(),
+    }
+}
+
+// Default data directory of a regular (non-portable) install
+fn installed_homemap_path() -> Result<String, String> {
+    // On mobile platforms, use the same path as resolve_data_dir
     #[cfg(any(target_os = "ios", target_os = "android"))]
     let default_homemap_path = {
         #[cfg(target_os = "ios")]
@@ -1301,20 +1134,49 @@ fn get_app_settings() -> Result<AppSettings, String> {
         .to_string_lossy()
         .to_string();
     
-    let settings = AppSettings {
-        hc3_host: String::new(),
-        hc3_user: String::new(),
-        hc3_password: String::new(),
-        hc3_protocol: "http".to_string(),
-        homemap_path: default_homemap_path,
-    };
-    
-    Ok(settings)
+    Ok(default_homemap_path)
 }
 
 #[tauri::command]
-fn save_app_settings(settings: AppSettings) -> Result<(), String> {
-    // Save to app config directory (platform-specific)
+fn save_app_settings(mut settings: AppSettings, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    // The settings dialog only sends the connection fields; keep the saved profiles
+    // and update the active one with what was edited
+    if settings.profiles.is_empty() {
+        if let Ok(Some(existing)) = load_app_settings() {
+            settings.profiles = existing.profiles;
+            settings.active_profile = existing.active_profile;
+        }
+    }
+    if !settings.profiles.is_empty() {
+        settings.store_active_profile();
+    }
+    
+    write_app_settings(&settings)?;
+    state.invalidate();
+    Ok(())
+}
+
+// settings.json, or the file given with --settings
+fn settings_file_path() -> Result<PathBuf, String> {
+    if let Some(path) = &settings_layers::cli_args().settings {
+        return Ok(PathBuf::from(path));
+    }
+    match portable::settings_dir() {
+        Some(dir) => Ok(dir.join("settings.json")),
+        None => Ok(app_config_dir()?.join("settings.json")),
+    }
+}
+
+// Directory holding settings.json and vault.json
+fn settings_dir() -> Result<PathBuf, String> {
+    let settings_file = settings_file_path()?;
+    Ok(settings_file.parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from(".")))
+}
+
+// App config directory (platform-specific)
+fn app_config_dir() -> Result<PathBuf, String> {
     #[cfg(any(target_os = "ios", target_os = "android"))]
     let config_dir = {
         #[cfg(target_os = "ios")]
@@ -1346,16 +1208,56 @@ fn save_app_settings(settings: AppSettings) -> Result<(), String> {
         .ok_or("Could not find config directory")?
         .join("HomeMap");
     
+    Ok(config_dir)
+}
+
+fn vault_store() -> Result<Box<dyn vault::CredentialStore>, String> {
+    Ok(vault::open_store(&settings_dir()?))
+}
+
+// HC3 password for a profile from the credential vault
+fn vault_password(profile_name: &str) -> Result<Option<String>, String> {
+    vault_store()?.get(&vault::password_account(profile_name))
+}
+
+// Move any passwords set on the settings into the vault. Empty passwords mean
+// "unchanged", since the webview never gets to see the stored one.
+fn store_passwords_in_vault(settings: &AppSettings) -> Result<(), String> {
+    let top_level = settings.top_level_profile();
+    let passwords: Vec<(&str, &str)> = std::iter::once(&top_level)
+        .chain(settings.profiles.iter())
+        .filter(|p| !p.hc3_password.is_empty())
+        .map(|p| (p.name.as_str(), p.hc3_password.as_str()))
+        .collect();
+    if passwords.is_empty() {
+        return Ok(());
+    }
+    
+    let store = vault_store()?;
+    if store.is_locked() {
+        return Err("Credential vault is locked. Unlock it before saving the HC3 password.".to_string());
+    }
+    for (profile_name, password) in passwords {
+        store.set(&vault::password_account(profile_name), password)?;
+        println!("Stored HC3 password for profile '{}' in {} vault", profile_name, store.backend());
+    }
+    Ok(())
+}
+
+fn write_app_settings(settings: &AppSettings) -> Result<(), String> {
+    let config_dir = settings_dir()?;
+    store_passwords_in_vault(settings)?;
+    
     fs::create_dir_all(&config_dir)
         .map_err(|e| format!("Failed to create config directory: {}", e))?;
     
-    let config_file = config_dir.join("settings.json");
+    let config_file = settings_file_path()?;
     
     println!("[DEBUG save_app_settings] Saving settings:");
     println!("  homemap_path: '{}'", settings.homemap_path);
     println!("  config_file: {:?}", config_file);
     
-    let json = serde_json::to_string_pretty(&settings)
+    let json = serde_json::to_string_pretty(settings)
         .map_err(|e| format!("Failed to serialize settings: {}", e))?;
     
     fs::write(&config_file, json)
@@ -1368,8 +1270,11 @@ fn save_app_settings(settings: AppSettings) -> Result<(), String> {
 #[tauri::command]
 fn is_hc3_configured() -> bool {
     // Check if settings.json exists and has non-empty HC3 host
+    if settings_layers::cli_args().hc3_host.is_some() {
+        return true;
+    }
     if let Ok(Some(settings)) = load_app_settings() {
-        !settings.hc3_host.is_empty()
+        !settings.effective().hc3_host.is_empty()
     } else {
         false
     }
@@ -1399,39 +1304,7 @@ async fn select_homemap_folder<R: tauri::Runtime>(_app: tauri::AppHandle<R>) ->
 
 #[tauri::command]
 fn load_app_settings() -> Result<Option<AppSettings>, String> {
-    // Load from app config directory (platform-specific)
-    #[cfg(any(target_os = "ios", target_os = "android"))]
-    let config_dir = {
-        #[cfg(target_os = "ios")]
-        let dir = dirs::document_dir()
-            .ok_or("Could not find document directory")?
-            .join(".config")
-            .join("HomeMap");
-        
-        #[cfg(target_os = "android")]
-        let dir = {
-            // Use /data/user/0 which is the actual path (symlink to /data/data)
-            use std::env;
-            let base = env::var("ANDROID_DATA").ok()
-                .and_then(|_| {
-                    Some(PathBuf::from("/data/user/0/com.gabrielsson.homemap/files"))
-                })
-                .or_else(|| {
-                    Some(PathBuf::from("/data/data/com.gabrielsson.homemap/files"))
-                })
-                .unwrap();
-            base.join(".config").join("HomeMap")
-        };
-        
-        dir
-    };
-    
-    #[cfg(not(any(target_os = "ios", target_os = "android")))]
-    let config_dir = dirs::config_dir()
-        .ok_or("Could not find config directory")?
-        .join("HomeMap");
-    
-    let config_file = config_dir.join("settings.json");
+    let config_file = settings_file_path()?;
     
     if !config_file.exists() {
         return Ok(None);
@@ -1446,6 +1319,22 @@ fn load_app_settings() -> Result<Option<AppSettings>, String> {
     Ok(Some(settings))
 }
 
+// Older versions stored the password in plain text; move it into the vault.
+// Runs at startup, and again once a locked vault has been unlocked.
+fn migrate_plaintext_passwords() {
+    let Ok(Some(settings)) = load_app_settings() else {
+        return;
+    };
+    let has_plaintext_password = !settings.hc3_password.is_empty()
+        || settings.profiles.iter().any(|p| !p.hc3_password.is_empty());
+    if has_plaintext_password {
+        match write_app_settings(&settings) {
+            Ok(()) => println!("Moved HC3 password from settings.json into the credential vault"),
+            Err(e) => println!("HC3 password not migrated to the vault yet: {}", e),
+        }
+    }
+}
+
 #[tauri::command]
 fn write_file_base64(file_path: String, b64: String) -> Result<(), String> {
     // Decode base64 and write binary file to given filesystem path
@@ -1481,9 +1370,9 @@ fn write_file_as_text(file_path: String, content: String) -> Result<(), String>
 
 // Save temporary file from byte array (for mobile file uploads)
 #[tauri::command]
-fn save_temp_file(file_name: String, data: Vec<u8>) -> Result<String, String> {
+fn save_temp_file(file_name: String, data: Vec<u8>, state: tauri::State<'_, AppState>) -> Result<String, String> {
     // Get the app data directory
-    let data_dir = get_homemap_data_path()?;
+    let data_dir = state.data_dir()?;
     
     // Create temp directory if it doesn't exist
     let temp_dir = data_dir.join("temp");
@@ -1507,117 +1396,17 @@ fn save_temp_file(file_name: String, data: Vec<u8>) -> Result<String, String> {
         .map(|s| s.to_string())
 }
 
-// Read bundled asset file and return as base64
-// Used to access files from APK assets that aren't accessible via fetch()
+// Read a file from the embedded default template and return it as base64
 #[tauri::command]
-fn read_bundled_asset(
-    #[allow(unused_variables)] app: tauri::AppHandle,
-    asset_path: String
-) -> Result<String, String> {
-    use base64::Engine;
+fn read_bundled_asset(asset_path: String) -> Result<String, String> {
     use base64::engine::general_purpose::STANDARD as BASE64_STD;
     
-    #[cfg(target_os = "android")]
-    {
-        use jni::JavaVM;
-        use jni::objects::{JObject, JValue};
-        
-        // Get Java VM and attach to current thread
-        let ctx = ndk_context::android_context();
-        let vm = unsafe { JavaVM::from_raw(ctx.vm().cast()) }
-            .map_err(|e| format!("Failed to get JavaVM: {}", e))?;
-        
-        let mut env = vm.attach_current_thread()
-            .map_err(|e| format!("Failed to attach thread: {}", e))?;
-        
-        // Get Android context
-        let context = unsafe { JObject::from_raw(ctx.context().cast()) };
-        
-        // Get AssetManager: context.getAssets()
-        let asset_manager = env.call_method(
-            &context,
-            "getAssets",
-            "()Landroid/content/res/AssetManager;",
-            &[]
-        ).map_err(|e| format!("Failed to get AssetManager: {}", e))?;
-        
-        let asset_manager = asset_manager.l()
-            .map_err(|e| format!("Failed to convert AssetManager: {}", e))?;
-        
-        // Build asset path: _up_/homemapdata.example/{asset_path}
-        let full_asset_path = format!("_up_/homemapdata.example/{}", asset_path);
-        
-        let path_jstring = env.new_string(&full_asset_path)
-            .map_err(|e| format!("Failed to create path string: {}", e))?;
-        
-        // Open asset: assetManager.open(path)
-        let input_stream = env.call_method(
-            &asset_manager,
-            "open",
-            "(Ljava/lang/String;)Ljava/io/InputStream;",
-            &[JValue::Object(&path_jstring)]
-        ).map_err(|e| format!("Failed to open asset '{}': {}", full_asset_path, e))?;
-        
-        let input_stream = input_stream.l()
-            .map_err(|e| format!("Failed to convert InputStream: {}", e))?;
-        
-        // Read all bytes from InputStream
-        let mut bytes = Vec::new();
-        loop {
-            // Read buffer: inputStream.read(buffer)
-            let buffer_size = 8192;
-            let buffer = env.new_byte_array(buffer_size)
-                .map_err(|e| format!("Failed to create buffer: {}", e))?;
-            
-            let bytes_read = env.call_method(
-                &input_stream,
-                "read",
-                "([B)I",
-                &[JValue::Object(&buffer)]
-            ).map_err(|e| format!("Failed to read from stream: {}", e))?;
-            
-            let bytes_read = bytes_read.i()
-                .map_err(|e| format!("Failed to get bytes read: {}", e))?;
-            
-            if bytes_read <= 0 {
-                break; // End of stream
-            }
-            
-            // Copy bytes from Java byte array to Rust Vec
-            let mut chunk = vec![0u8; bytes_read as usize];
-            env.get_byte_array_region(&buffer, 0, unsafe {
-                std::slice::from_raw_parts_mut(chunk.as_mut_ptr() as *mut i8, bytes_read as usize)
-            }).map_err(|e| format!("Failed to copy bytes: {}", e))?;
-            
-            bytes.extend_from_slice(&chunk);
-        }
-        
-        // Close stream: inputStream.close()
-        env.call_method(&input_stream, "close", "()V", &[])
-            .map_err(|e| format!("Failed to close stream: {}", e))?;
-        
-        Ok(BASE64_STD.encode(&bytes))
-    }
-    
-    #[cfg(not(target_os = "android"))]
-    {
-        // On desktop, read from filesystem
-        let resolver = app.path();
-        let resource_dir = resolver.resource_dir()
-            .map_err(|e| format!("Failed to get resource dir: {}", e))?;
-        let full_path = resource_dir.join("homemapdata.example").join(&asset_path);
-        
-        println!("Reading desktop asset from: {:?}", full_path);
-        
-        let bytes = std::fs::read(&full_path)
-            .map_err(|e| format!("Failed to read asset {}: {}", asset_path, e))?;
-        
-        Ok(BASE64_STD.encode(&bytes))
-    }
+    let bytes = embedded_template::read_asset(&asset_path)?;
+    Ok(BASE64_STD.encode(&bytes))
 }
 
 // Package management structures
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
 struct PackageManifest {
     id: String,
     name: String,
@@ -1639,13 +1428,13 @@ struct PackageManifest {
     tags: Option<Vec<String>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
 struct PackageRequires {
     #[serde(rename = "homeMapVersion")]
     home_map_version: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, schemars::JsonSchema)]
 struct PackageProvides {
     widgets: Vec<String>,
     #[serde(rename = "iconSets")]
@@ -1657,10 +1446,22 @@ struct ExtractedPackage {
     manifest: PackageManifest,
     #[serde(rename = "tempDir")]
     temp_dir: String,
+    // Lint results for the widgets the package provides
+    #[serde(rename = "widgetDiagnostics")]
+    widget_diagnostics: Vec<widget_lint::WidgetLintReport>,
+    // Whether the package is signed by a trusted publisher
+    signature: package_signing::SignatureCheck,
+    // Files left out because their type isn't allowed (see package_extract)
+    #[serde(rename = "skippedFiles")]
+    skipped_files: Vec<String>,
 }
 
 #[tauri::command]
-fn extract_widget_package(hwp_path: String, data_path: String) -> Result<ExtractedPackage, String> {
+fn extract_widget_package(
+    hwp_path: String,
+    data_path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<ExtractedPackage, String> {
     use zip::ZipArchive;
     
     println!("Extracting package from: {}", hwp_path);
@@ -1685,36 +1486,36 @@ fn extract_widget_package(hwp_path: String, data_path: String) -> Result<Extract
     
     println!("Extracting to temp dir: {:?}", temp_dir);
     
-    // Extract all files
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i)
-            .map_err(|e| format!("Failed to read file from archive: {}", e))?;
-        
-        let outpath = match file.enclosed_name() {
-            Some(path) => temp_dir.join(path),
-            None => continue,
-        };
-        
-        if file.name().ends_with('/') {
-            // Directory
-            fs::create_dir_all(&outpath)
-                .map_err(|e| format!("Failed to create directory: {}", e))?;
-        } else {
-            // File
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p)
-                        .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-                }
+    let extracted = unpack_widget_package(&mut archive, &temp_dir, &data_path);
+    match &extracted {
+        // Installing checks the verdict again, rather than trusting the UI to
+        Ok(package) => match fs::canonicalize(&temp_dir) {
+            Ok(dir) => state.record_signature(&dir, package.signature.status),
+            Err(e) => println!("Failed to resolve {:?}: {}", temp_dir, e),
+        },
+        // Don't leave a half-extracted package behind
+        Err(_) => {
+            if let Err(e) = fs::remove_dir_all(&temp_dir) {
+                println!("Failed to remove {:?}: {}", temp_dir, e);
             }
-            
-            let mut outfile = fs::File::create(&outpath)
-                .map_err(|e| format!("Failed to create file: {}", e))?;
-            
-            std::io::copy(&mut file, &mut outfile)
-                .map_err(|e| format!("Failed to extract file: {}", e))?;
         }
     }
+    extracted
+}
+
+// Extract the package into temp_dir, check its signature, then read its
+// manifest and lint its widgets
+fn unpack_widget_package(
+    archive: &mut zip::ZipArchive<fs::File>,
+    temp_dir: &Path,
+    data_path: &str,
+) -> Result<ExtractedPackage, String> {
+    let limits = package_extract::load_limits(Path::new(data_path));
+    let skipped_files = package_extract::extract(archive, temp_dir, &limits)?;
+    
+    // Only checked once the archive is known to be within the limits
+    let signature = package_signing::verify_package(archive, Path::new(data_path), &limits)?;
+    println!("Package signature: {:?} - {}", signature.status, signature.message);
     
     // Read and parse manifest
     let manifest_path = temp_dir.join("manifest.json");
@@ -1730,12 +1531,120 @@ fn extract_widget_package(hwp_path: String, data_path: String) -> Result<Extract
     
     println!("Package extracted: {} v{}", manifest.name, manifest.version);
     
+    // Lint the provided widgets; icon sets come from the package itself, or from
+    // an already installed version of it
+    let installed_icons = PathBuf::from(&data_path).join("icons").join("packages").join(&manifest.id);
+    let package_icons = temp_dir.join("icons");
+    let icon_sets = |icon_set: &str| {
+        [&package_icons, &installed_icons].into_iter()
+            .map(|dir| dir.join(icon_set))
+            .find(|dir| dir.is_dir())
+    };
+    let mut widget_diagnostics = Vec::new();
+    for widget_id in &manifest.provides.widgets {
+        let widget_path = temp_dir.join("widgets").join(format!("{}.json", widget_id));
+        if !widget_path.exists() {
+            continue;
+        }
+        // Bases and fragments may come from the package itself, which isn't installed yet
+        let report = widget_lint::lint_file(widget_id, &widget_path, None, &icon_sets);
+        widget_lint::log_report(&report);
+        if !report.diagnostics.is_empty() {
+            widget_diagnostics.push(report);
+        }
+    }
+    
     Ok(ExtractedPackage {
         manifest,
         temp_dir: temp_dir.to_string_lossy().to_string(),
+        widget_diagnostics,
+        signature,
+        skipped_files,
     })
 }
 
+// Publishers whose signed packages install without a warning
+#[tauri::command]
+fn list_trusted_publishers(state: tauri::State<'_, AppState>) -> Result<Vec<package_signing::TrustedPublisher>, String> {
+    Ok(package_signing::load_keyring(&state.data_dir()?).publishers)
+}
+
+#[tauri::command]
+fn trust_publisher(
+    name: String,
+    public_key: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<package_signing::TrustedPublisher, String> {
+    package_signing::trust_publisher(&state.data_dir()?, &name, &public_key)
+}
+
+#[tauri::command]
+fn untrust_publisher(key_id: String, state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    package_signing::untrust_publisher(&state.data_dir()?, &key_id)
+}
+
+// The folder extract_widget_package unpacked a package into; only folders in
+// the data directory's temp/ are accepted
+fn extracted_package_dir(data_path: &Path, temp_dir: &str) -> Result<PathBuf, String> {
+    let dir = fs::canonicalize(temp_dir).map_err(|e| format!("Extracted package not found {}: {}", temp_dir, e))?;
+    let temp_root = fs::canonicalize(data_path.join(cleanup::TEMP_DIR))
+        .map_err(|e| format!("Failed to resolve temp directory: {}", e))?;
+    if !dir.starts_with(&temp_root) || dir == temp_root {
+        return Err(format!("Not an extracted package: {}", temp_dir));
+    }
+    Ok(dir)
+}
+
+fn install_extracted_package(temp_dir: String, upgrade: bool, state: tauri::State<'_, AppState>) -> Result<package_install::PackageChange, String> {
+    let data_path = state.data_dir()?;
+    let extracted = extracted_package_dir(&data_path, &temp_dir)?;
+    match state.signature(&extracted) {
+        Some(package_signing::SignatureStatus::Tampered) => {
+            return Err("Package was modified after it was signed and will not be installed".to_string());
+        }
+        Some(_) => {}
+        None => return Err(format!("No signature check recorded for {}; extract the package again", temp_dir)),
+    }
+    let change = package_install::install(&data_path, &extracted, upgrade)?;
+    state.forget_signature(&extracted);
+    if let Err(e) = fs::remove_dir_all(&extracted) {
+        println!("Failed to remove {:?}: {}", extracted, e);
+    }
+    Ok(change)
+}
+
+// Remove a package extracted by extract_widget_package that won't be installed
+#[tauri::command]
+fn discard_extracted_package(temp_dir: String, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    let extracted = extracted_package_dir(&state.data_dir()?, &temp_dir)?;
+    state.forget_signature(&extracted);
+    fs::remove_dir_all(&extracted).map_err(|e| format!("Failed to remove {:?}: {}", extracted, e))
+}
+
+// Install a package extracted by extract_widget_package. Fails if it is
+// already installed; all files and both registries change together or not at all.
+#[tauri::command]
+fn install_package(temp_dir: String, state: tauri::State<'_, AppState>) -> Result<package_install::PackageChange, String> {
+    install_extracted_package(temp_dir, false, state)
+}
+
+// Replace an installed package with the version extracted by extract_widget_package
+#[tauri::command]
+fn upgrade_package(temp_dir: String, state: tauri::State<'_, AppState>) -> Result<package_install::PackageChange, String> {
+    install_extracted_package(temp_dir, true, state)
+}
+
+// Remove a package. While devices use it, nothing changes (committed is false
+// and the devices are listed) unless force is set.
+#[tauri::command]
+fn uninstall_package(
+    package_id: String,
+    force: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<package_install::PackageChange, String> {
+    package_install::uninstall(&state.data_dir()?, &package_id, force.unwrap_or(false))
+}
+
 #[tauri::command]
 fn copy_file(src: String, dst: String) -> Result<(), String> {
     std::fs::copy(&src, &dst)
@@ -1789,6 +1698,11 @@ fn create_backup(source_path: String, dest_path: String) -> Result<(), String> {
             continue;
         }
         
+        // Temp extractions, generated caches and the cleanup trash don't belong in a backup
+        if cleanup::is_scratch_path(name) {
+            continue;
+        }
+        
         let name_str = name.to_str()
             .ok_or_else(|| "Invalid UTF-8 in path".to_string())?;
         
@@ -2030,8 +1944,8 @@ fn restore_homemap_data(backup_path: String, target_path: String, options: Resto
     let backup_location = if options.backup_existing && target_dir.exists() {
         let timestamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
         let backup_name = format!("homemap-backup-before-restore-{}.zip", timestamp);
-        let backup_path = target_dir.parent()
-            .unwrap_or(&target_dir)
+        let backup_path = portable::backups_dir()
+            .unwrap_or_else(|| target_dir.parent().unwrap_or(&target_dir).to_path_buf())
             .join(&backup_name);
         
         create_backup(target_path.clone(), backup_path.to_string_lossy().to_string())?;
@@ -2069,7 +1983,7 @@ fn restore_homemap_data(backup_path: String, target_path: String, options: Resto
         
         // Check if we should restore this file type
         let should_restore = match file_path.as_str() {
-            p if p == "config.json" => options.restore_config,
+            "config.json" => options.restore_config,
             p if p.starts_with("images/") => options.restore_images,
             // Restore icons but EXCLUDE built-in icons (they should come from current app version)
             p if p.starts_with("icons/built-in/") => false,
@@ -2196,7 +2110,7 @@ fn restore_homemap_data(backup_path: String, target_path: String, options: Resto
             Ok(prefs_json) => {
                 match serde_json::from_str::<UiPreferences>(&prefs_json) {
                     Ok(prefs) => {
-                        if let Err(e) = apply_ui_preferences_to_current_config(prefs) {
+                        if let Err(e) = apply_ui_preferences(&target_dir, prefs) {
                             println!("Warning: Could not apply UI preferences: {}", e);
                         } else {
                             println!("Successfully applied UI preferences from backup");
@@ -2249,8 +2163,14 @@ fn extract_ui_preferences_from_config(config_content: &str) -> Result<UiPreferen
 
 // Apply UI preferences to the current config.json
 #[tauri::command]
-fn apply_ui_preferences_to_current_config(preferences: UiPreferences) -> Result<(), String> {
-    let data_path = get_homemap_data_path()?;
+fn apply_ui_preferences_to_current_config(preferences: UiPreferences, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    apply_ui_preferences(&state.data_dir()?, preferences)?;
+    state.invalidate_config();
+    Ok(())
+}
+
+// Write UI preferences into the config.json of a data directory
+fn apply_ui_preferences(data_path: &Path, preferences: UiPreferences) -> Result<(), String> {
     let config_path = data_path.join("config.json");
     
     if !config_path.exists() {
@@ -2294,6 +2214,72 @@ fn apply_ui_preferences_to_current_config(preferences: UiPreferences) -> Result<
     Ok(())
 }
 
+// Compare the current config.json with another config (config.json file or backup .zip)
+#[tauri::command]
+fn diff_homemap_config(source_path: String, state: tauri::State<'_, AppState>) -> Result<config_merge::ConfigDiff, String> {
+    let current = state.config()?;
+    let other = config_merge::read_config_source(&PathBuf::from(&source_path))?;
+    
+    let diff = config_merge::diff_configs(&current, &other);
+    println!("Config diff against {}: {} differences", source_path, diff.items.len());
+    Ok(diff)
+}
+
+// Pull the selected floors/devices from another config into the current config.json.
+// Floor images referenced by pulled floors are copied along when they are missing.
+#[tauri::command]
+fn merge_homemap_config(source_path: String, selection: config_merge::MergeSelection, state: tauri::State<'_, AppState>) -> Result<config_merge::MergeReport, String> {
+    let data_path = state.data_dir()?;
+    let config_path = data_path.join("config.json");
+    let source = PathBuf::from(&source_path);
+    
+    let current = state.config()?;
+    let other = config_merge::read_config_source(&source)?;
+    let (merged, report) = config_merge::apply_selection(&current, &other, &selection)?;
+    
+    let is_zip = source.extension().map(|e| e.eq_ignore_ascii_case("zip")).unwrap_or(false);
+    for image in &report.images_needed {
+        // The path comes from the other config, so it must not lead out of the data directory
+        let relative = Path::new(image);
+        if relative.is_absolute()
+            || relative.components().any(|c| !matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+        {
+            println!("Warning: skipping floor image outside the data directory: {}", image);
+            continue;
+        }
+        let image_dest = data_path.join(relative);
+        if image_dest.exists() {
+            continue;
+        }
+        let data = if is_zip {
+            config_merge::read_zip_entry(&source, image)?
+        } else {
+            source.parent().map(|dir| dir.join(image)).and_then(|p| fs::read(p).ok())
+        };
+        match data {
+            Some(bytes) => {
+                if let Some(parent) = image_dest.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create image directory: {}", e))?;
+                }
+                fs::write(&image_dest, bytes)
+                    .map_err(|e| format!("Failed to write floor image {}: {}", image, e))?;
+                println!("Copied floor image from merge source: {}", image);
+            }
+            None => println!("Warning: floor image {} not found in merge source", image),
+        }
+    }
+    
+    let content = serde_json::to_string_pretty(&merged)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    fs::write(&config_path, content)
+        .map_err(|e| format!("Failed to write config file: {}", e))?;
+    state.invalidate_config();
+    
+    println!("Merged {} changes from {} ({} skipped)", report.applied.len(), source_path, report.skipped.len());
+    Ok(report)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct BackupFileInfo {
     path: String,
@@ -2307,7 +2293,7 @@ fn list_backup_files() -> Result<Vec<BackupFileInfo>, String> {
     let mut backup_files = Vec::new();
     
     // Check common directories where backup files might be located
-    let search_dirs = vec![
+    let mut search_dirs = vec![
         #[cfg(target_os = "android")]
         PathBuf::from("/storage/emulated/0/Download"), // Android Downloads
         #[cfg(target_os = "android")]
@@ -2325,6 +2311,7 @@ fn list_backup_files() -> Result<Vec<BackupFileInfo>, String> {
         #[cfg(not(any(target_os = "android", target_os = "ios")))]
         dirs::desktop_dir().unwrap_or_default(), // Desktop
     ];
+    search_dirs.extend(portable::backups_dir()); // Portable install backups
     
     for search_dir in search_dirs {
         if !search_dir.exists() {
@@ -2332,30 +2319,28 @@ fn list_backup_files() -> Result<Vec<BackupFileInfo>, String> {
         }
         
         if let Ok(entries) = fs::read_dir(&search_dir) {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    let path = entry.path();
-                    if let Some(extension) = path.extension() {
-                        if extension == "zip" {
-                            if let Some(filename) = path.file_name() {
-                                if let Some(filename_str) = filename.to_str() {
-                                    // Only include files that look like HomeMap backups
-                                    if filename_str.contains("homemap") || filename_str.contains("HomeMap") {
-                                        if let Ok(metadata) = fs::metadata(&path) {
-                                            let modified = metadata.modified()
-                                                .map(|time| {
-                                                    let datetime: chrono::DateTime<chrono::Utc> = time.into();
-                                                    datetime.format("%Y-%m-%d %H:%M:%S").to_string()
-                                                })
-                                                .unwrap_or_else(|_| "Unknown".to_string());
-                                            
-                                            backup_files.push(BackupFileInfo {
-                                                path: path.to_string_lossy().to_string(),
-                                                filename: filename_str.to_string(),
-                                                size: metadata.len(),
-                                                modified,
-                                            });
-                                        }
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if let Some(extension) = path.extension() {
+                    if extension == "zip" {
+                        if let Some(filename) = path.file_name() {
+                            if let Some(filename_str) = filename.to_str() {
+                                // Only include files that look like HomeMap backups
+                                if filename_str.contains("homemap") || filename_str.contains("HomeMap") {
+                                    if let Ok(metadata) = fs::metadata(&path) {
+                                        let modified = metadata.modified()
+                                            .map(|time| {
+                                                let datetime: chrono::DateTime<chrono::Utc> = time.into();
+                                                datetime.format("%Y-%m-%d %H:%M:%S").to_string()
+                                            })
+                                            .unwrap_or_else(|_| "Unknown".to_string());
+                                        
+                                        backup_files.push(BackupFileInfo {
+                                            path: path.to_string_lossy().to_string(),
+                                            filename: filename_str.to_string(),
+                                            size: metadata.len(),
+                                            modified,
+                                        });
                                     }
                                 }
                             }
@@ -2402,6 +2387,587 @@ fn delete_backup_file(file_path: String) -> Result<(), String> {
     Ok(())
 }
 
+// ============================================================================
+// Profile Commands
+// ============================================================================
+
+fn load_or_default_settings() -> Result<AppSettings, String> {
+    match load_app_settings()? {
+        Some(settings) => Ok(settings),
+        None => get_app_settings(),
+    }
+}
+
+#[tauri::command]
+fn list_profiles() -> Result<Vec<ProfileSummary>, String> {
+    let settings = load_or_default_settings()?;
+    let active = settings.active();
+    
+    if settings.profiles.is_empty() {
+        return Ok(vec![ProfileSummary {
+            name: active.name,
+            hc3_host: active.hc3_host,
+            homemap_path: active.homemap_path,
+            active: true,
+        }]);
+    }
+    
+    Ok(settings.profiles.iter()
+        .map(|p| ProfileSummary {
+            name: p.name.clone(),
+            hc3_host: p.hc3_host.clone(),
+            homemap_path: p.homemap_path.clone(),
+            active: p.name == active.name,
+        })
+        .collect())
+}
+
+// Create or update a profile. Saving the active profile also updates the current connection.
+#[tauri::command]
+fn save_profile(mut profile: HomeProfile, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("Profile name cannot be empty".to_string());
+    }
+    
+    let mut settings = load_or_default_settings()?;
+    settings.store_active_profile();
+    
+    // The settings form never holds the stored password, so a new profile saved
+    // from it without one keeps the password of the current connection
+    if profile.hc3_password.is_empty() && vault_password(&profile.name)?.is_none() {
+        let active = settings.active();
+        profile.hc3_password = vault_password(&active.name)?.unwrap_or(active.hc3_password);
+    }
+    
+    match settings.profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile.clone(),
+        None => settings.profiles.push(profile.clone()),
+    }
+    if settings.active_profile.as_deref() == Some(profile.name.as_str()) {
+        settings.apply_profile(&profile);
+    }
+    
+    write_app_settings(&settings)?;
+    state.invalidate();
+    println!("Saved profile '{}'", profile.name);
+    Ok(())
+}
+
+#[tauri::command]
+fn delete_profile(name: String) -> Result<(), String> {
+    let mut settings = load_or_default_settings()?;
+    if settings.active().name == name {
+        return Err("Cannot delete the active profile. Switch to another profile first.".to_string());
+    }
+    
+    let before = settings.profiles.len();
+    settings.profiles.retain(|p| p.name != name);
+    if settings.profiles.len() == before {
+        return Err(format!("Profile '{}' not found", name));
+    }
+    
+    write_app_settings(&settings)?;
+    if let Err(e) = vault_store()?.delete(&vault::password_account(&name)) {
+        println!("Failed to remove HC3 password for profile '{}' from vault: {}", name, e);
+    }
+    println!("Deleted profile '{}'", name);
+    Ok(())
+}
+
+// Make another profile active, stop services bound to the current home and reload the webview
+#[tauri::command]
+async fn switch_profile(
+    name: String,
+    app: tauri::AppHandle,
+    ws_state: tauri::State<'_, Arc<Mutex<Option<Arc<WebSocketServer>>>>>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let mut settings = load_or_default_settings()?;
+    settings.store_active_profile();
+    
+    let profile = settings.profiles.iter()
+        .find(|p| p.name == name)
+        .cloned()
+        .ok_or_else(|| format!("Profile '{}' not found", name))?;
+    
+    if settings.active_profile.as_deref() != Some(name.as_str()) {
+        settings.apply_profile(&profile);
+        write_app_settings(&settings)?;
+        println!("Switched to profile '{}'", name);
+    }
+    state.invalidate();
+    
+    // Make sure the new home's data directory exists and is initialized before reloading
+    let data_path = state.data_dir()?;
+    println!("Profile '{}' data path: {:?}", name, data_path);
+    
+    if let Some(server) = ws_state.lock().await.take() {
+        server.stop().await?;
+    }
+    
+    if let Some(window) = app.get_webview_window("main") {
+        window.eval("window.location.reload()")
+            .map_err(|e| format!("Failed to reload window: {}", e))?;
+    }
+    
+    Ok(())
+}
+
+// ============================================================================
+// Data Relocation Commands
+// ============================================================================
+
+// Point settings.json at the moved data directory: every profile (and the
+// top-level fields) that used the old folder, or the active profile when the
+// old folder was the default location and not named in settings.json
+fn repoint_homemap_path(old: &std::path::Path, new: &std::path::Path) -> Result<(), String> {
+    let mut settings = load_or_default_settings()?;
+    let new_path = new.to_string_lossy().to_string();
+    let uses_old = |path: &str| resolve_settings_relative(path)
+        .map(|resolved| !resolved.is_empty() && std::path::Path::new(&resolved) == old)
+        .unwrap_or(false);
+    
+    let mut changed = false;
+    if uses_old(&settings.homemap_path) {
+        settings.homemap_path = new_path.clone();
+        changed = true;
+    }
+    for profile in settings.profiles.iter_mut().filter(|p| uses_old(&p.homemap_path)) {
+        profile.homemap_path = new_path.clone();
+        changed = true;
+    }
+    if !changed {
+        settings.homemap_path = new_path;
+        if !settings.profiles.is_empty() {
+            settings.store_active_profile();
+        }
+    }
+    
+    write_app_settings(&settings)?;
+    println!("Settings now point at data directory {:?}", new);
+    Ok(())
+}
+
+// Move the data directory to a new location. The copy is verified before the
+// settings are changed and the old folder is removed. Progress is reported
+// with "data-relocate-progress" events.
+#[tauri::command]
+async fn relocate_data_directory(app: tauri::AppHandle, destination: String, state: tauri::State<'_, AppState>) -> Result<relocate::RelocateResult, String> {
+    if settings_layers::cli_args().data_dir.is_some() {
+        return Err("The data directory is set with --data-dir; change it there instead".to_string());
+    }
+    let from = state.data_dir()?;
+    let to = PathBuf::from(destination);
+    
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let emit = |progress: &relocate::RelocateProgress| {
+            let _ = app.emit("data-relocate-progress", progress);
+        };
+        relocate::relocate(&from, &to, &emit, |new_path| repoint_homemap_path(&from, new_path))
+    })
+    .await
+    .map_err(|e| format!("Relocation task failed: {}", e))??;
+    
+    state.invalidate();
+    Ok(result)
+}
+
+// ============================================================================
+// Storage Cleanup Commands
+// ============================================================================
+
+// Delete stale temp entries and list unused images and icon sets
+#[tauri::command]
+fn scan_storage_cleanup(state: tauri::State<'_, AppState>) -> Result<cleanup::CleanupScan, String> {
+    let data_path = state.data_dir()?;
+    Ok(cleanup::scan(&data_path))
+}
+
+#[tauri::command]
+fn move_to_trash(paths: Vec<String>, state: tauri::State<'_, AppState>) -> Result<cleanup::TrashBatch, String> {
+    let data_path = state.data_dir()?;
+    cleanup::move_to_trash(&data_path, &paths)
+}
+
+#[tauri::command]
+fn list_trash(state: tauri::State<'_, AppState>) -> Result<Vec<cleanup::TrashBatch>, String> {
+    let data_path = state.data_dir()?;
+    Ok(cleanup::list_trash(&data_path))
+}
+
+#[tauri::command]
+fn restore_from_trash(batch_id: String, state: tauri::State<'_, AppState>) -> Result<cleanup::TrashRestore, String> {
+    let data_path = state.data_dir()?;
+    cleanup::restore_from_trash(&data_path, &batch_id)
+}
+
+// Permanently delete the trash; returns the number of bytes freed
+#[tauri::command]
+fn empty_trash(state: tauri::State<'_, AppState>) -> Result<u64, String> {
+    let data_path = state.data_dir()?;
+    cleanup::empty_trash(&data_path, None)
+}
+
+// ============================================================================
+// Data Integrity Commands
+// ============================================================================
+
+// Check the data directory for inconsistencies; with repair, apply the safe fixes
+#[tauri::command]
+fn check_data_integrity(repair: Option<bool>, state: tauri::State<'_, AppState>) -> Result<integrity::IntegrityReport, String> {
+    let data_path = state.data_dir()?;
+    integrity::check_data_dir(&data_path, repair.unwrap_or(false))
+}
+
+// ============================================================================
+// Portable Mode Commands
+// ============================================================================
+
+#[tauri::command]
+fn get_portable_status() -> portable::PortableStatus {
+    portable::status()
+}
+
+// Copy settings and data next to the executable; takes effect after a restart
+#[tauri::command]
+fn enable_portable_mode(state: tauri::State<'_, AppState>) -> Result<portable::PortableStatus, String> {
+    if portable::is_enabled() {
+        return Err("HomeMap is already running in portable mode".to_string());
+    }
+    if settings_layers::cli_args().settings.is_some() {
+        return Err("Portable mode cannot be enabled while --settings is in use".to_string());
+    }
+    
+    let data_dir = state.data_dir()?;
+    portable::convert_to_portable(&app_config_dir()?, &data_dir)
+}
+
+// Copy portable settings and data back to the standard locations; takes effect after a restart
+#[tauri::command]
+fn disable_portable_mode() -> Result<portable::PortableStatus, String> {
+    if !portable::is_enabled() {
+        return Err("HomeMap is not running in portable mode".to_string());
+    }
+    let status = portable::status();
+    if status.from_argument {
+        return Err(format!("Portable mode was enabled with {}; start HomeMap without it instead", portable::PORTABLE_ARG));
+    }
+    
+    let installed_data = PathBuf::from(installed_homemap_path()?);
+    portable::convert_to_installed(&app_config_dir()?, &installed_data)
+}
+
+// ============================================================================
+// Config Provenance Commands
+// ============================================================================
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ConfigProvenance {
+    settings_file: String,
+    command_line: settings_layers::CliArgs,
+    values: Vec<settings_layers::ResolvedValue>,
+}
+
+// Effective connection settings and the layer each one came from (secrets redacted)
+#[tauri::command]
+fn get_config_provenance(state: tauri::State<'_, AppState>) -> Result<ConfigProvenance, String> {
+    let resolved = state.settings()?;
+    Ok(ConfigProvenance {
+        settings_file: settings_file_path()?.to_string_lossy().to_string(),
+        command_line: settings_layers::cli_args().clone(),
+        values: resolved.redacted(),
+    })
+}
+
+// ============================================================================
+// Credential Vault Commands
+// ============================================================================
+
+#[tauri::command]
+fn get_vault_status() -> Result<vault::VaultStatus, String> {
+    Ok(vault::status(&settings_dir()?))
+}
+
+// Unlock the encrypted-file vault (no-op when the OS keyring is used)
+#[tauri::command]
+fn unlock_vault(passphrase: String, state: tauri::State<'_, AppState>) -> Result<vault::VaultStatus, String> {
+    let config_dir = settings_dir()?;
+    vault::unlock(&config_dir, &passphrase)?;
+    
+    // Migrate a plaintext password left behind while the vault was locked
+    migrate_plaintext_passwords();
+    state.invalidate();
+    
+    Ok(vault::status(&config_dir))
+}
+
+// ============================================================================
+// Auto-map Commands
+// ============================================================================
+
+#[tauri::command]
+async fn generate_automap_preview(include_hidden: Option<bool>, state: tauri::State<'_, AppState>) -> Result<automap::AutoMapPreview, String> {
+    let hc3 = hc3_config(&*state.settings()?);
+    
+    let sections = hc3_get_json(&hc3, "/api/sections").await?;
+    let rooms = hc3_get_json(&hc3, "/api/rooms").await?;
+    let devices = hc3_get_json(&hc3, "/api/devices").await?;
+    
+    let sections: Vec<automap::Hc3Section> = serde_json::from_value(sections)
+        .map_err(|e| format!("Failed to parse HC3 sections: {}", e))?;
+    let rooms: Vec<automap::Hc3Room> = serde_json::from_value(rooms)
+        .map_err(|e| format!("Failed to parse HC3 rooms: {}", e))?;
+    let devices: Vec<automap::Hc3Device> = serde_json::from_value(devices)
+        .map_err(|e| format!("Failed to parse HC3 devices: {}", e))?;
+    
+    let config = state.config()?;
+    let options = automap::AutoMapOptions {
+        include_hidden: include_hidden.unwrap_or(false),
+        available_widgets: discover_built_in_widgets(state.clone())?.into_iter().collect(),
+    };
+    
+    automap::build_preview(sections, rooms, devices, &config, &options)
+}
+
+#[tauri::command]
+fn apply_automap_preview(preview: automap::AutoMapPreview, state: tauri::State<'_, AppState>) -> Result<automap::AutoMapMergeResult, String> {
+    let data_path = state.data_dir()?;
+    let config_path = data_path.join("config.json");
+    
+    let mut config = state.config()?.as_ref().clone();
+    let result = automap::merge_preview(&mut config, &preview)?;
+    
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    fs::write(&config_path, content)
+        .map_err(|e| format!("Failed to write config file: {}", e))?;
+    state.invalidate_config();
+    
+    println!("Applied auto-map preview: {} floors, {} devices added", result.floors_added, result.devices_added);
+    Ok(result)
+}
+
+// ============================================================================
+// Schema Commands
+// ============================================================================
+
+// JSON Schema for "config", "widget", "package-manifest",
+// "websocket-client-message" or "websocket-server-message"
+#[tauri::command]
+fn get_json_schema(kind: String) -> Result<serde_json::Value, String> {
+    schemas::schema_value(&kind)
+}
+
+// (Re)write all schemas into <data>/schemas and return their paths
+#[tauri::command]
+fn write_json_schemas(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    let data_path = state.data_dir()?;
+    let written = schemas::write_schemas(&data_path)?;
+    Ok(written.iter().map(|p| p.to_string_lossy().to_string()).collect())
+}
+
+// Validate a JSON document (as text) against one of the schemas
+#[tauri::command]
+fn validate_json_document(kind: String, content: String) -> Result<Vec<schemas::SchemaError>, String> {
+    let document: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    schemas::validate(&kind, &document)
+}
+
+// ============================================================================
+// Widget Lint Commands
+// ============================================================================
+
+// Icon set folders a widget from `package` can use, in the webview's lookup order
+fn widget_icon_sets(data_path: PathBuf, package: String) -> impl Fn(&str) -> Option<PathBuf> {
+    move |icon_set| floor_export::icon_set_dir(&data_path, icon_set, Some(package.as_str()))
+}
+
+// Widget file for a built-in widget name or a "packageId/widgetId" reference
+fn widget_file(data_path: &std::path::Path, widget: &str) -> (PathBuf, String) {
+    let widgets_dir = data_path.join("widgets");
+    match widget.split_once('/') {
+        Some((package, id)) => (
+            widgets_dir.join("packages").join(package).join(format!("{}.json", id)),
+            package.to_string(),
+        ),
+        None => {
+            let built_in = widgets_dir.join("built-in").join(format!("{}.json", widget));
+            // Fall back to the legacy widgets/ root
+            let legacy = widgets_dir.join(format!("{}.json", widget));
+            let file = if !built_in.exists() && legacy.exists() { legacy } else { built_in };
+            (file, floor_export::BUILT_IN_PACKAGE.to_string())
+        }
+    }
+}
+
+// Check a widget definition and report problems with line and column.
+// `widget` is a built-in widget name or "packageId/widgetId"; pass `content`
+// to check unsaved text instead of the file on disk.
+#[tauri::command]
+fn validate_widget(
+    widget: String,
+    content: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<widget_lint::WidgetLintReport, String> {
+    let data_path = state.data_dir()?;
+    let (file, package) = widget_file(&data_path, &widget);
+    let compose = |definition: &serde_json::Value| widget_compose::compose(&data_path, &package, Some(&file), definition);
+    let icon_sets = widget_icon_sets(data_path.clone(), package.clone());
+    
+    let report = match content {
+        Some(content) => widget_lint::report(&widget, &file, widget_lint::lint_source(&content, Some(&compose), &icon_sets)),
+        None if file.exists() => widget_lint::lint_file(&widget, &file, Some(&compose), &icon_sets),
+        None => return Err(format!("Widget not found: {}", widget)),
+    };
+    println!("Validated widget {}: {} errors, {} warnings", widget, report.errors, report.warnings);
+    Ok(report)
+}
+
+// ============================================================================
+// Widget Resolution Commands
+// ============================================================================
+
+// Resolve a widget definition and report where it came from. `widget` is a
+// device type or a "package:widget" (or "package/widget") reference; for a
+// reference that can't be found, resolution continues with `device_type`.
+#[tauri::command]
+fn resolve_widget(
+    widget: String,
+    device_type: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<widget_resolve::ResolvedWidget, String> {
+    resolve_widget_reference(&state.data_dir()?, &widget, device_type.as_deref())
+}
+
+fn resolve_widget_reference(data_path: &Path, widget: &str, device_type: Option<&str>) -> Result<widget_resolve::ResolvedWidget, String> {
+    let (explicit, device_type) = match widget_resolve::split_reference(widget) {
+        Some(_) => (Some(widget), device_type),
+        None => (None, Some(widget)),
+    };
+    widget_resolve::resolve(data_path, device_type, explicit)
+        .ok_or_else(|| format!("No widget found for {}", widget))
+}
+
+// Values of a widget's computed section for a device state; the webview calls
+// this whenever a device's state changes and merges the result into it
+#[tauri::command]
+fn compute_widget_state(
+    computed: serde_json::Map<String, serde_json::Value>,
+    device_state: serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    widget_computed::compute(&computed, &device_state)
+}
+
+// Evaluate a widget for a device without the webview: the widget's default
+// state overlaid with `device_state`, then `events` (HC3 event objects) applied
+// in order. Returns the resulting state and the icon, subtext and styles.
+#[tauri::command]
+fn evaluate_widget(
+    widget: String,
+    device_type: Option<String>,
+    device_id: String,
+    device_state: Option<serde_json::Map<String, serde_json::Value>>,
+    events: Option<Vec<serde_json::Value>>,
+    state: tauri::State<'_, AppState>,
+) -> Result<widget_runtime::Evaluation, String> {
+    let data_path = state.data_dir()?;
+    let resolved = resolve_widget_reference(&data_path, &widget, device_type.as_deref())?;
+    let events = events.unwrap_or_default();
+    let evaluation = widget_runtime::evaluate(&resolved.definition, &device_id, &device_state.unwrap_or_default(), &events);
+    println!("Evaluated widget {} for device {}: {} events, icon {:?}", widget, device_id, events.len(), evaluation.render.icon);
+    Ok(evaluation)
+}
+
+// All built-in and package widgets with their metadata, for the widget pickers
+#[tauri::command]
+fn list_widgets(state: tauri::State<'_, AppState>) -> Result<Vec<widget_catalog::WidgetEntry>, String> {
+    let data_path = state.data_dir()?;
+    let widgets = widget_catalog::list_widgets(&data_path);
+    println!("Listed {} widgets", widgets.len());
+    Ok(widgets)
+}
+
+// ============================================================================
+// Icon Registry Commands
+// ============================================================================
+
+// All icon sets with their states, formats and sizes, plus the widget icons
+// that are missing from their set
+#[tauri::command]
+fn list_icon_sets(state: tauri::State<'_, AppState>) -> Result<icon_registry::IconRegistry, String> {
+    let data_path = state.data_dir()?;
+    let registry = icon_registry::icon_registry(&data_path);
+    let broken: usize = registry.sets.iter().map(|set| set.errors).sum();
+    println!(
+        "Listed {} icon sets ({} broken icons, {} missing widget icons)",
+        registry.sets.len(),
+        broken,
+        registry.missing_icons.len()
+    );
+    Ok(registry)
+}
+
+// Tinted and/or badged variant of an SVG icon. `icon_path` is data-relative
+// (as in the webview's icon set maps); so is the returned path of the cached
+// variant.
+#[tauri::command]
+fn generate_icon_variant(
+    icon_path: String,
+    variant: icon_variants::IconVariant,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let data_path = state.data_dir()?;
+    let relative = Path::new(&icon_path);
+    if relative.is_absolute() || relative.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return Err(format!("Icon path must be inside the data directory: {}", icon_path));
+    }
+    let file = icon_variants::variant_file(&data_path, &data_path.join(relative), &variant)?;
+    Ok(file.strip_prefix(&data_path).unwrap_or(&file).to_string_lossy().replace('\\', "/"))
+}
+
+// ============================================================================
+// Floor Export Commands
+// ============================================================================
+
+// Render a floor with its device icons to a PNG or SVG file.
+// With live_state the current device values are fetched from the HC3 through
+// each widget's getters; otherwise the widget defaults and stored state are used.
+#[tauri::command]
+async fn export_floor_plan(
+    floor_id: String,
+    output_path: String,
+    options: floor_export::ExportOptions,
+    live_state: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<floor_export::ExportResult, String> {
+    let data_path = state.data_dir()?;
+    let config = state.config()?;
+    
+    let mut live_states: HashMap<String, serde_json::Map<String, serde_json::Value>> = HashMap::new();
+    if live_state.unwrap_or(false) {
+        let hc3 = hc3_config(&*state.settings()?);
+        for (device, _) in floor_export::devices_on_floor(&config, &floor_id) {
+            let Some(device_id) = device.get("id").map(floor_export::id_string) else {
+                continue;
+            };
+            let Some(widget) = floor_export::resolve_device_widget(&data_path, device) else {
+                continue;
+            };
+            let mut device_state = serde_json::Map::new();
+            for (property, api, path) in floor_export::getter_requests(&widget.definition, &device_id) {
+                match hc3_get_json(&hc3, &api).await {
+                    Ok(response) => widget_runtime::apply_getter(&mut device_state, &property, &path, &response),
+                    Err(e) => println!("Warning: failed to fetch {} for device {}: {}", property, device_id, e),
+                }
+            }
+            live_states.insert(device_id, device_state);
+        }
+    }
+    
+    floor_export::export_floor(&data_path, &config, &floor_id, &PathBuf::from(output_path), &options, &live_states)
+}
+
 // ============================================================================
 // WebSocket Commands
 // ============================================================================
@@ -2506,6 +3072,13 @@ async fn ws_request_widgets(
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Portable installs keep their log output next to the executable
+    if portable::is_enabled() {
+        if let Err(e) = portable::redirect_output() {
+            eprintln!("{}", e);
+        }
+    }
+    
     let builder = tauri::Builder::default()
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_fs::init())
@@ -2520,6 +3093,7 @@ pub fn run() {
     
     builder
         .manage(Arc::new(Mutex::new(None::<Arc<WebSocketServer>>)))
+        .manage(AppState::default())
         .invoke_handler(tauri::generate_handler![
             http_fetch_insecure,
             get_hc3_config, 
@@ -2528,6 +3102,8 @@ pub fn run() {
             get_data_path,
             get_app_version,
             sync_resources,
+            resync_builtin_resources,
+            get_builtin_sync_report,
             read_image_as_base64,
             write_file_base64,
             write_file_as_text,
@@ -2544,6 +3120,13 @@ pub fn run() {
             select_homemap_folder,
             load_app_settings,
             extract_widget_package,
+            list_trusted_publishers,
+            trust_publisher,
+            untrust_publisher,
+            install_package,
+            discard_extracted_package,
+            upgrade_package,
+            uninstall_package,
             copy_file,
             create_dir,
             path_exists,
@@ -2554,6 +3137,39 @@ pub fn run() {
             delete_backup_file,
             is_mobile_platform,
             apply_ui_preferences_to_current_config,
+            diff_homemap_config,
+            merge_homemap_config,
+            validate_widget,
+            resolve_widget,
+            list_widgets,
+            evaluate_widget,
+            compute_widget_state,
+            list_icon_sets,
+            generate_icon_variant,
+            relocate_data_directory,
+            scan_storage_cleanup,
+            move_to_trash,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            check_data_integrity,
+            get_portable_status,
+            enable_portable_mode,
+            disable_portable_mode,
+            get_config_provenance,
+            hc3_request,
+            get_vault_status,
+            unlock_vault,
+            list_profiles,
+            save_profile,
+            delete_profile,
+            switch_profile,
+            export_floor_plan,
+            get_json_schema,
+            write_json_schemas,
+            validate_json_document,
+            generate_automap_preview,
+            apply_automap_preview,
             ws_start_server,
             ws_stop_server,
             ws_send_to_client,
@@ -2563,6 +3179,8 @@ pub fn run() {
             ws_request_widgets
         ])
         .setup(|app| {
+            migrate_plaintext_passwords();
+            
             // Mobile: Initialize data directory with bundled resources
             #[cfg(any(target_os = "ios", target_os = "android"))]
             {
@@ -2578,31 +3196,25 @@ pub fn run() {
                     PathBuf::from(home).join("Documents").join("homemapdata")
                 };
                 
-                if !data_dir.exists() {
-                    println!("Creating homemapdata directory: {:?}", data_dir);
-                    if let Err(e) = fs::create_dir_all(&data_dir) {
-                        eprintln!("Failed to create data directory: {}", e);
-                    } else {
-                        // Try to extract bundled template resources
-                        match extract_bundled_template(&app.handle(), &data_dir) {
-                            Ok(_) => {
-                                println!("Successfully initialized from bundled resources");
-                            }
-                            Err(e) => {
-                                eprintln!("Could not extract bundled resources: {}", e);
-                                eprintln!("Falling back to minimal initialization");
-                                // Fall back to minimal config creation
-                                if let Err(e2) = initialize_default_config(&data_dir) {
-                                    eprintln!("Failed to create minimal config: {}", e2);
-                                }
-                            }
-                        }
-                    }
-                } else {
-                    println!("Data directory already exists: {:?}", data_dir);
+                // Extracts the embedded template on first run, syncs built-ins afterwards
+                if let Err(e) = ensure_data_dir(&data_dir) {
+                    eprintln!("Failed to initialize data directory: {}", e);
                 }
             }
             
+            // Portable installs keep settings, data, backups and logs next to the executable
+            if portable::is_enabled() {
+                if let Err(e) = portable::ensure_layout() {
+                    eprintln!("Failed to create portable folders: {}", e);
+                }
+            }
+            
+            // Remove stale temp extractions and purge old trash
+            match app.state::<AppState>().data_dir() {
+                Ok(data_path) => cleanup::run_startup_cleanup(&data_path),
+                Err(e) => eprintln!("Skipping startup cleanup: {}", e),
+            }
+            
             // Desktop-only: Create menu
             #[cfg(not(any(target_os = "ios", target_os = "android")))]
             {
@@ -0,0 +1,241 @@
+// Widget package extraction
+// Packages come from forum posts and downloads, so what an .hwp may unpack is
+// bounded before anything is written:
+//   - at most maxEntries entries, maxFileBytes per file and maxTotalBytes in
+//     total; sizes are checked against what the archive declares and again
+//     while writing, as the declared sizes can lie
+//   - only files with an allowed extension are extracted; others are skipped
+//     and reported
+//   - symlinks and paths leading out of the package reject the whole package
+// Limits come from "packageLimits" in config.json; missing values use the
+// defaults below.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+const MB: u64 = 1024 * 1024;
+
+/// Limits for extracting widget packages (.hwp)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PackageLimits {
+    /// Most entries (files and folders) a package may contain
+    pub max_entries: usize,
+    /// Largest uncompressed size of one file, in bytes
+    pub max_file_bytes: u64,
+    /// Largest uncompressed size of the whole package, in bytes
+    pub max_total_bytes: u64,
+    /// File extensions that are extracted; other files are skipped
+    pub allowed_extensions: Vec<String>,
+}
+
+impl Default for PackageLimits {
+    fn default() -> Self {
+        PackageLimits {
+            max_entries: 1000,
+            max_file_bytes: 10 * MB,
+            max_total_bytes: 50 * MB,
+            allowed_extensions: ["json", "svg", "png", "md"].iter().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+impl PackageLimits {
+    fn allows(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| self.allowed_extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(ext)))
+    }
+}
+
+// Limits from config.json in the data directory
+pub fn load_limits(data_path: &Path) -> PackageLimits {
+    let Some(limits) = fs::read_to_string(data_path.join("config.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .and_then(|config| config.get("packageLimits").cloned()) else {
+        return PackageLimits::default();
+    };
+    serde_json::from_value(limits).unwrap_or_else(|e| {
+        println!("Invalid packageLimits in config.json, using defaults: {}", e);
+        PackageLimits::default()
+    })
+}
+
+fn too_large(what: &str, limit: u64) -> String {
+    let limit = if limit < MB { format!("{} bytes", limit) } else { format!("{:.1} MB", limit as f64 / MB as f64) };
+    format!("Package rejected: {} is larger than the limit of {}", what, limit)
+}
+
+// Extract the archive into `dir` within `limits`; returns the skipped files
+pub fn extract<R: Read + Seek>(archive: &mut ZipArchive<R>, dir: &Path, limits: &PackageLimits) -> Result<Vec<String>, String> {
+    if archive.len() > limits.max_entries {
+        return Err(format!("Package rejected: {} entries, the limit is {}", archive.len(), limits.max_entries));
+    }
+
+    // Check every entry before writing anything
+    let mut declared_total = 0u64;
+    let mut skipped = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i).map_err(|e| format!("Failed to read file from archive: {}", e))?;
+        if file.is_symlink() {
+            return Err(format!("Package rejected: {} is a symbolic link", file.name()));
+        }
+        let Some(path) = file.enclosed_name() else {
+            return Err(format!("Package rejected: {} points outside the package", file.name()));
+        };
+        if file.is_dir() {
+            continue;
+        }
+        // Skipped files count too: the signature check still reads them
+        if file.size() > limits.max_file_bytes {
+            return Err(too_large(file.name(), limits.max_file_bytes));
+        }
+        declared_total += file.size();
+        if !limits.allows(&path) {
+            skipped.push(file.name().to_string());
+        }
+    }
+    if declared_total > limits.max_total_bytes {
+        return Err(too_large("the unpacked package", limits.max_total_bytes));
+    }
+
+    let mut total = 0u64;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(|e| format!("Failed to read file from archive: {}", e))?;
+        let Some(path) = file.enclosed_name() else {
+            continue;
+        };
+        let outpath = dir.join(&path);
+        if file.is_dir() {
+            fs::create_dir_all(&outpath).map_err(|e| format!("Failed to create directory: {}", e))?;
+            continue;
+        }
+        if !limits.allows(&path) {
+            continue;
+        }
+        if let Some(parent) = outpath.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create parent directory: {}", e))?;
+        }
+        let name = file.name().to_string();
+        let mut outfile = fs::File::create(&outpath).map_err(|e| format!("Failed to create file: {}", e))?;
+        // Read one byte past the limit to notice entries that are bigger than declared
+        let written = std::io::copy(&mut file.take(limits.max_file_bytes + 1), &mut outfile)
+            .map_err(|e| format!("Failed to extract file: {}", e))?;
+        if written > limits.max_file_bytes {
+            return Err(too_large(&name, limits.max_file_bytes));
+        }
+        total += written;
+        if total > limits.max_total_bytes {
+            return Err(too_large("the unpacked package", limits.max_total_bytes));
+        }
+    }
+
+    for name in &skipped {
+        println!("Skipped package file with a disallowed type: {}", name);
+    }
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::{FileOptions, ZipWriter};
+    use zip::CompressionMethod;
+
+    fn limits() -> PackageLimits {
+        PackageLimits { max_entries: 10, max_file_bytes: 100, max_total_bytes: 150, ..PackageLimits::default() }
+    }
+
+    fn package(files: &[(&str, Vec<u8>)], build: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>)) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options: FileOptions<()> = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, content) in files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        build(&mut zip);
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn extract_bytes(bytes: Vec<u8>) -> (Result<Vec<String>, String>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("package-extract-test-{}-{}", std::process::id(), chrono::Local::now().format("%H%M%S%f")));
+        fs::create_dir_all(&dir).unwrap();
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        (extract(&mut archive, &dir, &limits()), dir)
+    }
+
+    fn rejected(bytes: Vec<u8>) -> String {
+        let (result, dir) = extract_bytes(bytes);
+        // Nothing is written for a package rejected before extraction
+        let written = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        let error = result.unwrap_err();
+        assert_eq!(written, 0, "{}", error);
+        error
+    }
+
+    // Patch the declared uncompressed size of the only entry, as a crafted archive would
+    fn declare_size(mut bytes: Vec<u8>, size: u32) -> Vec<u8> {
+        for (signature, offset) in [([0x50, 0x4b, 0x03, 0x04], 22), ([0x50, 0x4b, 0x01, 0x02], 24)] {
+            let at = bytes.windows(4).position(|w| w == signature).unwrap() + offset;
+            bytes[at..at + 4].copy_from_slice(&size.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn extracts_allowed_files_and_skips_others() {
+        let bytes = package(&[("manifest.json", b"{}".to_vec()), ("icons/a/on.svg", b"<svg/>".to_vec()), ("run.sh", b"x".to_vec())], |_| {});
+        let (result, dir) = extract_bytes(bytes);
+        assert_eq!(result.unwrap(), vec!["run.sh".to_string()]);
+        assert!(dir.join("icons/a/on.svg").is_file());
+        assert!(!dir.join("run.sh").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_symlinks() {
+        let bytes = package(&[("manifest.json", b"{}".to_vec())], |zip| {
+            zip.add_symlink("icons", "/etc", FileOptions::<()>::default()).unwrap();
+        });
+        assert!(rejected(bytes).contains("symbolic link"));
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_package() {
+        let bytes = package(&[("manifest.json", b"{}".to_vec()), ("../evil.json", b"{}".to_vec())], |_| {});
+        assert!(rejected(bytes).contains("outside the package"));
+    }
+
+    #[test]
+    fn rejects_too_many_entries() {
+        let files: Vec<(String, Vec<u8>)> = (0..11).map(|i| (format!("{}.json", i), b"{}".to_vec())).collect();
+        let files: Vec<(&str, Vec<u8>)> = files.iter().map(|(name, content)| (name.as_str(), content.clone())).collect();
+        assert!(rejected(package(&files, |_| {})).contains("11 entries"));
+    }
+
+    #[test]
+    fn rejects_declared_oversize() {
+        let bytes = package(&[("big.json", vec![b' '; 101])], |_| {});
+        assert!(rejected(bytes).contains("big.json is larger"));
+        let bytes = package(&[("a.json", vec![b' '; 80]), ("b.md", vec![b' '; 80])], |_| {});
+        assert!(rejected(bytes).contains("the unpacked package is larger"));
+    }
+
+    #[test]
+    fn rejects_files_larger_than_declared() {
+        let bytes = declare_size(package(&[("big.json", vec![b' '; 5000])], |_| {}), 10);
+        let (result, dir) = extract_bytes(bytes);
+        let written = fs::metadata(dir.join("big.json")).map(|m| m.len()).unwrap_or(0);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(result.unwrap_err().contains("big.json is larger"));
+        assert!(written <= 101);
+    }
+}
@@ -0,0 +1,511 @@
+// Package installation
+// Installing, upgrading or uninstalling a package changes widgets/packages/<id>/,
+// icons/packages/<id>/, installed-packages.json and widget-mappings.json. Each
+// operation runs as one transaction:
+//   1. stage: the new package folders are built in temp/transaction-<n>/ and
+//      the new registry contents are worked out in memory
+//   2. commit: the current folders are moved into the transaction folder, the
+//      staged ones are moved into place and the registries are written (to a
+//      .tmp file, then renamed)
+//   3. if any commit step fails, the steps done so far are undone in reverse
+//      order, leaving the data directory as it was. If that fails too, the
+//      transaction folder (with the backups) is moved to recovery/, since
+//      temp/ is cleared at startup
+// Everything lives on the data directory's file system, so the moves are renames.
+// Uninstalling leaves everything in place while devices in config.json still
+// use the package (by widget reference, widget mapping or icon package), unless
+// forced; the result then has committed false and lists those devices.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cleanup::TEMP_DIR;
+use crate::widget_resolve::split_reference;
+use crate::PackageManifest;
+
+const PACKAGES_FILE: &str = "installed-packages.json";
+const MAPPINGS_FILE: &str = "widget-mappings.json";
+const RECOVERY_DIR: &str = "recovery";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageChange {
+    pub package_id: String,
+    // False when nothing was changed (an uninstall refused because of affected_devices)
+    pub committed: bool,
+    // None after an uninstall
+    pub version: Option<String>,
+    // None for a fresh install
+    pub previous_version: Option<String>,
+    // Device types whose widget mapping pointed at a widget that is now gone
+    pub removed_mappings: Vec<String>,
+    // Devices still using a widget or icon set that is now gone
+    pub affected_devices: Vec<String>,
+}
+
+// A commit step that has been carried out, with what's needed to undo it
+enum Step {
+    Moved { from: PathBuf, to: PathBuf },
+    Wrote { path: PathBuf, original: Option<Vec<u8>> },
+}
+
+struct Transaction {
+    data_path: PathBuf,
+    dir: PathBuf,
+    done: Vec<Step>,
+}
+
+impl Transaction {
+    fn begin(data_path: &Path) -> Result<Self, String> {
+        let stamp = chrono::Local::now().format("%Y%m%d%H%M%S%f");
+        let dir = data_path.join(TEMP_DIR).join(format!("transaction-{}", stamp));
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        Ok(Transaction { data_path: data_path.to_path_buf(), dir, done: Vec::new() })
+    }
+
+    fn staging(&self, name: &str) -> PathBuf {
+        self.dir.join("staged").join(name)
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> Result<(), String> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create {:?}: {}", parent, e))?;
+        }
+        fs::rename(from, to).map_err(|e| format!("Failed to move {:?} to {:?}: {}", from, to, e))?;
+        self.done.push(Step::Moved { from: from.to_path_buf(), to: to.to_path_buf() });
+        Ok(())
+    }
+
+    // Put `staged` (if any) at `target`, keeping what was there for rollback
+    fn replace_dir(&mut self, target: &Path, staged: Option<&Path>, backup: &str) -> Result<(), String> {
+        if target.exists() {
+            let backup = self.dir.join("backup").join(backup);
+            self.rename(target, &backup)?;
+        }
+        match staged {
+            Some(staged) if staged.exists() => self.rename(staged, target),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_json(&mut self, path: &Path, value: &Value) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(value)
+            .map_err(|e| format!("Failed to serialize {:?}: {}", path.file_name(), e))?;
+        let original = fs::read(path).ok();
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json).map_err(|e| format!("Failed to write {:?}: {}", tmp, e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("Failed to replace {:?}: {}", path, e))?;
+        self.done.push(Step::Wrote { path: path.to_path_buf(), original });
+        Ok(())
+    }
+
+    // Undo the steps done so far; returns false if any of them couldn't be undone
+    fn rollback(&mut self) -> bool {
+        let mut complete = true;
+        while let Some(step) = self.done.pop() {
+            let result = match &step {
+                Step::Moved { from, to } => fs::rename(to, from),
+                Step::Wrote { path, original: Some(original) } => fs::write(path, original),
+                Step::Wrote { path, original: None } => fs::remove_file(path),
+            };
+            if let Err(e) = result {
+                // Keep going; the transaction folder still holds the backups
+                println!("Failed to roll back package change: {}", e);
+                complete = false;
+            }
+        }
+        complete
+    }
+
+    // Run the commit steps; on failure undo them and keep the error
+    fn commit<F: FnOnce(&mut Self) -> Result<(), String>>(mut self, steps: F) -> Result<(), String> {
+        let result = steps(&mut self);
+        if result.is_err() {
+            println!("Rolling back package change");
+            if !self.rollback() {
+                // The backups may be the only copy of the previous package left
+                let kept = self.keep_for_recovery();
+                println!("Rollback incomplete; keeping {:?} for manual recovery", kept);
+                return result;
+            }
+        }
+        self.discard();
+        result
+    }
+
+    // Move the transaction folder out of temp/ so startup cleanup leaves it alone
+    fn keep_for_recovery(self) -> PathBuf {
+        let target = self.data_path.join(RECOVERY_DIR).join(self.dir.file_name().unwrap_or_default());
+        let moved = fs::create_dir_all(self.data_path.join(RECOVERY_DIR)).and_then(|_| fs::rename(&self.dir, &target));
+        match moved {
+            Ok(()) => target,
+            Err(e) => {
+                println!("Failed to move {:?} to {:?}: {}", self.dir, target, e);
+                self.dir
+            }
+        }
+    }
+
+    // Remove the transaction folder with the staged files and backups
+    fn discard(self) {
+        if let Err(e) = fs::remove_dir_all(&self.dir) {
+            println!("Failed to remove {:?}: {}", self.dir, e);
+        }
+    }
+}
+
+fn read_json(path: &Path, default: Value) -> Result<Value, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path.file_name(), e)),
+        Err(_) => Ok(default),
+    }
+}
+
+fn object_mut<'a>(value: &'a mut Value, key: &str) -> &'a mut Map<String, Value> {
+    if !value.get(key).is_some_and(|v| v.is_object()) {
+        value[key] = json!({});
+    }
+    value[key].as_object_mut().expect("just set to an object")
+}
+
+fn copy_dir(src: &Path, dst: &Path) -> Result<(), String> {
+    fs::create_dir_all(dst).map_err(|e| format!("Failed to create {:?}: {}", dst, e))?;
+    for entry in fs::read_dir(src).map_err(|e| format!("Failed to read {:?}: {}", src, e))? {
+        let path = entry.map_err(|e| format!("Failed to read {:?}: {}", src, e))?.path();
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        if path.is_dir() {
+            copy_dir(&path, &dst.join(name))?;
+        } else {
+            fs::copy(&path, dst.join(name)).map_err(|e| format!("Failed to copy {:?}: {}", path, e))?;
+        }
+    }
+    Ok(())
+}
+
+// Copy the package's manifest, widgets and icon sets from the extracted folder
+// into the layout they are installed with
+fn stage(transaction: &Transaction, extracted: &Path, manifest: &PackageManifest) -> Result<(PathBuf, PathBuf), String> {
+    let widgets = transaction.staging("widgets");
+    let icons = transaction.staging("icons");
+    fs::create_dir_all(&widgets).map_err(|e| format!("Failed to create {:?}: {}", widgets, e))?;
+    fs::copy(extracted.join("manifest.json"), widgets.join("manifest.json"))
+        .map_err(|e| format!("Failed to copy manifest: {}", e))?;
+
+    let widgets_dir = extracted.join("widgets");
+    let single = extracted.join("widget.json");
+    if widgets_dir.is_dir() {
+        for entry in fs::read_dir(&widgets_dir).map_err(|e| format!("Failed to read package widgets: {}", e))? {
+            let path = entry.map_err(|e| format!("Failed to read package widgets: {}", e))?.path();
+            if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("json") {
+                let name = path.file_name().unwrap_or_default();
+                fs::copy(&path, widgets.join(name)).map_err(|e| format!("Failed to copy {:?}: {}", name, e))?;
+            }
+        }
+    } else if let (true, Some(widget_id)) = (single.is_file(), manifest.provides.widgets.first()) {
+        // A single widget.json is installed under the first widget id
+        fs::copy(&single, widgets.join(format!("{}.json", widget_id)))
+            .map_err(|e| format!("Failed to copy widget.json: {}", e))?;
+    }
+    for widget_id in &manifest.provides.widgets {
+        if !widgets.join(format!("{}.json", widget_id)).is_file() {
+            return Err(format!("Package lists widget '{}' but has no {}.json", widget_id, widget_id));
+        }
+    }
+
+    let icons_dir = extracted.join("icons");
+    if icons_dir.is_dir() {
+        copy_dir(&icons_dir, &icons)?;
+    }
+    Ok((widgets, icons))
+}
+
+// Devices in config.json using one of the package's widgets (all of them when
+// `widgets` is None), either by reference or through a mapping for their type,
+// or using its icon sets
+fn devices_using(data_path: &Path, mappings: &Value, package_id: &str, widgets: Option<&[String]>) -> Vec<String> {
+    let Ok(config) = read_json(&data_path.join("config.json"), json!({})) else {
+        return Vec::new();
+    };
+    let listed = |widget: &str| widgets.is_none_or(|widgets| widgets.iter().any(|w| w == widget));
+    let mut devices = Vec::new();
+    for device in config.get("devices").and_then(|d| d.as_array()).into_iter().flatten() {
+        let reference = device.get("widget").and_then(|w| w.as_str()).and_then(split_reference);
+        let uses_widget = reference.is_some_and(|(package, widget)| package == package_id && listed(widget));
+        let device_type = device.get("type").and_then(|t| t.as_str());
+        let mapping = device_type.and_then(|t| mappings.get("mappings")?.get(t));
+        let uses_mapping = reference.is_none()
+            && mapping.is_some_and(|mapping| {
+                let widget = mapping.get("widget").and_then(|w| w.as_str()).or(device_type).unwrap_or_default();
+                mapping.get("package").and_then(|p| p.as_str()) == Some(package_id) && listed(widget)
+            });
+        let uses_icons = widgets.is_none()
+            && device.get("params").and_then(|p| p.get("iconPackage")).and_then(|p| p.as_str()) == Some(package_id);
+        if uses_widget || uses_mapping || uses_icons {
+            let id = device.get("id").map(|id| id.to_string().trim_matches('"').to_string()).unwrap_or_default();
+            let name = device.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            devices.push(format!("{} ({})", name, id));
+        }
+    }
+    devices
+}
+
+// Drop the mappings to the package's widgets that aren't in `keep`; returns
+// the device types that lost their mapping
+fn remove_mappings(mappings: &mut Value, package_id: &str, keep: &[String]) -> Vec<String> {
+    let mut removed = Vec::new();
+    object_mut(mappings, "mappings").retain(|device_type, mapping| {
+        let gone = mapping.get("package").and_then(|p| p.as_str()) == Some(package_id)
+            && !mapping.get("widget").and_then(|w| w.as_str()).is_some_and(|w| keep.iter().any(|k| k == w));
+        if gone {
+            removed.push(device_type.clone());
+        }
+        !gone
+    });
+    removed
+}
+
+// `raw_manifest` is manifest.json as read, so fields PackageManifest doesn't
+// know about are kept in the registry
+fn registry_entry(manifest: &PackageManifest, raw_manifest: Value, installed_at: Value) -> Value {
+    let id = &manifest.id;
+    json!({
+        "version": manifest.version,
+        "installedAt": installed_at,
+        "updatedAt": chrono::Utc::now().to_rfc3339(),
+        "installedFrom": "local",
+        "manifest": raw_manifest,
+        "files": {
+            "widgets": manifest.provides.widgets.iter().map(|w| format!("widgets/packages/{}/{}.json", id, w)).collect::<Vec<_>>(),
+            "icons": manifest.provides.icon_sets.iter().map(|s| format!("icons/packages/{}/{}/", id, s)).collect::<Vec<_>>(),
+        }
+    })
+}
+
+// Install (upgrade false) or upgrade (upgrade true) the package extracted to
+// `extracted` by extract_widget_package
+pub fn install(data_path: &Path, extracted: &Path, upgrade: bool) -> Result<PackageChange, String> {
+    let manifest_content = fs::read_to_string(extracted.join("manifest.json"))
+        .map_err(|e| format!("Failed to read manifest: {}", e))?;
+    let raw_manifest: Value = serde_json::from_str(&manifest_content)
+        .map_err(|e| format!("Failed to parse manifest: {}", e))?;
+    let manifest: PackageManifest = serde_json::from_value(raw_manifest.clone())
+        .map_err(|e| format!("Failed to parse manifest: {}", e))?;
+    let id = manifest.id.clone();
+    if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+        return Err(format!("Invalid package id '{}'", id));
+    }
+
+    let packages_path = data_path.join(PACKAGES_FILE);
+    let mappings_path = data_path.join(MAPPINGS_FILE);
+    let mut packages = read_json(&packages_path, json!({ "version": "1.0", "packages": {} }))?;
+    let mut mappings = read_json(&mappings_path, json!({ "version": "1.0", "mappings": {}, "defaults": {} }))?;
+
+    let previous = packages.get("packages").and_then(|p| p.get(&id)).cloned();
+    match (&previous, upgrade) {
+        (Some(previous), false) => {
+            return Err(format!(
+                "Package {} is already installed (v{}); upgrade it instead",
+                id,
+                previous.get("version").and_then(|v| v.as_str()).unwrap_or("?")
+            ));
+        }
+        (None, true) => return Err(format!("Package {} is not installed", id)),
+        _ => {}
+    }
+    let previous_version = previous.as_ref().and_then(|p| p.get("version")).and_then(|v| v.as_str()).map(|v| v.to_string());
+    let installed_at = previous
+        .as_ref()
+        .and_then(|p| p.get("installedAt").cloned())
+        .unwrap_or_else(|| Value::String(chrono::Utc::now().to_rfc3339()));
+
+    // Widgets the old version had that the new one drops
+    let dropped: Vec<String> = previous
+        .as_ref()
+        .and_then(|p| p.pointer("/manifest/provides/widgets"))
+        .and_then(|w| w.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|w| w.as_str())
+        .filter(|w| !manifest.provides.widgets.iter().any(|n| n == w))
+        .map(|w| w.to_string())
+        .collect();
+    let affected_devices =
+        if dropped.is_empty() { Vec::new() } else { devices_using(data_path, &mappings, &id, Some(&dropped)) };
+    let removed_mappings = remove_mappings(&mut mappings, &id, &manifest.provides.widgets);
+
+    object_mut(&mut packages, "packages").insert(id.clone(), registry_entry(&manifest, raw_manifest, installed_at));
+
+    let transaction = Transaction::begin(data_path)?;
+    let (widgets, icons) = match stage(&transaction, extracted, &manifest) {
+        Ok(staged) => staged,
+        Err(e) => {
+            transaction.discard();
+            return Err(e);
+        }
+    };
+    transaction.commit(|t| {
+        t.replace_dir(&data_path.join("widgets").join("packages").join(&id), Some(&widgets), "widgets")?;
+        t.replace_dir(&data_path.join("icons").join("packages").join(&id), Some(&icons), "icons")?;
+        t.write_json(&packages_path, &packages)?;
+        if !removed_mappings.is_empty() {
+            t.write_json(&mappings_path, &mappings)?;
+        }
+        Ok(())
+    })?;
+
+    println!(
+        "{} package {} v{}{}",
+        if upgrade { "Upgraded" } else { "Installed" },
+        id,
+        manifest.version,
+        previous_version.as_deref().map(|v| format!(" (was v{})", v)).unwrap_or_default()
+    );
+    Ok(PackageChange {
+        package_id: id,
+        committed: true,
+        version: Some(manifest.version),
+        previous_version,
+        removed_mappings,
+        affected_devices,
+    })
+}
+
+// Remove an installed package. Devices that still use it are reported in
+// affected_devices; unless `force` is set the package is then left installed.
+pub fn uninstall(data_path: &Path, package_id: &str, force: bool) -> Result<PackageChange, String> {
+    let packages_path = data_path.join(PACKAGES_FILE);
+    let mappings_path = data_path.join(MAPPINGS_FILE);
+    let mut packages = read_json(&packages_path, json!({ "version": "1.0", "packages": {} }))?;
+    let mut mappings = read_json(&mappings_path, json!({ "version": "1.0", "mappings": {}, "defaults": {} }))?;
+
+    let Some(previous) = object_mut(&mut packages, "packages").remove(package_id) else {
+        return Err(format!("Package not found: {}", package_id));
+    };
+    let previous_version = previous.get("version").and_then(|v| v.as_str()).map(|v| v.to_string());
+    let affected_devices = devices_using(data_path, &mappings, package_id, None);
+    if !affected_devices.is_empty() && !force {
+        println!("Not uninstalling {}: still used by {} device(s)", package_id, affected_devices.len());
+        return Ok(PackageChange {
+            package_id: package_id.to_string(),
+            committed: false,
+            version: previous_version.clone(),
+            previous_version,
+            removed_mappings: Vec::new(),
+            affected_devices,
+        });
+    }
+    let removed_mappings = remove_mappings(&mut mappings, package_id, &[]);
+
+    Transaction::begin(data_path)?.commit(|t| {
+        t.replace_dir(&data_path.join("widgets").join("packages").join(package_id), None, "widgets")?;
+        t.replace_dir(&data_path.join("icons").join("packages").join(package_id), None, "icons")?;
+        t.write_json(&packages_path, &packages)?;
+        if !removed_mappings.is_empty() {
+            t.write_json(&mappings_path, &mappings)?;
+        }
+        Ok(())
+    })?;
+
+    println!("Uninstalled package {}", package_id);
+    Ok(PackageChange {
+        package_id: package_id.to_string(),
+        committed: true,
+        version: None,
+        previous_version,
+        removed_mappings,
+        affected_devices,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("package-install-test-{}-{}-{}", name, std::process::id(), chrono::Local::now().format("%H%M%S%f")));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // An extracted package with one widget and one icon, both containing `version`
+    fn extracted(root: &Path, version: &str) -> PathBuf {
+        let dir = root.join(format!("extracted-{}", version));
+        fs::create_dir_all(dir.join("widgets")).unwrap();
+        fs::create_dir_all(dir.join("icons").join("lamp")).unwrap();
+        let manifest = json!({
+            "id": "com.example.lamp", "name": "Lamp", "version": version, "author": "Example", "description": "Lamp",
+            "requires": { "homeMapVersion": ">=0.1.7" },
+            "provides": { "widgets": ["lamp"], "iconSets": ["lamp"] }
+        });
+        fs::write(dir.join("manifest.json"), manifest.to_string()).unwrap();
+        fs::write(dir.join("widgets").join("lamp.json"), version).unwrap();
+        fs::write(dir.join("icons").join("lamp").join("on.svg"), version).unwrap();
+        dir
+    }
+
+    fn installed_files(data: &Path) -> (String, String) {
+        (
+            fs::read_to_string(data.join("widgets/packages/com.example.lamp/lamp.json")).unwrap(),
+            fs::read_to_string(data.join("icons/packages/com.example.lamp/lamp/on.svg")).unwrap(),
+        )
+    }
+
+    fn transactions(data: &Path, dir: &str) -> usize {
+        fs::read_dir(data.join(dir)).map(|entries| entries.count()).unwrap_or(0)
+    }
+
+    #[test]
+    fn successful_commit_removes_the_transaction_folder() {
+        let data = data_dir("commit");
+        let change = install(&data, &extracted(&data, "1.0.0"), false).unwrap();
+        assert!(change.committed);
+        assert_eq!(installed_files(&data), ("1.0.0".to_string(), "1.0.0".to_string()));
+        assert_eq!(transactions(&data, TEMP_DIR), 0);
+        assert_eq!(transactions(&data, RECOVERY_DIR), 0);
+        fs::remove_dir_all(&data).unwrap();
+    }
+
+    #[test]
+    fn failing_step_restores_the_previous_package() {
+        let data = data_dir("rollback");
+        install(&data, &extracted(&data, "1.0.0"), false).unwrap();
+        let registry = fs::read_to_string(data.join(PACKAGES_FILE)).unwrap();
+
+        // Writing the registry fails after both folders have been replaced
+        fs::create_dir_all(data.join("installed-packages.json.tmp")).unwrap();
+        assert!(install(&data, &extracted(&data, "2.0.0"), true).is_err());
+
+        assert_eq!(installed_files(&data), ("1.0.0".to_string(), "1.0.0".to_string()));
+        assert_eq!(fs::read_to_string(data.join(PACKAGES_FILE)).unwrap(), registry);
+        assert_eq!(transactions(&data, TEMP_DIR), 0);
+        assert_eq!(transactions(&data, RECOVERY_DIR), 0);
+        fs::remove_dir_all(&data).unwrap();
+    }
+
+    #[test]
+    fn incomplete_rollback_keeps_the_backups_outside_temp() {
+        let data = data_dir("recovery");
+        let target = data.join("widgets/packages/com.example.lamp");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("lamp.json"), "1.0.0").unwrap();
+
+        let transaction = Transaction::begin(&data).unwrap();
+        let staged = transaction.staging("widgets");
+        fs::create_dir_all(&staged).unwrap();
+        let result = transaction.commit(|t| {
+            t.replace_dir(&target, Some(&staged), "widgets")?;
+            // The new folder disappears, so moving it back to staging fails
+            fs::remove_dir_all(&target).unwrap();
+            Err("step failed".to_string())
+        });
+
+        assert_eq!(result.unwrap_err(), "step failed");
+        assert_eq!(transactions(&data, TEMP_DIR), 0);
+        assert_eq!(transactions(&data, RECOVERY_DIR), 1);
+        fs::remove_dir_all(&data).unwrap();
+    }
+}
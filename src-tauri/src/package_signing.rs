@@ -0,0 +1,230 @@
+// Widget package signatures
+// A signed .hwp carries signature.json at its root:
+//   { "publisher": "Jan Gabrielsson", "publicKey": "<base64>", "signature": "<base64>" }
+// The signature is a detached ed25519 signature over the package digest: one
+// line "<sha256 hex>  <path>\n" per file in the archive, sorted by path and
+// leaving out signature.json itself (scripts/create-package.sh builds the same
+// text and signs it with openssl). Publishers whose packages are trusted are
+// kept in trusted-publishers.json in the data directory.
+// Verifying a package gives one of:
+//   valid          signed by a trusted publisher and unchanged since
+//   unknownSigner  unchanged since it was signed, but the key isn't trusted
+//   tampered       the signature doesn't match the contents, or is malformed
+//   unsigned       there is no signature.json
+
+use base64::engine::general_purpose::STANDARD as BASE64_STD;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::package_extract::PackageLimits;
+
+const SIGNATURE_FILE: &str = "signature.json";
+const KEYRING_FILE: &str = "trusted-publishers.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageSignatureFile {
+    publisher: String,
+    public_key: String,
+    signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureStatus {
+    Valid,
+    UnknownSigner,
+    Tampered,
+    Unsigned,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureCheck {
+    pub status: SignatureStatus,
+    // Publisher named in the signature (or in the keyring, when trusted)
+    pub publisher: Option<String>,
+    // Short fingerprint of the signing key, for showing to the user
+    pub key_id: Option<String>,
+    // Base64 signing key, for adding an unknown signer to the keyring
+    pub public_key: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedPublisher {
+    pub name: String,
+    // Base64 of the 32-byte ed25519 public key
+    pub public_key: String,
+    pub key_id: String,
+    pub added_at: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Keyring {
+    pub publishers: Vec<TrustedPublisher>,
+}
+
+fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let bytes = BASE64_STD.decode(text.trim()).map_err(|e| format!("Invalid public key: {}", e))?;
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| "Public key must be 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid public key: {}", e))
+}
+
+// First 8 bytes of the key's SHA-256, as hex
+fn key_id(key: &VerifyingKey) -> String {
+    Sha256::digest(key.as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+// The signed text: "<sha256 hex>  <path>\n" for every file but signature.json.
+// Files are read within the same limits as extraction, since skipped files are
+// hashed without having been written.
+fn package_digest<R: Read + Seek>(archive: &mut ZipArchive<R>, limits: &PackageLimits) -> Result<String, String> {
+    let mut lines = Vec::new();
+    let mut total = 0u64;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| format!("Failed to read file from archive: {}", e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let Some(path) = file.enclosed_name() else {
+            continue;
+        };
+        let path = path.to_string_lossy().replace('\\', "/");
+        if path == SIGNATURE_FILE {
+            continue;
+        }
+        let mut hasher = Sha256::new();
+        let read = std::io::copy(&mut (&mut file).take(limits.max_file_bytes + 1), &mut hasher)
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        total += read;
+        if read > limits.max_file_bytes || total > limits.max_total_bytes {
+            return Err(format!("Package rejected: {} unpacks to more than its size limit", path));
+        }
+        let hash: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        lines.push((path, hash));
+    }
+    lines.sort();
+    Ok(lines.into_iter().map(|(path, hash)| format!("{}  {}\n", hash, path)).collect())
+}
+
+pub fn load_keyring(data_path: &Path) -> Keyring {
+    fs::read_to_string(data_path.join(KEYRING_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_keyring(data_path: &Path, keyring: &Keyring) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(keyring).map_err(|e| format!("Failed to serialize keyring: {}", e))?;
+    fs::write(data_path.join(KEYRING_FILE), json).map_err(|e| format!("Failed to write {}: {}", KEYRING_FILE, e))
+}
+
+// Add (or rename) a trusted publisher
+pub fn trust_publisher(data_path: &Path, name: &str, public_key: &str) -> Result<TrustedPublisher, String> {
+    let key = parse_public_key(public_key)?;
+    let mut keyring = load_keyring(data_path);
+    keyring.publishers.retain(|p| p.public_key != BASE64_STD.encode(key.as_bytes()));
+    let publisher = TrustedPublisher {
+        name: name.to_string(),
+        public_key: BASE64_STD.encode(key.as_bytes()),
+        key_id: key_id(&key),
+        added_at: chrono::Utc::now().to_rfc3339(),
+    };
+    keyring.publishers.push(publisher.clone());
+    save_keyring(data_path, &keyring)?;
+    println!("Trusted publisher {} ({})", publisher.name, publisher.key_id);
+    Ok(publisher)
+}
+
+// Remove a trusted publisher by key id; returns whether it was in the keyring
+pub fn untrust_publisher(data_path: &Path, key_id: &str) -> Result<bool, String> {
+    let mut keyring = load_keyring(data_path);
+    let before = keyring.publishers.len();
+    keyring.publishers.retain(|p| p.key_id != key_id);
+    if keyring.publishers.len() == before {
+        return Ok(false);
+    }
+    save_keyring(data_path, &keyring)?;
+    println!("Removed trusted publisher {}", key_id);
+    Ok(true)
+}
+
+fn check(status: SignatureStatus, publisher: Option<String>, key: Option<&VerifyingKey>, message: String) -> SignatureCheck {
+    SignatureCheck {
+        status,
+        publisher,
+        key_id: key.map(key_id),
+        public_key: key.map(|key| BASE64_STD.encode(key.as_bytes())),
+        message,
+    }
+}
+
+// Check the package's signature against its contents and the keyring
+pub fn verify_package<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    data_path: &Path,
+    limits: &PackageLimits,
+) -> Result<SignatureCheck, String> {
+    let content = match archive.by_name(SIGNATURE_FILE) {
+        Ok(mut file) => {
+            let mut content = String::new();
+            file.read_to_string(&mut content).map_err(|e| format!("Failed to read {}: {}", SIGNATURE_FILE, e))?;
+            content
+        }
+        Err(zip::result::ZipError::FileNotFound) => {
+            return Ok(check(SignatureStatus::Unsigned, None, None, "Package is not signed".to_string()));
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", SIGNATURE_FILE, e)),
+    };
+    let signed: PackageSignatureFile = match serde_json::from_str(&content) {
+        Ok(signed) => signed,
+        Err(e) => return Ok(check(SignatureStatus::Tampered, None, None, format!("Invalid {}: {}", SIGNATURE_FILE, e))),
+    };
+    let publisher = Some(signed.publisher.clone());
+    let key = match parse_public_key(&signed.public_key) {
+        Ok(key) => key,
+        Err(e) => return Ok(check(SignatureStatus::Tampered, publisher, None, e)),
+    };
+    let signature = match BASE64_STD.decode(signed.signature.trim()).ok().and_then(|bytes| Signature::from_slice(&bytes).ok()) {
+        Some(signature) => signature,
+        None => return Ok(check(SignatureStatus::Tampered, publisher, Some(&key), "Invalid signature encoding".to_string())),
+    };
+
+    let digest = package_digest(archive, limits)?;
+    if key.verify_strict(digest.as_bytes(), &signature).is_err() {
+        return Ok(check(
+            SignatureStatus::Tampered,
+            publisher,
+            Some(&key),
+            "Package contents do not match the signature; it was modified after signing".to_string(),
+        ));
+    }
+
+    let keyring = load_keyring(data_path);
+    match keyring.publishers.iter().find(|p| p.public_key == BASE64_STD.encode(key.as_bytes())) {
+        Some(trusted) => Ok(check(
+            SignatureStatus::Valid,
+            Some(trusted.name.clone()),
+            Some(&key),
+            format!("Signed by trusted publisher {}", trusted.name),
+        )),
+        None => {
+            // Someone else claiming the name of a trusted publisher is worth spelling out
+            let impostor = keyring.publishers.iter().any(|p| p.name == signed.publisher);
+            let message = if impostor {
+                format!("Signed as {}, but not with the key trusted for that publisher", signed.publisher)
+            } else {
+                format!("Signed by {}, who is not a trusted publisher", signed.publisher)
+            };
+            Ok(check(SignatureStatus::UnknownSigner, publisher, Some(&key), message))
+        }
+    }
+}
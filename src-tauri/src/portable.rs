@@ -0,0 +1,280 @@
+// Portable mode
+// A "homemap.portable" marker file next to the executable (or the --portable
+// argument) makes HomeMap keep all of its state in a folder beside it, so an
+// install on a USB stick or network share carries its own settings and data:
+//   HomeMapPortable/settings.json   (and vault.json)
+//   HomeMapPortable/homemapdata/
+//   HomeMapPortable/backups/
+//   HomeMapPortable/logs/          (stdout and stderr, one file per day)
+// Not available on iOS/Android, where the app cannot write next to itself.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub const MARKER_FILE: &str = "homemap.portable";
+pub const PORTABLE_DIR: &str = "HomeMapPortable";
+pub const PORTABLE_ARG: &str = "--portable";
+const DATA_DIR: &str = "homemapdata";
+// Files moved along with settings.json
+const SETTINGS_FILES: [&str; 2] = ["settings.json", "vault.json"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortableStatus {
+    pub enabled: bool,
+    // Portable mode is on because of --portable rather than the marker file
+    pub from_argument: bool,
+    pub marker_path: String,
+    pub root: String,
+    pub settings_dir: String,
+    pub data_dir: String,
+    pub backups_dir: String,
+    pub logs_dir: String,
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe().ok()?.parent().map(|dir| dir.to_path_buf())
+}
+
+fn marker_path() -> Option<PathBuf> {
+    exe_dir().map(|dir| dir.join(MARKER_FILE))
+}
+
+fn portable_root() -> Option<PathBuf> {
+    exe_dir().map(|dir| dir.join(PORTABLE_DIR))
+}
+
+fn from_argument() -> bool {
+    std::env::args().skip(1).any(|arg| arg == PORTABLE_ARG)
+}
+
+// Decided once per run; switching modes takes effect after a restart
+pub fn is_enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| {
+        if cfg!(any(target_os = "ios", target_os = "android")) {
+            return false;
+        }
+        let enabled = from_argument() || marker_path().is_some_and(|marker| marker.exists());
+        if enabled {
+            println!("Portable mode: state is kept in {:?}", portable_root());
+        }
+        enabled
+    })
+}
+
+// Root folder for all state, when running portable
+pub fn root() -> Option<PathBuf> {
+    if is_enabled() {
+        portable_root()
+    } else {
+        None
+    }
+}
+
+pub fn settings_dir() -> Option<PathBuf> {
+    root()
+}
+
+pub fn data_dir() -> Option<PathBuf> {
+    root().map(|root| root.join(DATA_DIR))
+}
+
+pub fn backups_dir() -> Option<PathBuf> {
+    root().map(|root| root.join("backups"))
+}
+
+pub fn logs_dir() -> Option<PathBuf> {
+    root().map(|root| root.join("logs"))
+}
+
+fn path_string(path: Option<PathBuf>) -> String {
+    path.map(|p| p.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+pub fn status() -> PortableStatus {
+    let root = portable_root();
+    PortableStatus {
+        enabled: is_enabled(),
+        from_argument: from_argument(),
+        marker_path: path_string(marker_path()),
+        root: path_string(root.clone()),
+        settings_dir: path_string(root.clone()),
+        data_dir: path_string(root.as_ref().map(|r| r.join(DATA_DIR))),
+        backups_dir: path_string(root.as_ref().map(|r| r.join("backups"))),
+        logs_dir: path_string(root.as_ref().map(|r| r.join("logs"))),
+    }
+}
+
+// Create the portable folder layout (settings, data, backups, logs)
+pub fn ensure_layout() -> Result<(), String> {
+    for dir in [settings_dir(), backups_dir(), logs_dir()].into_iter().flatten() {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create portable directory {:?}: {}", dir, e))?;
+    }
+    Ok(())
+}
+
+// Append stdout and stderr to logs/homemap-<date>.log, so the println! output
+// of a portable install (which usually has no console) is kept beside it
+pub fn redirect_output() -> Result<(), String> {
+    let dir = logs_dir().ok_or("Portable mode is not enabled")?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create portable directory {:?}: {}", dir, e))?;
+    let path = dir.join(format!("homemap-{}.log", chrono::Local::now().format("%Y-%m-%d")));
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to open log file {:?}: {}", path, e))?;
+    println!("Portable mode: output goes to {:?}", path);
+    redirect_std_handles(file).map_err(|e| format!("Failed to redirect output to {:?}: {}", path, e))
+}
+
+#[cfg(unix)]
+fn redirect_std_handles(file: fs::File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    for target in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        if unsafe { libc::dup2(file.as_raw_fd(), target) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn redirect_std_handles(file: fs::File) -> io::Result<()> {
+    use std::os::windows::io::IntoRawHandle;
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetStdHandle(std_handle: u32, handle: *mut std::ffi::c_void) -> i32;
+    }
+    // Rust's stdout and stderr look the handles up on every write. Both share
+    // the file, which stays open for the rest of the run.
+    let handle = file.into_raw_handle();
+    for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+        if unsafe { SetStdHandle(std_handle, handle) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Point homemap_path entries (top level and profiles) that used the old data
+// directory at the new one. Relative entries are relative to the settings folder.
+fn rewrite_homemap_paths(settings: &mut Value, settings_dir: &Path, from: &Path, to: &str) {
+    let matches = |value: &Value| {
+        value.as_str().is_some_and(|path| !path.is_empty() && settings_dir.join(path) == from)
+    };
+
+    if matches(&settings["homemap_path"]) {
+        settings["homemap_path"] = Value::String(to.to_string());
+    }
+    if let Some(profiles) = settings.get_mut("profiles").and_then(|p| p.as_array_mut()) {
+        for profile in profiles {
+            if matches(&profile["homemap_path"]) {
+                profile["homemap_path"] = Value::String(to.to_string());
+            }
+        }
+    }
+}
+
+// Rename an existing file or directory out of the way instead of overwriting it
+fn move_aside(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let aside = path.with_file_name(format!("{}.replaced-{}", name, timestamp));
+    fs::rename(path, &aside).map_err(|e| format!("Failed to move {:?} aside: {}", path, e))?;
+    println!("Moved existing {:?} to {:?}", path, aside);
+    Ok(())
+}
+
+// Copy settings files and the data directory from one install layout to another.
+// Existing files at the destination are renamed, not overwritten.
+fn copy_state(
+    from_settings: &Path,
+    to_settings: &Path,
+    from_data: &Path,
+    to_data: &Path,
+    data_path_in_settings: &str,
+) -> Result<(), String> {
+    fs::create_dir_all(to_settings)
+        .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    for name in SETTINGS_FILES {
+        let src = from_settings.join(name);
+        if !src.exists() {
+            continue;
+        }
+        let dst = to_settings.join(name);
+        move_aside(&dst)?;
+        if name == "settings.json" {
+            let content = fs::read_to_string(&src)
+                .map_err(|e| format!("Failed to read settings file: {}", e))?;
+            let mut settings: Value = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse settings: {}", e))?;
+            rewrite_homemap_paths(&mut settings, from_settings, from_data, data_path_in_settings);
+            let json = serde_json::to_string_pretty(&settings)
+                .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+            fs::write(&dst, json).map_err(|e| format!("Failed to write settings file: {}", e))?;
+        } else {
+            fs::copy(&src, &dst).map_err(|e| format!("Failed to copy {}: {}", name, e))?;
+        }
+        println!("Copied {:?} -> {:?}", src, dst);
+    }
+
+    if from_data.exists() {
+        move_aside(to_data)?;
+        crate::copy_dir_recursive(&from_data.to_path_buf(), &to_data.to_path_buf())
+            .map_err(|e| format!("Failed to copy data directory: {}", e))?;
+        println!("Copied {:?} -> {:?}", from_data, to_data);
+    }
+    Ok(())
+}
+
+// Copy the installed settings and data next to the executable and create the
+// marker file. The original files are left in place.
+pub fn convert_to_portable(installed_settings: &Path, installed_data: &Path) -> Result<PortableStatus, String> {
+    let root = portable_root().ok_or("Could not find the executable directory")?;
+    let marker = marker_path().ok_or("Could not find the executable directory")?;
+
+    // Stored relative to the portable folder so the install can move between machines
+    copy_state(installed_settings, &root, installed_data, &root.join(DATA_DIR), DATA_DIR)?;
+    for dir in ["backups", "logs"] {
+        fs::create_dir_all(root.join(dir))
+            .map_err(|e| format!("Failed to create portable directory: {}", e))?;
+    }
+
+    fs::write(&marker, "HomeMap runs in portable mode while this file exists.\n")
+        .map_err(|e| format!("Failed to create portable marker: {}", e))?;
+    println!("Converted to portable install in {:?}", root);
+
+    let mut status = status();
+    status.enabled = true;
+    Ok(status)
+}
+
+// Copy the portable settings and data back to the standard locations and
+// remove the marker file. The portable folder is left in place.
+pub fn convert_to_installed(installed_settings: &Path, installed_data: &Path) -> Result<PortableStatus, String> {
+    let root = portable_root().ok_or("Could not find the executable directory")?;
+    let marker = marker_path().ok_or("Could not find the executable directory")?;
+
+    let installed_data_str = installed_data.to_string_lossy().to_string();
+    copy_state(&root, installed_settings, &root.join(DATA_DIR), installed_data, &installed_data_str)?;
+
+    if marker.exists() {
+        fs::remove_file(&marker).map_err(|e| format!("Failed to remove portable marker: {}", e))?;
+    }
+    println!("Converted portable install back to {:?}", installed_data);
+
+    let mut status = status();
+    status.enabled = false;
+    Ok(status)
+}
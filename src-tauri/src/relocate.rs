@@ -0,0 +1,302 @@
+// Moving the data directory
+// The whole tree (hidden files included) is copied to the destination and every
+// file is verified against its source by SHA-256. Absolute paths inside
+// config.json and the package registries that pointed into the old directory
+// are rewritten to the new one. The caller then repoints the settings, and only
+// after that is the old tree removed. Any failure before that point removes
+// the partial copy and leaves the old directory untouched.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// JSON files that may hold absolute paths into the data directory
+const PATH_FILES: [&str; 3] = ["config.json", "installed-packages.json", "widget-mappings.json"];
+// Emit a progress event every this many files
+const PROGRESS_EVERY: usize = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+    Scanning,
+    Copying,
+    Verifying,
+    Rewriting,
+    Removing,
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelocateProgress {
+    pub phase: Phase,
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    // Data-relative path of the file being worked on
+    pub current: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelocateResult {
+    pub from: String,
+    pub to: String,
+    pub files: usize,
+    pub bytes: u64,
+    // Files in which absolute paths were rewritten
+    pub rewritten: Vec<String>,
+    pub old_removed: bool,
+    // Why the old directory could not be removed (the move itself succeeded)
+    pub old_remove_error: Option<String>,
+}
+
+struct Entry {
+    rel: PathBuf,
+    size: u64,
+}
+
+struct Progress<'a, F: Fn(&RelocateProgress)> {
+    emit: &'a F,
+    state: RelocateProgress,
+}
+
+impl<F: Fn(&RelocateProgress)> Progress<'_, F> {
+    fn phase(&mut self, phase: Phase) {
+        self.state.phase = phase;
+        self.state.files_done = 0;
+        self.state.bytes_done = 0;
+        self.state.current = None;
+        (self.emit)(&self.state);
+    }
+
+    fn file(&mut self, entry: &Entry) {
+        self.state.files_done += 1;
+        self.state.bytes_done += entry.size;
+        if self.state.files_done.is_multiple_of(PROGRESS_EVERY) || self.state.files_done == self.state.files_total {
+            self.state.current = Some(entry.rel.to_string_lossy().replace('\\', "/"));
+            (self.emit)(&self.state);
+        }
+    }
+}
+
+// Check the destination before anything is written
+pub fn validate(from: &Path, to: &Path) -> Result<(), String> {
+    if !from.is_dir() {
+        return Err(format!("Data directory does not exist: {:?}", from));
+    }
+    if !to.is_absolute() {
+        return Err("The new location must be an absolute path".to_string());
+    }
+    let from = from.canonicalize().map_err(|e| format!("Failed to resolve {:?}: {}", from, e))?;
+    // The destination may not exist yet; resolve its nearest existing ancestor
+    let existing = to.ancestors().find(|p| p.exists()).unwrap_or(to);
+    let to_resolved = existing
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {:?}: {}", existing, e))?
+        .join(to.strip_prefix(existing).unwrap_or(Path::new("")));
+    if to_resolved == from {
+        return Err("The new location is the current data directory".to_string());
+    }
+    if to_resolved.starts_with(&from) {
+        return Err("The new location cannot be inside the current data directory".to_string());
+    }
+    if from.starts_with(&to_resolved) {
+        return Err("The new location cannot contain the current data directory".to_string());
+    }
+    if to.exists() {
+        let empty = fs::read_dir(to)
+            .map_err(|e| format!("Failed to read {:?}: {}", to, e))?
+            .next()
+            .is_none();
+        if !empty {
+            return Err(format!("{:?} already exists and is not empty", to));
+        }
+    }
+    Ok(())
+}
+
+fn scan(from: &Path) -> Result<(Vec<PathBuf>, Vec<Entry>), String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(from).follow_links(false) {
+        let entry = entry.map_err(|e| format!("Failed to read data directory: {}", e))?;
+        let rel = entry.path().strip_prefix(from).unwrap_or(entry.path()).to_path_buf();
+        if rel.as_os_str().is_empty() {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            dirs.push(rel);
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            files.push(Entry { rel, size });
+        }
+    }
+    Ok((dirs, files))
+}
+
+fn file_hash(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+// Replace `from` with `to` at the start of every string that is a path inside `from`
+fn rewrite_paths(value: &mut Value, from: &str, to: &str) -> bool {
+    match value {
+        Value::String(s) => {
+            let Some(rest) = s.strip_prefix(from) else {
+                return false;
+            };
+            if !(rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\')) {
+                return false;
+            }
+            *s = format!("{}{}", to, rest);
+            true
+        }
+        Value::Array(items) => items.iter_mut().fold(false, |changed, item| rewrite_paths(item, from, to) | changed),
+        Value::Object(map) => map.values_mut().fold(false, |changed, item| rewrite_paths(item, from, to) | changed),
+        _ => false,
+    }
+}
+
+fn rewrite_file(path: &Path, from: &str, to: &str) -> Result<bool, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let Ok(mut json) = serde_json::from_str::<Value>(&content) else {
+        println!("Not rewriting paths in {:?}: not valid JSON", path);
+        return Ok(false);
+    };
+    if !rewrite_paths(&mut json, from, to) {
+        return Ok(false);
+    }
+    let content = serde_json::to_string_pretty(&json)
+        .map_err(|e| format!("Failed to serialize {:?}: {}", path, e))?;
+    fs::write(path, content).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    Ok(true)
+}
+
+fn copy_and_verify<F: Fn(&RelocateProgress)>(
+    from: &Path,
+    to: &Path,
+    progress: &mut Progress<F>,
+) -> Result<RelocateResult, String> {
+    progress.phase(Phase::Scanning);
+    let (dirs, files) = scan(from)?;
+    progress.state.files_total = files.len();
+    progress.state.bytes_total = files.iter().map(|f| f.size).sum();
+
+    progress.phase(Phase::Copying);
+    fs::create_dir_all(to).map_err(|e| format!("Failed to create {:?}: {}", to, e))?;
+    for dir in &dirs {
+        fs::create_dir_all(to.join(dir)).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+    }
+    for file in &files {
+        fs::copy(from.join(&file.rel), to.join(&file.rel))
+            .map_err(|e| format!("Failed to copy {:?}: {}", file.rel, e))?;
+        progress.file(file);
+    }
+
+    progress.phase(Phase::Verifying);
+    for file in &files {
+        if file_hash(&from.join(&file.rel))? != file_hash(&to.join(&file.rel))? {
+            return Err(format!("Copy of {:?} does not match the original", file.rel));
+        }
+        progress.file(file);
+    }
+
+    progress.phase(Phase::Rewriting);
+    let from_str = from.to_string_lossy().to_string();
+    let to_str = to.to_string_lossy().to_string();
+    let mut rewritten = Vec::new();
+    for name in PATH_FILES {
+        let path = to.join(name);
+        if path.exists() && rewrite_file(&path, &from_str, &to_str)? {
+            println!("Rewrote data paths in {}", name);
+            rewritten.push(name.to_string());
+        }
+    }
+
+    Ok(RelocateResult {
+        from: from_str,
+        to: to_str,
+        files: files.len(),
+        bytes: progress.state.bytes_total,
+        rewritten,
+        old_removed: false,
+        old_remove_error: None,
+    })
+}
+
+// Copy, verify and rewrite, then call `repoint` (which updates the settings)
+// and finally remove the old directory.
+pub fn relocate<F, R>(from: &Path, to: &Path, emit: &F, repoint: R) -> Result<RelocateResult, String>
+where
+    F: Fn(&RelocateProgress),
+    R: FnOnce(&Path) -> Result<(), String>,
+{
+    validate(from, to)?;
+    let created = !to.exists();
+    let mut progress = Progress {
+        emit,
+        state: RelocateProgress {
+            phase: Phase::Scanning,
+            files_done: 0,
+            files_total: 0,
+            bytes_done: 0,
+            bytes_total: 0,
+            current: None,
+        },
+    };
+
+    let copied = copy_and_verify(from, to, &mut progress).and_then(|result| {
+        repoint(to)?;
+        Ok(result)
+    });
+    let mut result = match copied {
+        Ok(result) => result,
+        Err(e) => {
+            println!("Relocation to {:?} failed, removing the partial copy: {}", to, e);
+            let cleanup = if created { fs::remove_dir_all(to) } else { clear_dir(to) };
+            if let Err(cleanup_error) = cleanup {
+                println!("Failed to remove partial copy {:?}: {}", to, cleanup_error);
+            }
+            return Err(e);
+        }
+    };
+
+    progress.phase(Phase::Removing);
+    match fs::remove_dir_all(from) {
+        Ok(()) => result.old_removed = true,
+        Err(e) => {
+            println!("Data moved, but the old directory {:?} could not be removed: {}", from, e);
+            result.old_remove_error = Some(e.to_string());
+        }
+    }
+
+    progress.phase(Phase::Done);
+    println!("Moved data directory {:?} -> {:?} ({} files)", from, to, result.files);
+    Ok(result)
+}
+
+// Empty a directory that existed (empty) before the relocation started
+fn clear_dir(dir: &Path) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
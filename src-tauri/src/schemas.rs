@@ -0,0 +1,513 @@
+// JSON Schemas for the files and messages HomeMap reads
+// The typed models below describe config.json, widget definitions and the
+// WebSocket protocol (docs/WIDGET_FORMAT.md, docs/WEBSOCKET_PROTOCOL.md). They
+// are only used to generate schemas and validate documents; the rest of the
+// backend keeps working on serde_json::Value. Doc comments on the model types
+// become the schema descriptions shown by editors.
+
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::package_extract::PackageLimits;
+use crate::PackageManifest;
+
+// ============================================================================
+// config.json
+// ============================================================================
+
+/// HomeMap configuration (config.json in the HomeMap data directory)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct HomeMapConfig {
+    /// House name, shown in the window title
+    pub name: Option<String>,
+    /// House icon (emoji), shown in the window title
+    pub icon: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub floors: Vec<FloorConfig>,
+    #[serde(default)]
+    pub devices: Vec<DeviceConfig>,
+    #[serde(rename = "widgetBackground")]
+    pub widget_background: Option<WidgetBackground>,
+    pub websocket: Option<WebSocketSettings>,
+    /// Remote (QuickApp) widget instances placed on floors
+    #[serde(rename = "remoteWidgets")]
+    pub remote_widgets: Option<Vec<Value>>,
+    #[serde(rename = "packageLimits")]
+    pub package_limits: Option<PackageLimits>,
+}
+
+/// A floor plan
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FloorConfig {
+    pub id: String,
+    pub name: String,
+    /// Floor image, relative to the data directory (e.g. "images/floor1.png")
+    pub image: String,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+}
+
+/// HC3 device ids are numbers; remote widgets use strings
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum DeviceId {
+    Number(i64),
+    Text(String),
+}
+
+/// Position in floor image pixels
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Placement of a device on one floor (multi-floor format)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FloorPlacement {
+    pub floor_id: String,
+    pub position: Position,
+}
+
+/// A device on the map. Use either floor_id + position or floors.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceConfig {
+    pub id: DeviceId,
+    pub name: String,
+    /// Widget type (built-in widget name or mapped device type)
+    #[serde(rename = "type")]
+    pub widget_type: String,
+    /// Explicit widget reference "packageId/widgetId"
+    pub widget: Option<String>,
+    pub floor_id: Option<String>,
+    pub position: Option<Position>,
+    pub floors: Option<Vec<FloorPlacement>>,
+    pub params: Option<DeviceParams>,
+    /// Last known state values
+    pub state: Option<Map<String, Value>>,
+}
+
+/// Per-device overrides
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DeviceParams {
+    /// Icon set used instead of the widget's iconSet
+    #[serde(rename = "iconSet")]
+    pub icon_set: Option<String>,
+    /// Package providing iconSet
+    #[serde(rename = "iconPackage")]
+    pub icon_package: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetBackground {
+    pub enabled: bool,
+    /// CSS colour, e.g. "#FFFF00"
+    pub color: String,
+    /// Opacity in percent (0-100)
+    pub opacity: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WebSocketSettings {
+    pub enabled: bool,
+    pub port: u16,
+    #[serde(rename = "bindAddress")]
+    pub bind_address: String,
+    #[serde(rename = "autoStart")]
+    pub auto_start: Option<bool>,
+}
+
+// ============================================================================
+// Widget definitions
+// ============================================================================
+
+/// Widget definition (format v0.1.5, see docs/WIDGET_FORMAT.md)
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetDefinition {
+    /// Widget format version, e.g. "0.1.5"
+    #[serde(rename = "widgetVersion")]
+    pub widget_version: Option<String>,
+    /// Icon set folder name (icons/built-in/<iconSet> or icons/packages/<package>/<iconSet>)
+    #[serde(rename = "iconSet")]
+    pub icon_set: Option<String>,
+    /// Package whose icon folder holds iconSet (defaults to the widget's own package)
+    #[serde(rename = "iconPackage")]
+    pub icon_package: Option<String>,
+    /// Widget this one builds on ("lightdim" or "packageId/widgetId")
+    pub extends: Option<String>,
+    /// Partial definitions mixed in, in order (widgets/fragments/<name>.json)
+    pub fragments: Option<Vec<String>>,
+    /// Default state properties
+    pub state: Option<Map<String, Value>>,
+    /// How to fetch each state property from the HC3 API
+    pub getters: Option<BTreeMap<String, WidgetGetter>>,
+    /// Derived state properties: name -> expression, e.g. "round(watts / 1000, 2)"
+    pub computed: Option<BTreeMap<String, String>>,
+    /// HC3 event type -> state updates
+    pub events: Option<BTreeMap<String, WidgetEvent>>,
+    pub render: Option<WidgetRender>,
+    /// Named actions referenced from ui elements
+    pub actions: Option<BTreeMap<String, WidgetAction>>,
+    pub ui: Option<WidgetUi>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetGetter {
+    /// HC3 API path, ${id} is replaced with the device id
+    pub api: String,
+    /// Dotted path to the value in the response, e.g. "properties.value"
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetEvent {
+    /// JSONPath filter selecting matching events; without it the event applies
+    /// whenever data.id is the device
+    #[serde(rename = "match", default, skip_serializing_if = "Option::is_none")]
+    pub match_path: Option<String>,
+    /// State property -> expression ("event.newValue" or "prop == event.property ? event.newValue")
+    pub updates: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetRender {
+    pub icon: Option<RenderIcon>,
+    pub subtext: Option<RenderSubtext>,
+    /// CSS property -> template applied to the icon element
+    pub style: Option<BTreeMap<String, String>>,
+    pub svg: Option<RenderSvg>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderIconType {
+    Static,
+    Conditional,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RenderIcon {
+    #[serde(rename = "type")]
+    pub icon_type: RenderIconType,
+    /// Icon name for static icons
+    pub icon: Option<String>,
+    /// State property the conditions are evaluated against
+    pub property: Option<String>,
+    /// First matching condition wins
+    pub conditions: Option<Vec<IconCondition>>,
+    /// Colour template the SVG icon is tinted with, e.g. "rgb(${colorComponents.red}, 0, 0)"
+    pub tint: Option<String>,
+    /// Dot drawn in a corner of the SVG icon
+    pub badge: Option<IconBadge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct IconCondition {
+    /// Condition expression, e.g. "value > 0"
+    pub when: String,
+    /// Icon name (file name without extension) in the icon set
+    pub icon: String,
+    /// Tint for this icon, replacing the icon-level tint
+    pub tint: Option<String>,
+    /// Badge for this icon, replacing the icon-level badge
+    pub badge: Option<IconBadge>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct IconBadge {
+    /// Colour template
+    pub color: String,
+    /// Text template, a few characters at most
+    pub text: Option<String>,
+    /// "top-right" (default), "top-left", "bottom-right" or "bottom-left"
+    pub position: Option<String>,
+    /// Visibility expression
+    pub visible: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RenderSubtext {
+    /// Text template with ${...} expressions
+    pub template: String,
+    /// Visibility expression
+    pub visible: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RenderSvg {
+    /// CSS selector inside the SVG icon (default "g")
+    pub selector: Option<String>,
+    pub style: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetAction {
+    /// HTTP method (GET, POST, PUT, ...)
+    pub method: String,
+    /// HC3 API path, ${id} is replaced with the device id
+    pub api: String,
+    /// Request body; string values may contain ${...} templates
+    pub body: Option<Value>,
+}
+
+/// Either a composable layout (rows) or a legacy slider/buttons UI
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WidgetUi {
+    pub rows: Option<Vec<UiRow>>,
+    /// Legacy UI type: "slider" or "buttons"
+    #[serde(rename = "type")]
+    pub ui_type: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub property: Option<String>,
+    pub action: Option<String>,
+    pub buttons: Option<Vec<UiButton>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct UiRow {
+    pub elements: Vec<UiElement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub enum UiElementType {
+    #[serde(rename = "button")]
+    Button,
+    #[serde(rename = "label")]
+    Label,
+    #[serde(rename = "slider")]
+    Slider,
+    #[serde(rename = "colorSelect")]
+    ColorSelect,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct UiElement {
+    #[serde(rename = "type")]
+    pub element_type: UiElementType,
+    pub label: Option<String>,
+    /// Text of a label element
+    pub text: Option<String>,
+    /// Action name from the widget's actions
+    pub action: Option<String>,
+    /// State property bound to a slider or colorSelect
+    pub property: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct UiButton {
+    pub label: String,
+    pub action: String,
+}
+
+// ============================================================================
+// WebSocket messages (docs/WEBSOCKET_PROTOCOL.md)
+// ============================================================================
+
+/// Messages sent by QuickApps to HomeMap
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientMessage {
+    RegisterWidgets {
+        /// Stable QuickApp identifier
+        #[serde(rename = "qaId")]
+        qa_id: String,
+        #[serde(rename = "qaName")]
+        qa_name: String,
+        widgets: Vec<RemoteWidgetDefinition>,
+    },
+    WidgetUpdate {
+        #[serde(rename = "widgetId")]
+        widget_id: String,
+        changes: RemoteWidgetChanges,
+    },
+    UnregisterWidgets {
+        #[serde(rename = "qaId")]
+        qa_id: String,
+    },
+    Heartbeat,
+}
+
+/// Messages sent by HomeMap to QuickApps
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ServerMessage {
+    WidgetEvent {
+        #[serde(rename = "widgetId")]
+        widget_id: String,
+        /// "click" or "ui-action"
+        event: String,
+        data: Box<RemoteWidgetEventData>,
+    },
+    RequestWidgets {
+        message: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteWidgetDefinition {
+    /// Widget id, stable across reconnections
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "iconSet")]
+    pub icon_set: Option<String>,
+    pub label: Option<String>,
+    pub ui: Option<RemoteUi>,
+    pub metadata: Option<Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteUi {
+    /// Currently always "dialog"
+    #[serde(rename = "type")]
+    pub ui_type: Option<String>,
+    pub title: Option<String>,
+    pub elements: Vec<RemoteUiElement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoteUiElementType {
+    Button,
+    Switch,
+    Slider,
+    Label,
+    Input,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteUiElement {
+    pub id: String,
+    /// Required when defining the UI, omitted in widget-update changes
+    #[serde(rename = "type")]
+    pub element_type: Option<RemoteUiElementType>,
+    pub label: Option<String>,
+    pub value: Option<Value>,
+    pub icon: Option<String>,
+    /// Button style: "primary", "secondary" or "danger"
+    pub style: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub step: Option<f64>,
+    pub unit: Option<String>,
+    pub color: Option<String>,
+    pub placeholder: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteWidgetChanges {
+    #[serde(rename = "iconSet")]
+    pub icon_set: Option<String>,
+    pub label: Option<String>,
+    pub color: Option<String>,
+    #[serde(rename = "backgroundColor")]
+    pub background_color: Option<String>,
+    /// Custom state (stored, not displayed)
+    pub state: Option<Value>,
+    pub ui: Option<RemoteUiUpdate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteUiUpdate {
+    pub elements: Vec<RemoteUiElement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RemoteWidgetEventData {
+    pub floor: Option<String>,
+    pub x: Option<f64>,
+    pub y: Option<f64>,
+    /// Milliseconds since epoch
+    pub timestamp: Option<i64>,
+    #[serde(rename = "elementId")]
+    pub element_id: Option<String>,
+    /// "click", "toggle" or "change" for ui-action events
+    pub action: Option<String>,
+    pub value: Option<Value>,
+    /// User-defined parameters of the widget instance
+    pub parameters: Option<Map<String, Value>>,
+}
+
+// ============================================================================
+// Generation and validation
+// ============================================================================
+
+pub const SCHEMA_KINDS: [&str; 5] = [
+    "config",
+    "widget",
+    "package-manifest",
+    "websocket-client-message",
+    "websocket-server-message",
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SchemaError {
+    // JSON pointer to the offending value ("" for the document root)
+    pub path: String,
+    pub message: String,
+}
+
+pub fn schema_for_kind(kind: &str) -> Result<RootSchema, String> {
+    let mut schema = match kind {
+        "config" => schema_for!(HomeMapConfig),
+        "widget" => schema_for!(WidgetDefinition),
+        "package-manifest" => schema_for!(PackageManifest),
+        "websocket-client-message" => schema_for!(ClientMessage),
+        "websocket-server-message" => schema_for!(ServerMessage),
+        _ => return Err(format!("Unknown schema kind '{}'. Expected one of: {}", kind, SCHEMA_KINDS.join(", "))),
+    };
+    schema.schema.metadata().id = Some(format!("https://homemap.local/schemas/{}.schema.json", kind));
+    Ok(schema)
+}
+
+pub fn schema_value(kind: &str) -> Result<Value, String> {
+    serde_json::to_value(schema_for_kind(kind)?)
+        .map_err(|e| format!("Failed to serialize schema: {}", e))
+}
+
+// Write every schema to <data>/schemas/<kind>.schema.json
+pub fn write_schemas(data_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let schema_dir = data_dir.join("schemas");
+    fs::create_dir_all(&schema_dir)
+        .map_err(|e| format!("Failed to create schemas directory: {}", e))?;
+
+    let mut written = Vec::new();
+    for kind in SCHEMA_KINDS {
+        let content = serde_json::to_string_pretty(&schema_value(kind)?)
+            .map_err(|e| format!("Failed to serialize schema: {}", e))?;
+        let path = schema_dir.join(format!("{}.schema.json", kind));
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write schema {}: {}", path.display(), e))?;
+        written.push(path);
+    }
+
+    println!("Wrote {} JSON schemas to {:?}", written.len(), schema_dir);
+    Ok(written)
+}
+
+// Validate a document against one of the schemas; an empty list means valid
+pub fn validate(kind: &str, document: &Value) -> Result<Vec<SchemaError>, String> {
+    let schema = schema_value(kind)?;
+    let compiled = jsonschema::JSONSchema::compile(&schema)
+        .map_err(|e| format!("Failed to compile {} schema: {}", kind, e))?;
+
+    let errors = match compiled.validate(document) {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors
+            .map(|error| SchemaError {
+                path: error.instance_path.to_string(),
+                message: error.to_string(),
+            })
+            .collect(),
+    };
+    Ok(errors)
+}
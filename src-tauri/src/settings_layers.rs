@@ -0,0 +1,253 @@
+// Layered resolution of the HC3 connection and data directory settings
+// Every value comes from the highest layer that sets it (empty values don't count):
+//   1. built-in defaults
+//   2. .env files (~/.env, then the one next to the executable, which wins)
+//   3. process environment (HC3_HOST, HC3_USER, HC3_PASSWORD, HC3_PROTOCOL)
+//   4. settings.json (the active profile, or the one named with --profile)
+//   5. command-line arguments (--data-dir, --settings, --hc3-host, --profile)
+// .env files are parsed with dotenvy::from_path_iter, so the process
+// environment is never modified.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+pub const HC3_HOST: &str = "hc3_host";
+pub const HC3_USER: &str = "hc3_user";
+pub const HC3_PASSWORD: &str = "hc3_password";
+pub const HC3_PROTOCOL: &str = "hc3_protocol";
+pub const HOMEMAP_PATH: &str = "homemap_path";
+pub const PROFILE: &str = "profile";
+
+// Reported in this order by get_config_provenance
+const KEYS: [&str; 6] = [HC3_HOST, HC3_USER, HC3_PASSWORD, HC3_PROTOCOL, HOMEMAP_PATH, PROFILE];
+const SECRET_KEYS: [&str; 1] = [HC3_PASSWORD];
+
+pub const SOURCE_DEFAULT: &str = "default";
+pub const SOURCE_ENVIRONMENT: &str = "environment";
+pub const SOURCE_CLI: &str = "command line";
+
+const ENV_VARS: [(&str, &str); 4] = [
+    ("HC3_HOST", HC3_HOST),
+    ("HC3_USER", HC3_USER),
+    ("HC3_PASSWORD", HC3_PASSWORD),
+    ("HC3_PROTOCOL", HC3_PROTOCOL),
+];
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CliArgs {
+    pub data_dir: Option<String>,
+    pub settings: Option<String>,
+    pub hc3_host: Option<String>,
+    pub profile: Option<String>,
+}
+
+// Parse the options we know about; anything else is left for Tauri and the OS
+// (e.g. -psn_* on macOS). Accepts both "--flag value" and "--flag=value".
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> CliArgs {
+    let mut cli = CliArgs::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let slot = match flag.as_str() {
+            "--data-dir" => &mut cli.data_dir,
+            "--settings" => &mut cli.settings,
+            "--hc3-host" => &mut cli.hc3_host,
+            "--profile" => &mut cli.profile,
+            _ => continue,
+        };
+        match inline_value.or_else(|| args.next()) {
+            Some(value) if !value.is_empty() => *slot = Some(value),
+            _ => println!("Ignoring command-line option {} without a value", flag),
+        }
+    }
+
+    cli
+}
+
+// Arguments of the running process, parsed once
+pub fn cli_args() -> &'static CliArgs {
+    static CLI: OnceLock<CliArgs> = OnceLock::new();
+    CLI.get_or_init(|| {
+        let cli = parse_args(std::env::args().skip(1));
+        println!("Command-line settings: {:?}", cli);
+        cli
+    })
+}
+
+// One source of values, e.g. "settings.json (profile 'Home')"
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub source: String,
+    values: Vec<(&'static str, String)>,
+}
+
+impl Layer {
+    pub fn new(source: impl Into<String>) -> Self {
+        Layer {
+            source: source.into(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn set(&mut self, key: &'static str, value: impl Into<String>) {
+        let value = value.into();
+        if value.is_empty() {
+            return;
+        }
+        self.values.retain(|(k, _)| *k != key);
+        self.values.push((key, value));
+    }
+
+    pub fn with(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.values.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+pub fn defaults_layer(homemap_path: &str) -> Layer {
+    Layer::new(SOURCE_DEFAULT)
+        .with(HC3_HOST, "192.168.1.1")
+        .with(HC3_USER, "admin")
+        .with(HC3_PASSWORD, "admin")
+        .with(HC3_PROTOCOL, "http")
+        .with(HOMEMAP_PATH, homemap_path)
+}
+
+// .env files in the order they are applied (later files win)
+fn env_file_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home_dir) = dirs::home_dir() {
+        paths.push(home_dir.join(".env"));
+    }
+    if let Some(exe_dir) = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf())) {
+        paths.push(exe_dir.join(".env"));
+    }
+    paths
+}
+
+pub fn env_file_layers() -> Vec<Layer> {
+    env_file_paths()
+        .into_iter()
+        .filter(|path| path.exists())
+        .filter_map(|path| {
+            let entries = match dotenvy::from_path_iter(&path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    println!("Failed to read {:?}: {}", path, e);
+                    return None;
+                }
+            };
+            let mut layer = Layer::new(format!(".env ({})", path.display()));
+            for (name, value) in entries.flatten() {
+                if let Some((_, key)) = ENV_VARS.iter().find(|(var, _)| *var == name) {
+                    layer.set(key, value);
+                }
+            }
+            Some(layer)
+        })
+        .collect()
+}
+
+pub fn process_env_layer() -> Layer {
+    let mut layer = Layer::new(SOURCE_ENVIRONMENT);
+    for (var, key) in ENV_VARS {
+        if let Ok(value) = std::env::var(var) {
+            layer.set(key, value);
+        }
+    }
+    layer
+}
+
+pub fn cli_layer(cli: &CliArgs) -> Layer {
+    let mut layer = Layer::new(SOURCE_CLI);
+    if let Some(host) = &cli.hc3_host {
+        layer.set(HC3_HOST, host.as_str());
+    }
+    if let Some(data_dir) = &cli.data_dir {
+        layer.set(HOMEMAP_PATH, data_dir.as_str());
+    }
+    if let Some(profile) = &cli.profile {
+        layer.set(PROFILE, profile.as_str());
+    }
+    layer
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedValue {
+    pub key: String,
+    pub value: String,
+    pub source: String,
+    // Lower layers that also set this value
+    pub overrides: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    values: Vec<ResolvedValue>,
+}
+
+impl ResolvedConfig {
+    // Layers are given lowest priority first
+    pub fn resolve(layers: &[Layer]) -> Self {
+        let values = KEYS
+            .iter()
+            .filter_map(|key| {
+                let mut setters = layers.iter().filter_map(|layer| layer.get(key).map(|v| (layer, v)));
+                let (mut winner, mut value) = setters.next()?;
+                let mut overrides = Vec::new();
+                for (layer, v) in setters {
+                    overrides.push(winner.source.clone());
+                    winner = layer;
+                    value = v;
+                }
+                overrides.reverse();
+                Some(ResolvedValue {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                    source: winner.source.clone(),
+                    overrides,
+                })
+            })
+            .collect();
+        ResolvedConfig { values }
+    }
+
+    fn entry(&self, key: &str) -> Option<&ResolvedValue> {
+        self.values.iter().find(|v| v.key == key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|v| v.value.as_str())
+    }
+
+    pub fn value(&self, key: &str) -> String {
+        self.get(key).unwrap_or_default().to_string()
+    }
+
+    pub fn source(&self, key: &str) -> Option<&str> {
+        self.entry(key).map(|v| v.source.as_str())
+    }
+
+    // Values with secrets masked, for showing in the UI
+    pub fn redacted(&self) -> Vec<ResolvedValue> {
+        self.values
+            .iter()
+            .map(|v| {
+                let mut v = v.clone();
+                if SECRET_KEYS.contains(&v.key.as_str()) {
+                    v.value = "********".to_string();
+                }
+                v
+            })
+            .collect()
+    }
+}
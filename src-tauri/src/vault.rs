@@ -0,0 +1,340 @@
+// Credential vault for HC3 passwords
+// Secrets are kept out of settings.json. Where the platform offers a keyring
+// (macOS/iOS Keychain, Windows Credential Manager, Secret Service on Linux) it
+// is used directly; otherwise secrets go into vault.json next to settings.json,
+// encrypted with AES-256-GCM under a key derived from a passphrase (Argon2id).
+// The passphrase comes from HOMEMAP_VAULT_PASSPHRASE or is entered in the app
+// at startup; only the derived key is held in memory.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+#[cfg(not(target_os = "android"))]
+use std::sync::OnceLock;
+
+pub const SERVICE_NAME: &str = "com.gabrielsson.homemap";
+pub const PASSPHRASE_ENV: &str = "HOMEMAP_VAULT_PASSPHRASE";
+const VAULT_FILE: &str = "vault.json";
+const CHECK_PLAINTEXT: &str = "homemap-vault";
+
+// Keys of the encrypted file stores unlocked so far, by vault path, so a
+// different settings folder (portable or installed) needs its own unlock
+static FILE_KEYS: Mutex<BTreeMap<PathBuf, [u8; 32]>> = Mutex::new(BTreeMap::new());
+#[cfg(not(target_os = "android"))]
+static KEYRING_AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+pub trait CredentialStore {
+    fn backend(&self) -> &'static str;
+    fn is_locked(&self) -> bool {
+        false
+    }
+    fn get(&self, account: &str) -> Result<Option<String>, String>;
+    fn set(&self, account: &str, secret: &str) -> Result<(), String>;
+    fn delete(&self, account: &str) -> Result<(), String>;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VaultStatus {
+    pub backend: String,
+    pub locked: bool,
+    // Whether the encrypted file has been created (always true for the keyring)
+    pub initialized: bool,
+}
+
+// Account name for a profile's HC3 password
+pub fn password_account(profile: &str) -> String {
+    format!("hc3-password/{}", profile)
+}
+
+// ============================================================================
+// OS keyring
+// ============================================================================
+
+#[cfg(not(target_os = "android"))]
+pub struct KeyringStore;
+
+#[cfg(not(target_os = "android"))]
+impl KeyringStore {
+    fn entry(account: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(SERVICE_NAME, account)
+            .map_err(|e| format!("Failed to open keyring entry: {}", e))
+    }
+
+    // The keyring counts as available when a lookup either succeeds or reports a missing entry
+    fn probe() -> bool {
+        let result = Self::entry("probe").and_then(|entry| match entry.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        });
+        if let Err(e) = &result {
+            println!("OS keyring not available: {}", e);
+        }
+        result.is_ok()
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+impl CredentialStore for KeyringStore {
+    fn backend(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn get(&self, account: &str) -> Result<Option<String>, String> {
+        match Self::entry(account)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read from keyring: {}", e)),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<(), String> {
+        Self::entry(account)?
+            .set_password(secret)
+            .map_err(|e| format!("Failed to write to keyring: {}", e))
+    }
+
+    fn delete(&self, account: &str) -> Result<(), String> {
+        match Self::entry(account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete from keyring: {}", e)),
+        }
+    }
+}
+
+// ============================================================================
+// Passphrase-encrypted file
+// ============================================================================
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    data: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    kdf: String,
+    salt: String,
+    // Known plaintext used to tell a wrong passphrase from a corrupt entry
+    check: Sealed,
+    #[serde(default)]
+    entries: BTreeMap<String, Sealed>,
+}
+
+pub struct EncryptedFileStore {
+    path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(config_dir: &Path) -> Self {
+        EncryptedFileStore {
+            path: config_dir.join(VAULT_FILE),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read(&self) -> Result<VaultFile, String> {
+        let json = fs::read_to_string(&self.path)
+            .map_err(|e| format!("Failed to read vault: {}", e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse vault: {}", e))
+    }
+
+    fn write(&self, vault: &VaultFile) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create vault directory: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(vault)
+            .map_err(|e| format!("Failed to serialize vault: {}", e))?;
+        fs::write(&self.path, json).map_err(|e| format!("Failed to write vault: {}", e))
+    }
+
+    // Derive the key from the passphrase; creates the vault file on first use
+    pub fn unlock(&self, passphrase: &str) -> Result<(), String> {
+        if passphrase.is_empty() {
+            return Err("Vault passphrase cannot be empty".to_string());
+        }
+
+        if !self.exists() {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let key = derive_key(passphrase, &salt)?;
+            let vault = VaultFile {
+                version: 1,
+                kdf: "argon2id".to_string(),
+                salt: encode(&salt),
+                check: seal(&key, CHECK_PLAINTEXT)?,
+                entries: BTreeMap::new(),
+            };
+            self.write(&vault)?;
+            self.set_key(key);
+            println!("Created credential vault: {:?}", self.path);
+            return Ok(());
+        }
+
+        let vault = self.read()?;
+        let salt = decode(&vault.salt)?;
+        let key = derive_key(passphrase, &salt)?;
+        match open(&key, &vault.check) {
+            Ok(check) if check == CHECK_PLAINTEXT => {
+                self.set_key(key);
+                println!("Credential vault unlocked");
+                Ok(())
+            }
+            _ => Err("Wrong vault passphrase".to_string()),
+        }
+    }
+
+    fn key(&self) -> Result<[u8; 32], String> {
+        file_keys().get(&self.path).copied().ok_or_else(|| "Credential vault is locked".to_string())
+    }
+
+    fn set_key(&self, key: [u8; 32]) {
+        file_keys().insert(self.path.clone(), key);
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn backend(&self) -> &'static str {
+        "encrypted-file"
+    }
+
+    fn is_locked(&self) -> bool {
+        self.key().is_err()
+    }
+
+    fn get(&self, account: &str) -> Result<Option<String>, String> {
+        if !self.exists() {
+            return Ok(None);
+        }
+        let key = self.key()?;
+        let vault = self.read()?;
+        vault.entries.get(account).map(|sealed| open(&key, sealed)).transpose()
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<(), String> {
+        let key = self.key()?;
+        let mut vault = self.read()?;
+        vault.entries.insert(account.to_string(), seal(&key, secret)?);
+        self.write(&vault)
+    }
+
+    fn delete(&self, account: &str) -> Result<(), String> {
+        if !self.exists() {
+            return Ok(());
+        }
+        let mut vault = self.read()?;
+        if vault.entries.remove(account).is_some() {
+            self.write(&vault)?;
+        }
+        Ok(())
+    }
+}
+
+fn file_keys() -> MutexGuard<'static, BTreeMap<PathBuf, [u8; 32]>> {
+    FILE_KEYS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Failed to derive vault key: {}", e))?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], plaintext: &str) -> Result<Sealed, String> {
+    let cipher = Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let data = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| format!("Failed to encrypt secret: {}", e))?;
+    Ok(Sealed {
+        nonce: encode(&nonce),
+        data: encode(&data),
+    })
+}
+
+fn open(key: &[u8; 32], sealed: &Sealed) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key));
+    let nonce: [u8; 12] = decode(&sealed.nonce)?
+        .try_into()
+        .map_err(|_| "Invalid nonce in vault".to_string())?;
+    let plaintext = cipher
+        .decrypt(&Nonce::from(nonce), decode(&sealed.data)?.as_slice())
+        .map_err(|_| "Failed to decrypt secret (wrong passphrase or corrupt vault)".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| format!("Invalid secret encoding: {}", e))
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(text: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| format!("Invalid base64 in vault: {}", e))
+}
+
+// ============================================================================
+// Store selection
+// ============================================================================
+
+#[cfg(not(target_os = "android"))]
+fn keyring_available() -> bool {
+    // HOMEMAP_VAULT_PASSPHRASE forces the file store (e.g. headless machines)
+    if std::env::var(PASSPHRASE_ENV).is_ok() {
+        return false;
+    }
+    *KEYRING_AVAILABLE.get_or_init(KeyringStore::probe)
+}
+
+#[cfg(target_os = "android")]
+fn keyring_available() -> bool {
+    false
+}
+
+// The store to use on this machine. The file store is unlocked from the
+// environment passphrase when one is set.
+pub fn open_store(config_dir: &Path) -> Box<dyn CredentialStore> {
+    #[cfg(not(target_os = "android"))]
+    if keyring_available() {
+        return Box::new(KeyringStore);
+    }
+
+    let store = EncryptedFileStore::new(config_dir);
+    if store.is_locked() {
+        if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+            if let Err(e) = store.unlock(&passphrase) {
+                println!("Failed to unlock vault from {}: {}", PASSPHRASE_ENV, e);
+            }
+        }
+    }
+    Box::new(store)
+}
+
+pub fn status(config_dir: &Path) -> VaultStatus {
+    let store = open_store(config_dir);
+    VaultStatus {
+        backend: store.backend().to_string(),
+        locked: store.is_locked(),
+        initialized: store.backend() != "encrypted-file" || EncryptedFileStore::new(config_dir).exists(),
+    }
+}
+
+pub fn unlock(config_dir: &Path, passphrase: &str) -> Result<(), String> {
+    if keyring_available() {
+        return Ok(());
+    }
+    EncryptedFileStore::new(config_dir).unlock(passphrase)
+}
@@ -0,0 +1,164 @@
+// Widget catalogue
+// Lists every widget the user can pick: built-in widgets (widgets/built-in, then
+// any legacy widgets/*.json not shadowed by a built-in) and the widgets of all
+// packages in installed-packages.json. Each entry carries the metadata the
+// widget pickers show, a preview icon and the linter's error diagnostics.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::floor_export::{self, DeviceWidget, BUILT_IN_PACKAGE};
+use crate::widget_compose;
+use crate::widget_lint::{self, Severity, WidgetDiagnostic};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WidgetEntry {
+    pub id: String,
+    pub package: String,
+    // Value for a device's widget field: the id for built-ins, "package/id" otherwise
+    pub reference: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub widget_version: Option<String>,
+    pub icon_set: Option<String>,
+    // Data-relative path of the icon shown for the widget's default state
+    pub icon_preview: Option<String>,
+    pub package_version: Option<String>,
+    pub device_types: Vec<String>,
+    // Data-relative path of the widget definition
+    pub file: String,
+    pub errors: Vec<WidgetDiagnostic>,
+    pub warnings: usize,
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn relative(data_path: &Path, path: &Path) -> String {
+    path.strip_prefix(data_path).unwrap_or(path).to_string_lossy().replace('\\', "/")
+}
+
+fn text(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+fn json_stems(dir: &Path) -> Vec<String> {
+    let mut stems: Vec<String> = fs::read_dir(dir)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("json"))
+            .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(|s| s.to_string()))
+            .collect())
+        .unwrap_or_default();
+    stems.sort();
+    stems
+}
+
+fn entry(data_path: &Path, id: &str, package: &str, file: PathBuf) -> WidgetEntry {
+    let icon_sets = |icon_set: &str| floor_export::icon_set_dir(data_path, icon_set, Some(package));
+    let compose = |definition: &Value| widget_compose::compose(data_path, package, Some(&file), definition);
+    let report = widget_lint::lint_file(id, &file, Some(&compose), &icon_sets);
+    // Metadata and preview come from the composed definition
+    let definition = read_json(&file)
+        .and_then(|definition| compose(&definition).ok())
+        .map(|composition| composition.definition)
+        .unwrap_or(Value::Null);
+
+    let icon_set = text(&definition, "iconSet").or_else(|| definition
+        .get("render")
+        .and_then(|r| r.get("icon"))
+        .and_then(|i| i.get("set"))
+        .and_then(|s| s.as_str())
+        .map(|s| s.to_string()));
+    let state = floor_export::effective_state(&definition, &Value::Null, None);
+    let widget = DeviceWidget {
+        definition,
+        package: package.to_string(),
+    };
+    let icon_preview = floor_export::resolve_device_icon(data_path, &Value::Null, &widget, &state)
+        .map(|path| relative(data_path, &path));
+    let definition = widget.definition;
+
+    WidgetEntry {
+        id: id.to_string(),
+        package: package.to_string(),
+        reference: if package == BUILT_IN_PACKAGE { id.to_string() } else { format!("{}/{}", package, id) },
+        name: text(&definition, "name"),
+        description: text(&definition, "description"),
+        widget_version: text(&definition, "widgetVersion"),
+        icon_set,
+        icon_preview,
+        package_version: None,
+        // The widget's own type; packages replace this with their manifest's deviceTypes
+        device_types: vec![text(&definition, "type").unwrap_or_else(|| id.to_string())],
+        file: relative(data_path, &file),
+        errors: report.diagnostics.into_iter().filter(|d| d.severity == Severity::Error).collect(),
+        warnings: report.warnings,
+    }
+}
+
+fn built_in_widgets(data_path: &Path) -> Vec<WidgetEntry> {
+    let widgets_dir = data_path.join("widgets");
+    let built_in_dir = widgets_dir.join("built-in");
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for dir in [&built_in_dir, &widgets_dir] {
+        for id in json_stems(dir) {
+            if seen.insert(id.clone()) {
+                entries.push(entry(data_path, &id, BUILT_IN_PACKAGE, dir.join(format!("{}.json", id))));
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+    entries
+}
+
+fn package_widgets(data_path: &Path) -> Vec<WidgetEntry> {
+    let Some(installed) = read_json(&data_path.join("installed-packages.json")) else {
+        return Vec::new();
+    };
+    let Some(packages) = installed.get("packages").and_then(|p| p.as_object()) else {
+        return Vec::new();
+    };
+
+    let mut entries = Vec::new();
+    for (package, info) in packages {
+        let manifest = info.get("manifest").unwrap_or(&Value::Null);
+        let version = text(info, "version").or_else(|| text(manifest, "version"));
+        let device_types: Vec<String> = manifest
+            .get("deviceTypes")
+            .and_then(|t| t.as_array())
+            .map(|types| types.iter().filter_map(|t| t.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+        let widgets = manifest
+            .get("provides")
+            .and_then(|p| p.get("widgets"))
+            .and_then(|w| w.as_array())
+            .cloned()
+            .unwrap_or_default();
+
+        for id in widgets.iter().filter_map(|w| w.as_str()) {
+            let file = data_path.join("widgets").join("packages").join(package).join(format!("{}.json", id));
+            let mut widget = entry(data_path, id, package, file);
+            widget.package_version = version.clone();
+            if !device_types.is_empty() {
+                widget.device_types = device_types.clone();
+            }
+            entries.push(widget);
+        }
+    }
+    entries
+}
+
+// Built-in widgets sorted by id, then package widgets in package id order
+pub fn list_widgets(data_path: &Path) -> Vec<WidgetEntry> {
+    let mut widgets = built_in_widgets(data_path);
+    widgets.extend(package_widgets(data_path));
+    widgets
+}
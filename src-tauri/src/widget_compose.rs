@@ -0,0 +1,261 @@
+// Widget composition
+// A widget definition can build on other definitions instead of copying them:
+//   "extends": "lightdim"          another widget ("package/widget" for a package's)
+//   "fragments": ["battery"]       partial definitions mixed in, in order
+// Layers are merged base -> fragments -> the widget itself. Objects merge key by
+// key, anything else (arrays included) replaces, and null removes a key. When a
+// fragment changes a value the base or an earlier fragment set, that is reported
+// as a conflict and the later layer wins; the widget's own keys are overrides
+// and never conflict.
+// A bare name used by a package widget is looked up in the same package first,
+// then among the built-ins. Fragments live in widgets/fragments/<name>.json, or
+// widgets/packages/<package>/fragments/<name>.json for package fragments.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::floor_export::BUILT_IN_PACKAGE;
+
+// Longest extends chain followed before giving up
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conflict {
+    // JSON pointer of the value set by both layers
+    pub path: String,
+    pub previous: String,
+    // The layer whose value was kept
+    pub kept: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Composition {
+    pub definition: Value,
+    // Layers in merge order, e.g. "extends com.fibaro.built-in/lightdim"
+    pub layers: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+struct Layer {
+    label: String,
+    // Package whose icon folder holds this layer's iconSet
+    icon_package: String,
+}
+
+// Whether the definition uses extends or fragments
+pub fn is_composed(definition: &Value) -> bool {
+    definition.get("extends").is_some() || definition.get("fragments").is_some()
+}
+
+fn read_definition(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+// (package, file) candidates for an extends or fragments reference
+fn candidates(data_path: &Path, package: &str, reference: &str, fragment: bool) -> Vec<(String, PathBuf)> {
+    let widgets = data_path.join("widgets");
+    let file = |dir: PathBuf, name: &str| {
+        let dir = if fragment { dir.join("fragments") } else { dir };
+        dir.join(format!("{}.json", name))
+    };
+    if let Some((pkg, name)) = reference.split_once('/') {
+        return vec![(pkg.to_string(), file(widgets.join("packages").join(pkg), name))];
+    }
+
+    let mut found = Vec::new();
+    if package != BUILT_IN_PACKAGE {
+        found.push((package.to_string(), file(widgets.join("packages").join(package), reference)));
+    }
+    if fragment {
+        found.push((BUILT_IN_PACKAGE.to_string(), widgets.join("fragments").join(format!("{}.json", reference))));
+    } else {
+        found.push((BUILT_IN_PACKAGE.to_string(), widgets.join("built-in").join(format!("{}.json", reference))));
+        // Legacy widgets/ root
+        found.push((BUILT_IN_PACKAGE.to_string(), widgets.join(format!("{}.json", reference))));
+    }
+    found
+}
+
+fn find(data_path: &Path, package: &str, reference: &str, fragment: bool, skip: Option<&Path>) -> Result<(String, PathBuf), String> {
+    candidates(data_path, package, reference, fragment)
+        .into_iter()
+        .find(|(_, path)| path.exists() && Some(path.as_path()) != skip)
+        .ok_or_else(|| format!("{} '{}' not found", if fragment { "Fragment" } else { "Widget" }, reference))
+}
+
+fn pointer_key(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+// Layer that last set `pointer` (or the object containing it)
+fn owner_of(owners: &HashMap<String, usize>, pointer: &str) -> Option<usize> {
+    let mut current = pointer;
+    loop {
+        if let Some(owner) = owners.get(current) {
+            return Some(*owner);
+        }
+        current = &current[..current.rfind('/')?];
+    }
+}
+
+struct Merge {
+    labels: Vec<String>,
+    // JSON pointer -> index of the layer that last set it
+    owners: HashMap<String, usize>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    fn forget_below(&mut self, path: &str) {
+        let prefix = format!("{}/", path);
+        self.owners.retain(|p, _| !p.starts_with(&prefix));
+    }
+
+    fn merge(&mut self, target: &mut Map<String, Value>, overlay: &Map<String, Value>, pointer: &str, layer: usize, own: bool) {
+        for (key, value) in overlay {
+            let path = format!("{}/{}", pointer, pointer_key(key));
+            if value.is_null() {
+                target.remove(key);
+                self.owners.remove(&path);
+                self.forget_below(&path);
+                continue;
+            }
+            if let (Some(Value::Object(existing)), Value::Object(overlay)) = (target.get_mut(key), value) {
+                self.merge(existing, overlay, &path, layer, own);
+                continue;
+            }
+            if !own && target.get(key).is_some_and(|existing| existing != value) {
+                let previous = owner_of(&self.owners, &path).map(|i| self.labels[i].clone()).unwrap_or_default();
+                self.conflicts.push(Conflict {
+                    path: path.clone(),
+                    previous,
+                    kept: self.labels[layer].clone(),
+                });
+            }
+            self.forget_below(&path);
+            self.owners.insert(path, layer);
+            target.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+fn references(definition: &Value, key: &str) -> Result<Vec<String>, String> {
+    match (key, definition.get(key)) {
+        (_, None) => Ok(Vec::new()),
+        ("extends", Some(Value::String(name))) => Ok(vec![name.clone()]),
+        ("extends", Some(_)) => Err("extends must be a widget name".to_string()),
+        (_, Some(Value::Array(names))) => names
+            .iter()
+            .map(|n| n.as_str().map(|s| s.to_string()).ok_or_else(|| "fragments must be a list of names".to_string()))
+            .collect(),
+        (_, Some(_)) => Err("fragments must be a list of names".to_string()),
+    }
+}
+
+fn icon_package(definition: &Value, package: &str) -> String {
+    definition.get("iconPackage").and_then(|p| p.as_str()).unwrap_or(package).to_string()
+}
+
+// `chain` holds the files of the widgets being composed, outermost first
+fn compose_inner(data_path: &Path, package: &str, definition: &Value, chain: &mut Vec<PathBuf>) -> Result<Composition, String> {
+    if chain.len() > MAX_DEPTH {
+        return Err(format!("extends is nested more than {} levels deep", MAX_DEPTH));
+    }
+    let Some(own) = definition.as_object() else {
+        return Err("A widget definition must be a JSON object".to_string());
+    };
+
+    let mut inherited_layers = Vec::new();
+    let mut inherited_conflicts = Vec::new();
+    let mut layers: Vec<(Layer, Map<String, Value>)> = Vec::new();
+
+    if let Some(base) = references(definition, "extends")?.first() {
+        // A package widget may extend the built-in widget of the same name
+        let (base_package, path) = find(data_path, package, base, false, chain.last().map(|p| p.as_path()))?;
+        if chain.contains(&path) {
+            return Err(format!("extends '{}' loops back to a widget that extends it", base));
+        }
+        chain.push(path.clone());
+        let base_definition = read_definition(&path)?;
+        let composed = compose_inner(data_path, &base_package, &base_definition, chain)
+            .map_err(|e| format!("In '{}': {}", base, e))?;
+        chain.pop();
+
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or(base);
+        inherited_layers.extend(composed.layers.into_iter().filter(|label| label != "widget"));
+        inherited_conflicts.extend(composed.conflicts);
+        let layer = Layer {
+            label: format!("extends {}/{}", base_package, name),
+            icon_package: icon_package(&composed.definition, &base_package),
+        };
+        layers.push((layer, composed.definition.as_object().cloned().unwrap_or_default()));
+    }
+
+    for fragment in references(definition, "fragments")? {
+        let (fragment_package, path) = find(data_path, package, &fragment, true, None)?;
+        let fragment_definition = read_definition(&path)?;
+        if is_composed(&fragment_definition) {
+            return Err(format!("Fragment '{}' cannot use extends or fragments itself", fragment));
+        }
+        let Some(fields) = fragment_definition.as_object() else {
+            return Err(format!("Fragment '{}' must be a JSON object", fragment));
+        };
+        let layer = Layer {
+            label: format!("fragment {}/{}", fragment_package, fragment),
+            icon_package: icon_package(&fragment_definition, &fragment_package),
+        };
+        layers.push((layer, fields.clone()));
+    }
+
+    let mut own = own.clone();
+    own.remove("extends");
+    own.remove("fragments");
+    let layer = Layer {
+        label: "widget".to_string(),
+        icon_package: icon_package(definition, package),
+    };
+    layers.push((layer, own));
+
+    let mut merge = Merge {
+        labels: layers.iter().map(|(layer, _)| layer.label.clone()).collect(),
+        owners: HashMap::new(),
+        conflicts: inherited_conflicts,
+    };
+    let mut merged = Map::new();
+    let last = layers.len() - 1;
+    for (index, (_, fields)) in layers.iter().enumerate() {
+        merge.merge(&mut merged, fields, "", index, index == last);
+    }
+
+    // Icon sets are looked up in the folder of the package whose layer chose them
+    let icon_pointer = if merged.contains_key("iconSet") { "/iconSet" } else { "/render/icon/set" };
+    let icon_owner = owner_of(&merge.owners, icon_pointer);
+    merged.remove("iconPackage");
+    if let Some(owner) = icon_owner.map(|i| &layers[i].0.icon_package).filter(|owner| *owner != package) {
+        merged.insert("iconPackage".to_string(), Value::String(owner.clone()));
+    }
+
+    inherited_layers.extend(merge.labels);
+    Ok(Composition {
+        definition: Value::Object(merged),
+        layers: inherited_layers,
+        conflicts: merge.conflicts,
+    })
+}
+
+// Flatten a widget definition from `package`; `file` is where it was read from,
+// if anywhere. Definitions without extends or fragments come back unchanged.
+pub fn compose(data_path: &Path, package: &str, file: Option<&Path>, definition: &Value) -> Result<Composition, String> {
+    if !is_composed(definition) {
+        return Ok(Composition {
+            definition: definition.clone(),
+            layers: vec!["widget".to_string()],
+            conflicts: Vec::new(),
+        });
+    }
+    compose_inner(data_path, package, definition, &mut file.map(|f| vec![f.to_path_buf()]).unwrap_or_default())
+}
@@ -0,0 +1,124 @@
+// Computed state
+// A widget's "computed" section derives extra state properties from the others:
+//   "computed": { "kw": "round(watts / 1000, 2)", "openFor": "duration(since(lastBreached))" }
+// Each value is a widget_eval expression. Computed properties may use each
+// other in any order; ones in a circular dependency (or using one) are null.
+// Results are added to the state, replacing a state property of the same name,
+// so render and ui use them like ordinary state.
+
+use serde_json::{Map, Value};
+
+use crate::widget_eval;
+
+fn roots(expression: &str) -> Vec<String> {
+    widget_eval::referenced_paths(expression)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| path.split('.').next().unwrap_or(&path).to_string())
+        .collect()
+}
+
+// Values of the computed expressions for `state` at `now` (Unix seconds)
+pub fn compute_at(computed: &Map<String, Value>, state: &Map<String, Value>, now: f64) -> Map<String, Value> {
+    let mut scope = state.clone();
+    for name in computed.keys() {
+        scope.remove(name);
+    }
+    let mut values = Map::new();
+    let mut pending: Vec<(&String, &Value)> = computed.iter().collect();
+
+    // Evaluate whatever has its computed inputs ready until nothing changes
+    loop {
+        let before = pending.len();
+        pending.retain(|(name, expression)| {
+            let Some(expression) = expression.as_str() else {
+                println!("Computed property '{}' is not an expression string", name);
+                values.insert(name.to_string(), Value::Null);
+                return false;
+            };
+            let waiting = roots(expression).iter().any(|root| computed.contains_key(root) && !values.contains_key(root));
+            if waiting {
+                return true;
+            }
+            let value = widget_eval::evaluate_at(expression, &scope, now).unwrap_or_else(|e| {
+                println!("Failed to compute '{}': {}", name, e);
+                Value::Null
+            });
+            scope.insert(name.to_string(), value.clone());
+            values.insert(name.to_string(), value);
+            false
+        });
+        if pending.is_empty() || pending.len() == before {
+            break;
+        }
+    }
+    for (name, _) in pending {
+        println!("Computed property '{}' is part of a circular dependency", name);
+        values.insert(name.clone(), Value::Null);
+    }
+    values
+}
+
+// Values of the computed expressions for `state` now
+pub fn compute(computed: &Map<String, Value>, state: &Map<String, Value>) -> Map<String, Value> {
+    compute_at(computed, state, widget_eval::unix_now())
+}
+
+// Add the widget's computed properties to `state`
+pub fn apply(widget: &Value, state: &mut Map<String, Value>) {
+    if let Some(computed) = widget.get("computed").and_then(|c| c.as_object()) {
+        let values = compute(computed, state);
+        state.extend(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn map(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn computes_in_dependency_order() {
+        let computed = map(json!({
+            "label": "fixed(kw, 1) + ' kW'",
+            "kw": "watts / 1000",
+            "avg": "avg(a, b)"
+        }));
+        let values = compute_at(&computed, &map(json!({ "watts": 2500, "a": 1, "b": 2 })), 0.0);
+        assert_eq!(values["kw"], json!(2.5));
+        assert_eq!(values["label"], json!("2.5 kW"));
+        assert_eq!(values["avg"], json!(1.5));
+    }
+
+    #[test]
+    fn time_since_uses_now() {
+        let computed = map(json!({ "openFor": "value ? duration(since(lastBreached)) : ''" }));
+        let state = map(json!({ "value": true, "lastBreached": 1000 }));
+        assert_eq!(compute_at(&computed, &state, 1360.0)["openFor"], json!("6 min"));
+    }
+
+    #[test]
+    fn cycles_errors_and_non_strings_are_null() {
+        let computed = map(json!({ "x": "y + 1", "y": "x + 1", "bad": "1 +", "num": 5, "ok": "2 * 2" }));
+        let values = compute_at(&computed, &Map::new(), 0.0);
+        assert_eq!(values["x"], Value::Null);
+        assert_eq!(values["y"], Value::Null);
+        assert_eq!(values["bad"], Value::Null);
+        assert_eq!(values["num"], Value::Null);
+        assert_eq!(values["ok"], json!(4));
+    }
+
+    #[test]
+    fn computed_values_replace_state() {
+        let widget = json!({ "computed": { "value": "raw * 2" } });
+        let mut state = map(json!({ "raw": 4, "value": 1 }));
+        apply(&widget, &mut state);
+        assert_eq!(state["value"], json!(8));
+        apply(&json!({}), &mut state);
+        assert_eq!(state["value"], json!(8));
+    }
+}
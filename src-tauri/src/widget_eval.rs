@@ -0,0 +1,663 @@
+// Expression evaluation for widget definitions
+// Supports the condition syntax used in render.icon.conditions[].when and
+// render.subtext.visible, and the expressions inside ${...} templates:
+// literals (numbers, 'strings', "strings", true, false, null), state property
+// paths (value, colorComponents.red), arithmetic (+ - * / % and unary -),
+// comparison operators (== != < <= > >=), logical operators (&& || !), the
+// conditional operator (a ? b : c), parentheses and the functions listed at
+// call(). Operators follow JavaScript: + concatenates when either side is a
+// string, && and || return one of their operands, and arithmetic that doesn't
+// give a finite number yields null.
+
+use serde_json::{Map, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Parentheses, call arguments, conditionals and unary operators nested deeper
+// than this are rejected, so a hostile widget can't overflow the stack
+const MAX_NESTING: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_digit() || (c == '.' && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit())) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse::<f64>()
+                .map_err(|_| format!("Invalid number '{}'", text))?;
+            tokens.push(Token::Number(number));
+            continue;
+        }
+        if c == '\'' || c == '"' {
+            let quote = c;
+            let start = i + 1;
+            i += 1;
+            while i < chars.len() && chars[i] != quote {
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(format!("Unterminated string in '{}'", input));
+            }
+            tokens.push(Token::Str(chars[start..i].iter().collect()));
+            i += 1;
+            continue;
+        }
+        // '@' starts the current node in JSONPath filters
+        if c.is_alphabetic() || c == '_' || c == '$' || c == '@' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$' || chars[i] == '@' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            continue;
+        }
+        if c == '(' {
+            tokens.push(Token::LParen);
+            i += 1;
+            continue;
+        }
+        if c == ')' {
+            tokens.push(Token::RParen);
+            i += 1;
+            continue;
+        }
+        if c == ',' {
+            tokens.push(Token::Comma);
+            i += 1;
+            continue;
+        }
+
+        let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+        let op = ["===", "!==", "==", "!=", "<=", ">=", "&&", "||", "<", ">", "!", "+", "-", "*", "/", "%", "?", ":"]
+            .into_iter()
+            .find(|op| rest.starts_with(op))
+            .ok_or_else(|| format!("Unexpected character '{}' in '{}'", c, input))?;
+        i += op.len();
+        // JS strict equality behaves like == for the JSON values widgets compare
+        tokens.push(Token::Op(match op {
+            "===" => "==",
+            "!==" => "!=",
+            other => other,
+        }));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    state: &'a Map<String, Value>,
+    // Unix time in seconds, for now() and since()
+    now: f64,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        if self.depth >= MAX_NESTING {
+            return Err("expression nested too deeply".to_string());
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_op(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse_conditional(&mut self) -> Result<Value, String> {
+        let condition = self.parse_or()?;
+        if !self.eat_op("?") {
+            return Ok(condition);
+        }
+        let when_true = self.nested(Self::parse_conditional)?;
+        if !self.eat_op(":") {
+            return Err("Missing ':' in conditional expression".to_string());
+        }
+        let when_false = self.nested(Self::parse_conditional)?;
+        Ok(if truthy(&condition) { when_true } else { when_false })
+    }
+
+    fn parse_or(&mut self) -> Result<Value, String> {
+        let mut left = self.parse_and()?;
+        while self.eat_op("||") {
+            let right = self.parse_and()?;
+            left = if truthy(&left) { left } else { right };
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Value, String> {
+        let mut left = self.parse_comparison()?;
+        while self.eat_op("&&") {
+            let right = self.parse_comparison()?;
+            left = if truthy(&left) { right } else { left };
+        }
+        Ok(left)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Value, String> {
+        let mut left = self.parse_additive()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            if !matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=") {
+                break;
+            }
+            self.pos += 1;
+            let right = self.parse_additive()?;
+            left = Value::Bool(compare(&left, op, &right));
+        }
+        Ok(left)
+    }
+
+    fn parse_additive(&mut self) -> Result<Value, String> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            if self.eat_op("+") {
+                let right = self.parse_multiplicative()?;
+                left = add(&left, &right);
+            } else if self.eat_op("-") {
+                let right = self.parse_multiplicative()?;
+                left = arithmetic(&left, '-', &right);
+            } else {
+                return Ok(left);
+            }
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Value, String> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op("*")) => '*',
+                Some(Token::Op("/")) => '/',
+                Some(Token::Op("%")) => '%',
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = arithmetic(&left, op, &right);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Value, String> {
+        if self.eat_op("!") {
+            let value = self.nested(Self::parse_unary)?;
+            return Ok(Value::Bool(!truthy(&value)));
+        }
+        if self.eat_op("-") {
+            let value = self.nested(Self::parse_unary)?;
+            return Ok(arithmetic(&Value::from(0), '-', &value));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Value, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(number_value(n)),
+            Some(Token::Str(s)) => Ok(Value::String(s)),
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() == Some(&Token::RParen) {
+                    self.pos += 1;
+                } else {
+                    loop {
+                        args.push(self.nested(Self::parse_conditional)?);
+                        match self.next() {
+                            Some(Token::Comma) => continue,
+                            Some(Token::RParen) => break,
+                            _ => return Err(format!("Missing closing parenthesis in call to {}()", name)),
+                        }
+                    }
+                }
+                call(&name, &args, self.now)
+            }
+            Some(Token::Ident(name)) => Ok(match name.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" | "undefined" => Value::Null,
+                path => lookup_path(self.state, path).cloned().unwrap_or(Value::Null),
+            }),
+            Some(Token::LParen) => {
+                let value = self.nested(Self::parse_conditional)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(value),
+                    _ => Err("Missing closing parenthesis".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected token {:?}", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn number_value(n: f64) -> Value {
+    // Whole numbers stay integers so they print as "5", not "5.0"
+    if n.fract() == 0.0 && n.abs() < 9.0e15 {
+        return Value::from(n as i64);
+    }
+    serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+}
+
+fn add(left: &Value, right: &Value) -> Value {
+    if left.is_string() || right.is_string() {
+        return Value::String(format!("{}{}", to_js_string(left), to_js_string(right)));
+    }
+    arithmetic(left, '+', right)
+}
+
+fn arithmetic(left: &Value, op: char, right: &Value) -> Value {
+    let (Some(a), Some(b)) = (as_number(left), as_number(right)) else {
+        return Value::Null;
+    };
+    let result = match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        '/' => a / b,
+        _ => a % b,
+    };
+    if result.is_finite() {
+        number_value(result)
+    } else {
+        Value::Null
+    }
+}
+
+// String(value) in JavaScript, as used when a value is put into a template
+pub fn to_js_string(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => i.to_string(),
+            (None, Some(f)) if f.fract() == 0.0 && f.abs() < 1e21 => format!("{}", f as i128),
+            (None, Some(f)) => f.to_string(),
+            _ => n.to_string(),
+        },
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| if item.is_null() { String::new() } else { to_js_string(item) })
+            .collect::<Vec<_>>()
+            .join(","),
+        Value::Object(_) => "[object Object]".to_string(),
+    }
+}
+
+fn numbers(args: &[Value]) -> Vec<f64> {
+    args.iter().filter(|a| !a.is_null()).filter_map(as_number).collect()
+}
+
+fn number_or_null(n: Option<f64>) -> Value {
+    n.filter(|n| n.is_finite()).map(number_value).unwrap_or(Value::Null)
+}
+
+// Math.round: halves round up
+fn round_to(n: f64, digits: f64) -> f64 {
+    let factor = 10f64.powi(digits as i32);
+    (n * factor + 0.5).floor() / factor
+}
+
+// "45 s", "5 min", "2 h", "3 d"
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).floor();
+    if seconds < 60.0 {
+        format!("{} s", seconds)
+    } else if seconds < 3600.0 {
+        format!("{} min", (seconds / 60.0).floor())
+    } else if seconds < 86400.0 {
+        format!("{} h", (seconds / 3600.0).floor())
+    } else {
+        format!("{} d", (seconds / 86400.0).floor())
+    }
+}
+
+// Functions available in expressions ("Math." prefixes are accepted). Numeric
+// functions give null for arguments that aren't numbers; min, max and avg skip
+// null arguments. Timestamps are Unix seconds (values above 1e11 are taken as
+// milliseconds).
+//   round(x[, digits]) floor(x) ceil(x) abs(x) min(...) max(...) avg(...)
+//   fixed(x, digits)   number(x) text(x) upper(s) lower(s)
+//   now()              since(timestamp) -> seconds   duration(seconds) -> "5 min"
+fn call(name: &str, args: &[Value], now: f64) -> Result<Value, String> {
+    let name = name.strip_prefix("Math.").unwrap_or(name);
+    let arg = |i: usize| args.get(i).unwrap_or(&Value::Null);
+    let number = |i: usize| Some(arg(i)).filter(|a| !a.is_null()).and_then(as_number);
+    let all = numbers(args);
+    Ok(match name {
+        "round" => number_or_null(number(0).map(|n| round_to(n, number(1).unwrap_or(0.0)))),
+        "floor" => number_or_null(number(0).map(f64::floor)),
+        "ceil" => number_or_null(number(0).map(f64::ceil)),
+        "abs" => number_or_null(number(0).map(f64::abs)),
+        "min" => number_or_null(all.iter().copied().reduce(f64::min)),
+        "max" => number_or_null(all.iter().copied().reduce(f64::max)),
+        "avg" => number_or_null((!all.is_empty()).then(|| all.iter().sum::<f64>() / all.len() as f64)),
+        "fixed" => match number(0) {
+            Some(n) => Value::String(format!("{:.*}", number(1).unwrap_or(0.0).clamp(0.0, 20.0) as usize, n)),
+            None => Value::Null,
+        },
+        "number" => number_or_null(number(0)),
+        "text" => Value::String(to_js_string(arg(0))),
+        "upper" => Value::String(to_js_string(arg(0)).to_uppercase()),
+        "lower" => Value::String(to_js_string(arg(0)).to_lowercase()),
+        "now" => number_value(now.floor()),
+        "since" => number_or_null(number(0).map(|t| {
+            let t = if t > 1e11 { t / 1000.0 } else { t };
+            (now - t).max(0.0).floor()
+        })),
+        "duration" => match number(0) {
+            Some(seconds) => Value::String(format_duration(seconds)),
+            None => Value::Null,
+        },
+        other => return Err(format!("Unknown function '{}'", other)),
+    })
+}
+
+// Current Unix time in seconds
+pub fn unix_now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs_f64()).unwrap_or(0.0)
+}
+
+// Resolve a dotted property path ("colorComponents.red") against the state
+pub fn lookup_path<'a>(state: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+    let mut parts = path.split('.');
+    let mut current = state.get(parts.next()?)?;
+    for part in parts {
+        current = match current {
+            Value::Object(map) => map.get(part)?,
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+// JavaScript-like truthiness
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().map(|f| f != 0.0 && !f.is_nan()).unwrap_or(false),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        Value::Null => Some(0.0),
+        _ => None,
+    }
+}
+
+fn compare(left: &Value, op: &str, right: &Value) -> bool {
+    let equal = match (left, right) {
+        (Value::Null, Value::Null) => true,
+        (Value::Null, _) | (_, Value::Null) => false,
+        (Value::String(a), Value::String(b)) => a == b,
+        _ => match (as_number(left), as_number(right)) {
+            (Some(a), Some(b)) => a == b,
+            _ => left == right,
+        },
+    };
+    match op {
+        "==" => equal,
+        "!=" => !equal,
+        _ => {
+            let ordering = match (left, right) {
+                (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                _ => match (as_number(left), as_number(right)) {
+                    (Some(a), Some(b)) => a.partial_cmp(&b),
+                    _ => None,
+                },
+            };
+            let Some(ordering) = ordering else {
+                return false;
+            };
+            match op {
+                "<" => ordering.is_lt(),
+                "<=" => ordering.is_le(),
+                ">" => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }
+        }
+    }
+}
+
+// Evaluate an expression against a state object
+pub fn evaluate(expression: &str, state: &Map<String, Value>) -> Result<Value, String> {
+    evaluate_at(expression, state, unix_now())
+}
+
+// Evaluate with `now` (Unix seconds) as the current time
+pub fn evaluate_at(expression: &str, state: &Map<String, Value>, now: f64) -> Result<Value, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        pos: 0,
+        state,
+        now,
+        depth: 0,
+    };
+    let value = parser.parse_conditional()?;
+    if parser.pos < parser.tokens.len() {
+        return Err(format!("Unexpected trailing input in '{}'", expression));
+    }
+    Ok(value)
+}
+
+fn paths_in(tokens: Vec<Token>) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        // Function names aren't properties
+        if tokens.get(i + 1) == Some(&Token::LParen) {
+            continue;
+        }
+        if let Token::Ident(name) = token {
+            if !matches!(name.as_str(), "true" | "false" | "null" | "undefined") && !paths.contains(name) {
+                paths.push(name.clone());
+            }
+        }
+    }
+    paths
+}
+
+// Check that an expression parses and return the state property paths it reads
+pub fn referenced_paths(expression: &str) -> Result<Vec<String>, String> {
+    evaluate(expression, &Map::new())?;
+    Ok(paths_in(tokenize(expression)?))
+}
+
+// Evaluate a condition. Errors count as false, and so does reading a property
+// the state doesn't have at all (the webview can't evaluate those either).
+// That is expected while a device's state fills in, so it isn't logged.
+pub fn evaluate_condition(expression: &str, state: &Map<String, Value>) -> bool {
+    let missing = tokenize(expression)
+        .map(|tokens| paths_in(tokens).into_iter().any(|path| !state.contains_key(path.split('.').next().unwrap_or(&path))))
+        .unwrap_or(false);
+    if missing {
+        return false;
+    }
+    match evaluate(expression, state) {
+        Ok(value) => truthy(&value),
+        Err(e) => {
+            println!("Failed to evaluate condition '{}': {}", expression, e);
+            false
+        }
+    }
+}
+
+// Substitute every ${...} in a template. A placeholder containing an operator
+// character (* + - / % < > ! = ? & | or parentheses) is evaluated as an
+// expression; anything else is a property path, and a missing
+// colorComponents.* part counts as 0. A placeholder that reads a missing
+// property or doesn't parse is left as written, like the webview does.
+pub fn evaluate_template(template: &str, state: &Map<String, Value>) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let placeholder = &rest[start..start + 2 + end + 1];
+        let expression = &after[..end];
+        rest = &after[end + 1..];
+
+        let expression = expression.trim();
+        let is_path = !expression.contains(|c: char| "*+-/%<>!=?&|()".contains(c));
+        let value = if is_path {
+            lookup_path(state, expression).cloned().or_else(|| expression.starts_with("colorComponents.").then(|| Value::from(0)))
+        } else {
+            let paths = tokenize(expression).map(paths_in).unwrap_or_default();
+            if paths.iter().any(|path| lookup_path(state, path).is_none()) {
+                None
+            } else {
+                evaluate(expression, state).ok()
+            }
+        };
+        match value {
+            Some(value) => result.push_str(&to_js_string(&value)),
+            None => result.push_str(placeholder),
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    #[test]
+    fn follows_javascript_operators() {
+        let s = state(json!({ "value": 7, "name": "lamp", "on": true }));
+        assert_eq!(evaluate("value + 1", &s).unwrap(), json!(8));
+        assert_eq!(evaluate("name + value", &s).unwrap(), json!("lamp7"));
+        assert_eq!(evaluate("value % 4 * 2 - -1", &s).unwrap(), json!(7));
+        assert_eq!(evaluate("value / 2", &s).unwrap(), json!(3.5));
+        assert_eq!(evaluate("value / 0", &s).unwrap(), Value::Null);
+        assert_eq!(evaluate("on && name", &s).unwrap(), json!("lamp"));
+        assert_eq!(evaluate("missing || 'none'", &s).unwrap(), json!("none"));
+        assert_eq!(evaluate("value > 5 ? 'high' : 'low'", &s).unwrap(), json!("high"));
+        assert_eq!(evaluate("1 + 2 * 3 == 7 && !false", &s).unwrap(), json!(true));
+        assert_eq!(evaluate("'5' == 5", &s).unwrap(), json!(true));
+        assert!(evaluate("value >", &s).is_err());
+        assert!(evaluate("value ? 1", &s).is_err());
+    }
+
+    #[test]
+    fn rejects_deeply_nested_expressions() {
+        let s = state(json!({ "value": 1 }));
+        let nested = |open: &str, close: &str, depth: usize| format!("{}value{}", open.repeat(depth), close.repeat(depth));
+        assert_eq!(evaluate(&nested("(", ")", MAX_NESTING), &s).unwrap(), json!(1));
+        for expression in [nested("(", ")", 20_000), nested("!", "", 20_000), nested("-", "", 20_000), nested("max(", ")", 20_000)] {
+            assert_eq!(evaluate(&expression, &s).unwrap_err(), "expression nested too deeply");
+        }
+        let conditional = format!("{}1", "value ? ".repeat(20_000));
+        assert!(evaluate(&conditional, &s).is_err());
+        assert!(!evaluate_condition(&nested("(", ")", 20_000), &s));
+        assert!(referenced_paths(&nested("!", "", 20_000)).is_err());
+    }
+
+    #[test]
+    fn conditions_reading_absent_properties_are_false() {
+        let s = state(json!({ "value": null }));
+        assert!(evaluate_condition("value == null", &s));
+        assert!(!evaluate_condition("other == null", &s));
+        assert!(!evaluate_condition("value ==", &s));
+    }
+
+    #[test]
+    fn substitutes_templates() {
+        let s = state(json!({ "value": 21.5, "colorComponents": { "red": 255 }, "flags": [1, null, 2] }));
+        assert_eq!(evaluate_template("${value}°C", &s), "21.5°C");
+        assert_eq!(evaluate_template("${value * 2}", &s), "43");
+        assert_eq!(evaluate_template("${ value > 20 ? 'warm' : 'cold' }", &s), "warm");
+        assert_eq!(evaluate_template("rgb(${colorComponents.red}, ${colorComponents.green})", &s), "rgb(255, 0)");
+        assert_eq!(evaluate_template("${flags}", &s), "1,,2");
+        assert_eq!(evaluate_template("${missing} and ${missing + 1}", &s), "${missing} and ${missing + 1}");
+        assert_eq!(evaluate_template("${value +}", &s), "${value +}");
+        assert_eq!(evaluate_template("open ${value", &s), "open ${value");
+    }
+
+    #[test]
+    fn calls_functions() {
+        let s = state(json!({ "watts": 1234.5, "a": 20, "b": null, "opened": 1000, "name": "Hall" }));
+        let eval = |expression: &str| evaluate_at(expression, &s, 1300.0).unwrap();
+        assert_eq!(eval("round(watts / 1000, 1)"), json!(1.2));
+        assert_eq!(eval("Math.round(2.5) + floor(-1.5) + ceil(0.2) + abs(-3)"), json!(5));
+        assert_eq!(eval("fixed(watts / 1000, 2) + ' kW'"), json!("1.23 kW"));
+        assert_eq!(eval("avg(a, b, 30)"), json!(25));
+        assert_eq!(eval("min(a, 5) + max(a, 5)"), json!(25));
+        assert_eq!(eval("min(b)"), Value::Null);
+        assert_eq!(eval("round(name)"), Value::Null);
+        assert_eq!(eval("upper(name) + lower('X') + text(a)"), json!("HALLx20"));
+        assert_eq!(eval("number('12') + 1"), json!(13));
+        assert_eq!(eval("now()"), json!(1300));
+        assert_eq!(eval("since(opened)"), json!(300));
+        assert_eq!(eval("since(opened * 1000000000)"), json!(0));
+        assert_eq!(eval("duration(since(opened))"), json!("5 min"));
+        assert_eq!(eval("duration(59) + '/' + duration(7200) + '/' + duration(200000)"), json!("59 s/2 h/2 d"));
+        assert!(evaluate_at("nope(1)", &s, 0.0).is_err());
+        assert!(evaluate_at("round(1", &s, 0.0).is_err());
+        assert_eq!(referenced_paths("round(watts, 1) > a").unwrap(), vec!["watts", "a"]);
+    }
+
+    #[test]
+    fn formats_values_like_javascript_strings() {
+        assert_eq!(to_js_string(&json!(3.0)), "3");
+        assert_eq!(to_js_string(&json!(0.1)), "0.1");
+        assert_eq!(to_js_string(&Value::Null), "null");
+        assert_eq!(to_js_string(&json!({ "a": 1 })), "[object Object]");
+    }
+}
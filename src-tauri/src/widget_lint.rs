@@ -0,0 +1,972 @@
+// Widget definition linter
+// The webview only finds out at render time that a widget is broken, and then
+// it simply shows nothing. This checks a widget file (format v0.1.5, see
+// docs/WIDGET_FORMAT.md) up front: JSON syntax and the widget schema, getter
+// paths, event match filters and update expressions, render conditions and
+// ${...} templates, the icon set folder and the icon names used from it, icon
+// tint and badge settings, and the actions referenced from ui elements. Every
+// diagnostic carries the JSON pointer and the line and column of the offending
+// value.
+// A widget using extends or fragments is checked in its composed form, keeping
+// only diagnostics for values written in the file itself; inherited values are
+// reported when their own file is linted.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::floor_export::ICON_EXTENSIONS;
+use crate::icon_variants::{self, BADGE_POSITIONS};
+use crate::schemas;
+use crate::widget_compose::{self, Composition};
+use crate::widget_eval;
+
+// Oldest widget format the webview accepts (MIN_WIDGET_VERSION in utils.js)
+const MIN_WIDGET_VERSION: (u64, u64, u64) = (0, 1, 5);
+const KNOWN_KEYS: [&str; 17] = [
+    "$schema", "widgetVersion", "iconSet", "state", "getters", "events", "render", "actions", "ui",
+    "id", "name", "description", "version", "extends", "fragments", "iconPackage", "computed",
+];
+// Checks that need the inherited parts of a composed widget
+const CONTEXT_CODES: [&str; 2] = ["unknown-property", "unknown-action"];
+const HTTP_METHODS: [&str; 5] = ["GET", "POST", "PUT", "DELETE", "PATCH"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    // The widget (or part of it) will not work
+    Error,
+    // Probably a mistake, but the widget still renders
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WidgetDiagnostic {
+    pub severity: Severity,
+    // Machine-readable kind, e.g. "unknown-action"
+    pub code: String,
+    // JSON pointer to the offending value ("" for the document root)
+    pub path: String,
+    // 1-based position in the file
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WidgetLintReport {
+    pub widget: String,
+    pub file: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub diagnostics: Vec<WidgetDiagnostic>,
+}
+
+// ============================================================================
+// Source positions
+// ============================================================================
+
+// Records the line and column at which every value in a JSON document starts,
+// keyed by JSON pointer. Only run on text serde_json has already accepted.
+struct PositionScanner {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    positions: HashMap<String, (usize, usize)>,
+}
+
+impl PositionScanner {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let mut text = String::new();
+        self.bump();
+        while let Some(c) = self.bump() {
+            match c {
+                '"' => break,
+                '\\' => match self.bump() {
+                    Some('n') => text.push('\n'),
+                    Some('t') => text.push('\t'),
+                    Some('r') => text.push('\r'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                        let decoded = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32);
+                        text.push(decoded.unwrap_or('\u{fffd}'));
+                    }
+                    Some(other) => text.push(other),
+                    None => break,
+                },
+                other => text.push(other),
+            }
+        }
+        text
+    }
+
+    fn value(&mut self, pointer: String) {
+        self.skip_whitespace();
+        self.positions.insert(pointer.clone(), (self.line, self.column));
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some('"') => {
+                            let key = self.string();
+                            self.skip_whitespace();
+                            self.bump(); // ':'
+                            self.value(format!("{}/{}", pointer, escape_pointer(&key)));
+                        }
+                        Some(',') => {
+                            self.bump();
+                        }
+                        _ => {
+                            self.bump(); // '}'
+                            break;
+                        }
+                    }
+                }
+            }
+            Some('[') => {
+                self.bump();
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(']') | None => {
+                            self.bump();
+                            break;
+                        }
+                        Some(',') => {
+                            self.bump();
+                        }
+                        _ => {
+                            self.value(format!("{}/{}", pointer, index));
+                            index += 1;
+                        }
+                    }
+                }
+            }
+            Some('"') => {
+                self.string();
+            }
+            _ => {
+                while self.peek().is_some_and(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | '}')) {
+                    self.bump();
+                }
+            }
+        }
+    }
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn value_positions(source: &str) -> HashMap<String, (usize, usize)> {
+    let mut scanner = PositionScanner {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 1,
+        column: 1,
+        positions: HashMap::new(),
+    };
+    scanner.value(String::new());
+    scanner.positions
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+struct Lint<'a> {
+    positions: HashMap<String, (usize, usize)>,
+    diagnostics: Vec<WidgetDiagnostic>,
+    // State properties the widget defines (state, getters, event updates and computed)
+    properties: BTreeSet<String>,
+    actions: BTreeSet<String>,
+    find_icon_set: &'a dyn Fn(&str) -> Option<PathBuf>,
+}
+
+impl Lint<'_> {
+    fn push(&mut self, severity: Severity, code: &str, path: &str, message: String) {
+        // Missing members are reported at their parent
+        let mut located = path;
+        let (line, column) = loop {
+            if let Some(position) = self.positions.get(located) {
+                break *position;
+            }
+            match located.rfind('/') {
+                Some(index) => located = &located[..index],
+                None => break (1, 1),
+            }
+        };
+        self.diagnostics.push(WidgetDiagnostic {
+            severity,
+            code: code.to_string(),
+            path: path.to_string(),
+            line,
+            column,
+            message,
+        });
+    }
+
+    fn error(&mut self, code: &str, path: &str, message: String) {
+        self.push(Severity::Error, code, path, message);
+    }
+
+    fn warning(&mut self, code: &str, path: &str, message: String) {
+        self.push(Severity::Warning, code, path, message);
+    }
+
+    fn check_property(&mut self, path: &str, property: &str, context: &str) {
+        let root = property.split('.').next().unwrap_or(property);
+        if !self.properties.contains(root) {
+            self.warning(
+                "unknown-property",
+                path,
+                format!("{} uses '{}', which is not in state, getters, event updates or computed", context, property),
+            );
+        }
+    }
+
+    fn check_dotted_path(&mut self, code: &str, path: &str, value: &str, what: &str) -> bool {
+        let valid = !value.is_empty()
+            && value.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '$'));
+        if !valid {
+            self.error(code, path, format!("{} '{}' is not a dotted property path like 'properties.value'", what, value));
+        }
+        valid
+    }
+
+    // ${...} placeholders in a template; `variables` limits which ones may appear
+    fn check_template(&mut self, path: &str, template: &str, variables: Option<&[&str]>) {
+        let mut rest = template;
+        while let Some(start) = rest.find("${") {
+            let after = &rest[start + 2..];
+            let Some(end) = after.find('}') else {
+                self.error("template", path, format!("Unterminated ${{ in '{}'", template));
+                return;
+            };
+            let expression = after[..end].trim();
+            rest = &after[end + 1..];
+
+            if expression.is_empty() {
+                self.error("template", path, format!("Empty ${{}} in '{}'", template));
+                continue;
+            }
+            if let Err(e) = check_balanced(expression) {
+                self.error("template", path, format!("Invalid expression '${{{}}}': {}", expression, e));
+                continue;
+            }
+            let (names, calls) = template_identifiers(expression);
+            for call in calls {
+                // The webview evaluates templates as JavaScript
+                self.warning(
+                    "template-function",
+                    path,
+                    format!("'${{{}}}' calls {}(), which only works in computed; compute the value there and use it by name", expression, call),
+                );
+            }
+            for name in names {
+                match variables {
+                    Some(allowed) => {
+                        if !allowed.contains(&name.as_str()) {
+                            self.error(
+                                "template",
+                                path,
+                                format!("'${{{}}}' is not available here (only {})", expression, allowed.iter().map(|v| format!("${{{}}}", v)).collect::<Vec<_>>().join(", ")),
+                            );
+                        }
+                    }
+                    None => self.check_property(path, &name, &format!("Template '${{{}}}'", expression)),
+                }
+            }
+        }
+    }
+
+    // A condition expression; returns the properties it reads
+    fn check_condition(&mut self, path: &str, expression: &str) -> Vec<String> {
+        match widget_eval::referenced_paths(expression) {
+            Ok(paths) => paths,
+            Err(e) => {
+                self.error("condition", path, format!("Invalid condition '{}': {}", expression, e));
+                Vec::new()
+            }
+        }
+    }
+
+    fn check_version(&mut self, widget: &Map<String, Value>) {
+        let Some(version) = widget.get("widgetVersion") else {
+            self.warning("widget-version", "", "widgetVersion is missing; the widget is treated as an old format widget".to_string());
+            return;
+        };
+        let parsed = version.as_str().and_then(|v| {
+            let parts: Vec<u64> = v.split('.').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+            (parts.len() == 3).then(|| (parts[0], parts[1], parts[2]))
+        });
+        match parsed {
+            None => self.error("widget-version", "/widgetVersion", format!("widgetVersion {} is not a version like \"0.1.5\"", version)),
+            Some(v) if v.0 > MIN_WIDGET_VERSION.0 => self.error(
+                "widget-version",
+                "/widgetVersion",
+                format!("widgetVersion {} is newer than the supported {}.x.y", version, MIN_WIDGET_VERSION.0),
+            ),
+            Some(v) if v < MIN_WIDGET_VERSION => self.error(
+                "widget-version",
+                "/widgetVersion",
+                format!("widgetVersion {} is older than the supported {}.{}.{}", version, MIN_WIDGET_VERSION.0, MIN_WIDGET_VERSION.1, MIN_WIDGET_VERSION.2),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    fn check_getters(&mut self, widget: &Map<String, Value>) {
+        let Some(getters) = widget.get("getters").and_then(|g| g.as_object()) else {
+            return;
+        };
+        for (property, getter) in getters {
+            let base = format!("/getters/{}", escape_pointer(property));
+            if let Some(api) = getter.get("api").and_then(|a| a.as_str()) {
+                if !api.starts_with('/') {
+                    self.error("getter-api", &format!("{}/api", base), format!("Getter api '{}' must start with '/'", api));
+                }
+                self.check_template(&format!("{}/api", base), api, Some(&["id"]));
+            }
+            if let Some(path) = getter.get("path").and_then(|p| p.as_str()) {
+                self.check_dotted_path("getter-path", &format!("{}/path", base), path, "Getter path");
+            }
+        }
+    }
+
+    fn check_computed(&mut self, widget: &Map<String, Value>) {
+        let Some(computed) = widget.get("computed").and_then(|c| c.as_object()) else {
+            return;
+        };
+        let shadowing: Vec<&String> = computed.keys().filter(|name| self.properties.contains(*name)).collect();
+        for name in shadowing {
+            self.warning(
+                "computed-shadow",
+                &format!("/computed/{}", escape_pointer(name)),
+                format!("Computed '{}' replaces the state property of the same name", name),
+            );
+        }
+        self.properties.extend(computed.keys().cloned());
+
+        for (name, expression) in computed {
+            let path = format!("/computed/{}", escape_pointer(name));
+            let Some(expression) = expression.as_str() else {
+                self.error("computed", &path, format!("Computed '{}' must be an expression string", name));
+                continue;
+            };
+            match widget_eval::referenced_paths(expression) {
+                Ok(paths) => {
+                    for property in paths {
+                        self.check_property(&path, &property, &format!("Computed '{}'", name));
+                    }
+                }
+                Err(e) => self.error("computed", &path, format!("Invalid expression '{}': {}", expression, e)),
+            }
+        }
+    }
+
+    fn check_events(&mut self, widget: &Map<String, Value>) {
+        let Some(events) = widget.get("events").and_then(|e| e.as_object()) else {
+            return;
+        };
+        for (event_type, event) in events {
+            let base = format!("/events/{}", escape_pointer(event_type));
+            if let Some(filter) = event.get("match").and_then(|m| m.as_str()) {
+                let path = format!("{}/match", base);
+                if !filter.starts_with('$') {
+                    self.error("event-match", &path, format!("JSONPath '{}' must start with '$'", filter));
+                } else if let Err(e) = check_balanced(filter) {
+                    self.error("event-match", &path, format!("Invalid JSONPath '{}': {}", filter, e));
+                } else if filter.contains("?") && !filter.contains("?(") {
+                    self.error("event-match", &path, format!("JSONPath filter in '{}' must be written as ?(...)", filter));
+                }
+                self.check_template(&path, filter, Some(&["id"]));
+                if !filter.contains("${id}") {
+                    self.warning("event-match", &path, "match does not use ${id}, so events from every device update this widget".to_string());
+                }
+            }
+            let Some(updates) = event.get("updates").and_then(|u| u.as_object()) else {
+                continue;
+            };
+            for (property, update) in updates {
+                let Some(update) = update.as_str() else {
+                    continue;
+                };
+                self.check_update(&format!("{}/updates/{}", base, escape_pointer(property)), update);
+            }
+        }
+    }
+
+    // "event.newValue", "prop == event.property ? event.newValue" or
+    // "(a == event.property || b == event.property) ? event.newValue", as parsed by eventManager.js
+    fn check_update(&mut self, path: &str, update: &str) {
+        let value_path = if update.contains("==") {
+            let Some((condition, value_path)) = update.split_once('?') else {
+                self.error("event-update", path, format!("Update '{}' compares with == but has no '? value' part", update));
+                return;
+            };
+            let condition = condition.trim();
+            let inner = condition.strip_prefix('(').and_then(|c| c.strip_suffix(')')).unwrap_or(condition);
+            let alternatives: Vec<&str> = inner.split("||").map(str::trim).collect();
+            let valid = alternatives.iter().all(|alternative| {
+                alternative.split_once("==").is_some_and(|(name, rhs)| {
+                    let name = name.trim();
+                    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') && rhs.trim() == "event.property"
+                })
+            });
+            if !valid || (alternatives.len() > 1 && inner == condition) {
+                self.error(
+                    "event-update",
+                    path,
+                    format!("Condition '{}' must be 'prop == event.property' or '(a == event.property || b == event.property)'", condition),
+                );
+                return;
+            }
+            value_path.trim()
+        } else {
+            update.trim()
+        };
+        if self.check_dotted_path("event-update", path, value_path, "Update value") {
+            // eventManager.js reads "event.<key>" as a single key of the event data
+            if value_path.strip_prefix("event.").is_some_and(|key| key.contains('.')) {
+                self.error("event-update", path, format!("'{}' can only read a top-level field of the event data", value_path));
+            }
+        }
+    }
+
+    fn check_icon_name(&mut self, path: &str, icon: &str, icons: Option<&(String, BTreeSet<String>)>) {
+        if let Some((set, names)) = icons {
+            if !names.contains(icon) {
+                self.error("icon-missing", path, format!("Icon '{}' is not in icon set '{}'", icon, set));
+            }
+        }
+    }
+
+    // Literal colours must be ones the icon variant generator understands
+    fn check_color(&mut self, path: &str, color: &str) {
+        if color.contains("${") {
+            self.check_template(path, color, None);
+        } else if icon_variants::parse_color(color).is_none() {
+            self.error("icon-variant", path, format!("Colour '{}' is not #rgb, #rrggbb, rgb(r, g, b) or a basic colour name", color));
+        }
+    }
+
+    // tint and badge of render.icon or one of its conditions
+    fn check_icon_variant(&mut self, base: &str, settings: &Map<String, Value>) {
+        if let Some(tint) = settings.get("tint").and_then(|t| t.as_str()) {
+            self.check_color(&format!("{}/tint", base), tint);
+        }
+        let Some(badge) = settings.get("badge") else {
+            return;
+        };
+        let path = format!("{}/badge", base);
+        match badge.get("color").and_then(|c| c.as_str()) {
+            Some(color) => self.check_color(&format!("{}/color", path), color),
+            None => self.error("icon-variant", &path, "Badge needs a \"color\"".to_string()),
+        }
+        if let Some(text) = badge.get("text").and_then(|t| t.as_str()) {
+            self.check_template(&format!("{}/text", path), text, None);
+        }
+        if let Some(position) = badge.get("position").and_then(|p| p.as_str()) {
+            if !BADGE_POSITIONS.contains(&position) {
+                self.error(
+                    "icon-variant",
+                    &format!("{}/position", path),
+                    format!("Badge position '{}' is not one of {}", position, BADGE_POSITIONS.join(", ")),
+                );
+            }
+        }
+        if let Some(visible) = badge.get("visible").and_then(|v| v.as_str()) {
+            let visible_path = format!("{}/visible", path);
+            for name in self.check_condition(&visible_path, visible) {
+                self.check_property(&visible_path, &name, "Badge visibility");
+            }
+        }
+    }
+
+    fn check_render(&mut self, widget: &Map<String, Value>) {
+        let Some(render) = widget.get("render").and_then(|r| r.as_object()) else {
+            return;
+        };
+
+        // Icon set and the names available in it
+        let icon_set = widget.get("iconSet")
+            .map(|s| ("/iconSet", s))
+            .or_else(|| render.get("icon").and_then(|i| i.get("set")).map(|s| ("/render/icon/set", s)));
+        let icons = match icon_set {
+            // Inherited from another package; checked with that package's widget
+            Some(_) if widget.contains_key("iconPackage") => None,
+            Some((path, set)) => match set.as_str() {
+                Some(set) => match (self.find_icon_set)(set) {
+                    Some(dir) => Some((set.to_string(), icon_names(&dir))),
+                    None => {
+                        self.error("icon-set-missing", path, format!("Icon set folder '{}' not found", set));
+                        None
+                    }
+                },
+                None => None,
+            },
+            None => {
+                self.warning("icon-set-missing", "", "No iconSet; devices need an icon set from their parameters to be visible".to_string());
+                None
+            }
+        };
+
+        if let Some(icon) = render.get("icon").and_then(|i| i.as_object()) {
+            self.check_icon_variant("/render/icon", icon);
+            match icon.get("type").and_then(|t| t.as_str()) {
+                Some("static") => match icon.get("icon").and_then(|i| i.as_str()) {
+                    Some(name) => self.check_icon_name("/render/icon/icon", name, icons.as_ref()),
+                    None => self.error("render-icon", "/render/icon", "Static icon needs an \"icon\" name".to_string()),
+                },
+                Some("conditional") => {
+                    let property = icon.get("property").and_then(|p| p.as_str()).unwrap_or("value").to_string();
+                    self.check_property("/render/icon/property", &property, "render.icon");
+                    let conditions = icon.get("conditions").and_then(|c| c.as_array()).cloned().unwrap_or_default();
+                    if conditions.is_empty() {
+                        self.error("render-icon", "/render/icon", "Conditional icon needs at least one condition".to_string());
+                    }
+                    for (index, condition) in conditions.iter().enumerate() {
+                        let base = format!("/render/icon/conditions/{}", index);
+                        if let Some(when) = condition.get("when").and_then(|w| w.as_str()) {
+                            for name in self.check_condition(&format!("{}/when", base), when) {
+                                // Conditions only see the selected property, as in the webview
+                                if name.split('.').next() != Some(property.as_str()) {
+                                    self.warning(
+                                        "condition",
+                                        &format!("{}/when", base),
+                                        format!("Condition '{}' reads '{}', but icon conditions only see '{}'", when, name, property),
+                                    );
+                                }
+                            }
+                        }
+                        if let Some(name) = condition.get("icon").and_then(|i| i.as_str()) {
+                            self.check_icon_name(&format!("{}/icon", base), name, icons.as_ref());
+                        }
+                        if let Some(condition) = condition.as_object() {
+                            self.check_icon_variant(&base, condition);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(subtext) = render.get("subtext").and_then(|s| s.as_object()) {
+            if let Some(template) = subtext.get("template").and_then(|t| t.as_str()) {
+                self.check_template("/render/subtext/template", template, None);
+            }
+            if let Some(visible) = subtext.get("visible").and_then(|v| v.as_str()) {
+                for name in self.check_condition("/render/subtext/visible", visible) {
+                    self.check_property("/render/subtext/visible", &name, "render.subtext.visible");
+                }
+            }
+        }
+
+        for (key, base) in [("style", "/render/style"), ("svg", "/render/svg/style")] {
+            let styles = match key {
+                "svg" => render.get("svg").and_then(|s| s.get("style")),
+                _ => render.get("style"),
+            };
+            for (property, template) in styles.and_then(|s| s.as_object()).into_iter().flatten() {
+                if let Some(template) = template.as_str() {
+                    self.check_template(&format!("{}/{}", base, escape_pointer(property)), template, None);
+                }
+            }
+        }
+    }
+
+    // Action bodies may use any parameter name, so only the ${...} syntax is checked
+    fn check_template_syntax(&mut self, path: &str, value: &Value) {
+        match value {
+            Value::String(template) => {
+                let mut rest = template.as_str();
+                while let Some(start) = rest.find("${") {
+                    let after = &rest[start + 2..];
+                    let Some(end) = after.find('}') else {
+                        self.error("template", path, format!("Unterminated ${{ in '{}'", template));
+                        return;
+                    };
+                    if let Err(e) = check_balanced(&after[..end]) {
+                        self.error("template", path, format!("Invalid expression '${{{}}}': {}", &after[..end], e));
+                    }
+                    rest = &after[end + 1..];
+                }
+            }
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.check_template_syntax(&format!("{}/{}", path, index), item);
+                }
+            }
+            Value::Object(map) => {
+                for (key, item) in map {
+                    self.check_template_syntax(&format!("{}/{}", path, escape_pointer(key)), item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn check_actions(&mut self, widget: &Map<String, Value>) {
+        let Some(actions) = widget.get("actions").and_then(|a| a.as_object()) else {
+            return;
+        };
+        for (name, action) in actions {
+            let base = format!("/actions/{}", escape_pointer(name));
+            if let Some(method) = action.get("method").and_then(|m| m.as_str()) {
+                if !HTTP_METHODS.contains(&method.to_uppercase().as_str()) {
+                    self.error("action", &format!("{}/method", base), format!("Unsupported HTTP method '{}'", method));
+                }
+            }
+            if let Some(api) = action.get("api").and_then(|a| a.as_str()) {
+                if !api.starts_with('/') {
+                    self.error("action", &format!("{}/api", base), format!("Action api '{}' must start with '/'", api));
+                }
+            }
+            if let Some(api) = action.get("api") {
+                self.check_template_syntax(&format!("{}/api", base), api);
+            }
+            if let Some(body) = action.get("body") {
+                self.check_template_syntax(&format!("{}/body", base), body);
+            }
+        }
+    }
+
+    fn check_action_ref(&mut self, path: &str, element: &Value) {
+        match element.get("action").and_then(|a| a.as_str()) {
+            Some(action) if !self.actions.contains(action) => {
+                self.error("unknown-action", &format!("{}/action", path), format!("Action '{}' is not defined in actions", action));
+            }
+            Some(_) => {}
+            None => self.error("ui", path, "This element needs an \"action\"".to_string()),
+        }
+    }
+
+    fn check_slider(&mut self, path: &str, element: &Value) {
+        match element.get("property").and_then(|p| p.as_str()) {
+            Some(property) => self.check_property(&format!("{}/property", path), property, "Slider"),
+            None => self.error("ui", path, "Slider needs a \"property\"".to_string()),
+        }
+        let min = element.get("min").and_then(|m| m.as_f64());
+        let max = element.get("max").and_then(|m| m.as_f64());
+        if let (Some(min), Some(max)) = (min, max) {
+            if min >= max {
+                self.error("ui", &format!("{}/min", path), format!("Slider min ({}) must be below max ({})", min, max));
+            }
+        }
+        self.check_action_ref(path, element);
+    }
+
+    fn check_ui(&mut self, widget: &Map<String, Value>) {
+        let Some(ui) = widget.get("ui").and_then(|u| u.as_object()) else {
+            return;
+        };
+
+        if let Some(rows) = ui.get("rows").and_then(|r| r.as_array()) {
+            for (row_index, row) in rows.iter().enumerate() {
+                let elements = row.get("elements").and_then(|e| e.as_array()).cloned().unwrap_or_default();
+                for (index, element) in elements.iter().enumerate() {
+                    let path = format!("/ui/rows/{}/elements/{}", row_index, index);
+                    match element.get("type").and_then(|t| t.as_str()) {
+                        Some("button") => self.check_action_ref(&path, element),
+                        Some("slider") => self.check_slider(&path, element),
+                        Some("colorSelect") => {
+                            match element.get("property").and_then(|p| p.as_str()) {
+                                Some(property) => self.check_property(&format!("{}/property", path), property, "colorSelect"),
+                                None => self.error("ui", &path, "colorSelect needs a \"property\"".to_string()),
+                            }
+                            self.check_action_ref(&path, element);
+                        }
+                        Some("label") => {
+                            if let Some(text) = element.get("text").and_then(|t| t.as_str()) {
+                                self.check_template(&format!("{}/text", path), text, None);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            return;
+        }
+
+        match ui.get("type").and_then(|t| t.as_str()) {
+            Some("slider") => self.check_slider("/ui", &Value::Object(ui.clone())),
+            Some("buttons") => {
+                let buttons = ui.get("buttons").and_then(|b| b.as_array()).cloned().unwrap_or_default();
+                if buttons.is_empty() {
+                    self.error("ui", "/ui", "Buttons UI needs a non-empty \"buttons\" list".to_string());
+                }
+                for (index, button) in buttons.iter().enumerate() {
+                    self.check_action_ref(&format!("/ui/buttons/{}", index), button);
+                }
+            }
+            Some("toggle") => self.warning("ui", "/ui/type", "The toggle UI type is not implemented yet; clicking does nothing".to_string()),
+            Some(other) => self.error("ui", "/ui/type", format!("Unknown ui type '{}' (expected rows, \"slider\" or \"buttons\")", other)),
+            None => self.error("ui", "/ui", "ui needs either \"rows\" or a \"type\"".to_string()),
+        }
+    }
+}
+
+// Brackets, parentheses and quotes must pair up
+fn check_balanced(text: &str) -> Result<(), String> {
+    let mut stack = Vec::new();
+    let mut quote: Option<char> = None;
+    for c in text.chars() {
+        if let Some(q) = quote {
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' => quote = Some(c),
+            '(' | '[' => stack.push(c),
+            ')' | ']' => {
+                let open = if c == ')' { '(' } else { '[' };
+                if stack.pop() != Some(open) {
+                    return Err(format!("unmatched '{}'", c));
+                }
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated string".to_string());
+    }
+    match stack.pop() {
+        Some(open) => Err(format!("unclosed '{}'", open)),
+        None => Ok(()),
+    }
+}
+
+// Property paths read by a template expression (which may use arithmetic and ?: like the
+// webview) and the non-Math functions it calls
+fn template_identifiers(expression: &str) -> (Vec<String>, Vec<String>) {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut names = Vec::new();
+    let mut calls = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' || c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += 1;
+            }
+            i += 1;
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect::<String>().trim_end_matches('.').to_string();
+            let is_call = chars[i..].iter().find(|c| !c.is_whitespace()) == Some(&'(');
+            if is_call {
+                if !name.starts_with("Math.") && !calls.contains(&name) {
+                    calls.push(name);
+                }
+            } else if !matches!(name.as_str(), "true" | "false" | "null" | "undefined" | "Math") && !name.starts_with("Math.") && !names.contains(&name) {
+                names.push(name);
+            }
+        } else {
+            i += 1;
+        }
+    }
+    (names, calls)
+}
+
+// Icon names (file names without extension) in an icon set folder
+fn icon_names(dir: &Path) -> BTreeSet<String> {
+    fs::read_dir(dir)
+        .map(|entries| entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| ICON_EXTENSIONS.contains(&e.to_lowercase().as_str())))
+            .filter_map(|path| path.file_stem().and_then(|s| s.to_str()).map(|s| s.to_string()))
+            .collect())
+        .unwrap_or_default()
+}
+
+fn object_keys(widget: &Map<String, Value>, key: &str) -> BTreeSet<String> {
+    widget.get(key)
+        .and_then(|v| v.as_object())
+        .map(|map| map.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+// Flattens a definition that uses extends or fragments
+pub type Compose<'a> = &'a dyn Fn(&Value) -> Result<Composition, String>;
+
+fn check_widget(lint: &mut Lint, document: &Value) {
+    let Some(widget) = document.as_object() else {
+        lint.error("schema", "", "A widget definition must be a JSON object".to_string());
+        return;
+    };
+
+    match schemas::validate("widget", document) {
+        Ok(errors) => {
+            for error in errors {
+                lint.error("schema", &error.path, error.message);
+            }
+        }
+        Err(e) => println!("Skipping widget schema check: {}", e),
+    }
+    for key in widget.keys().filter(|key| !KNOWN_KEYS.contains(&key.as_str())) {
+        lint.warning("unknown-key", &format!("/{}", escape_pointer(key)), format!("'{}' is not part of the widget format and is ignored", key));
+    }
+
+    lint.properties.extend(object_keys(widget, "state"));
+    lint.properties.extend(object_keys(widget, "getters"));
+    for event in widget.get("events").and_then(|e| e.as_object()).into_iter().flat_map(|e| e.values()) {
+        lint.properties.extend(event.get("updates").and_then(|u| u.as_object()).into_iter().flat_map(|u| u.keys().cloned()));
+    }
+    lint.actions = object_keys(widget, "actions");
+
+    lint.check_version(widget);
+    lint.check_computed(widget);
+    lint.check_getters(widget);
+    lint.check_events(widget);
+    lint.check_render(widget);
+    lint.check_actions(widget);
+    lint.check_ui(widget);
+}
+
+// Lint widget source text. `compose` flattens extends and fragments (without
+// it, checks that need the inherited parts are skipped); `find_icon_set` maps
+// an iconSet name to its folder.
+pub fn lint_source(source: &str, compose: Option<Compose>, find_icon_set: &dyn Fn(&str) -> Option<PathBuf>) -> Vec<WidgetDiagnostic> {
+    let document: Value = match serde_json::from_str(source) {
+        Ok(document) => document,
+        Err(e) => {
+            return vec![WidgetDiagnostic {
+                severity: Severity::Error,
+                code: "invalid-json".to_string(),
+                path: String::new(),
+                line: e.line().max(1),
+                column: e.column().max(1),
+                message: format!("Invalid JSON: {}", e),
+            }];
+        }
+    };
+
+    let mut lint = Lint {
+        positions: value_positions(source),
+        diagnostics: Vec::new(),
+        properties: BTreeSet::new(),
+        actions: BTreeSet::new(),
+        find_icon_set,
+    };
+
+    if !widget_compose::is_composed(&document) {
+        check_widget(&mut lint, &document);
+        lint.diagnostics.sort_by_key(|d| (d.line, d.column));
+        return lint.diagnostics;
+    }
+
+    let reference = if document.get("extends").is_some() { "/extends" } else { "/fragments" };
+    let composition = match compose.map(|compose| compose(&document)) {
+        Some(Ok(composition)) => Some(composition),
+        Some(Err(e)) => {
+            lint.error("compose", reference, e);
+            None
+        }
+        None => None,
+    };
+
+    let mut diagnostics = std::mem::take(&mut lint.diagnostics);
+    match composition {
+        Some(composition) => {
+            let fragments = if document.get("fragments").is_some() { "/fragments" } else { reference };
+            for conflict in &composition.conflicts {
+                lint.warning(
+                    "compose-conflict",
+                    fragments,
+                    format!("{} replaces {} from {}", conflict.kept, conflict.path, conflict.previous),
+                );
+            }
+            check_widget(&mut lint, &composition.definition);
+            // Values that come from the base or fragments are reported in their own files
+            let positions = &lint.positions;
+            diagnostics.extend(lint.diagnostics.into_iter().filter(|d| d.path.is_empty() || d.code.starts_with("compose") || positions.contains_key(&d.path)));
+        }
+        None => {
+            check_widget(&mut lint, &document);
+            // Unknown references and missing widget-level keys may be inherited
+            let needs_context = |d: &WidgetDiagnostic| {
+                CONTEXT_CODES.contains(&d.code.as_str()) || (d.path.is_empty() && d.severity == Severity::Warning)
+            };
+            diagnostics.extend(lint.diagnostics.into_iter().filter(|d| !needs_context(d)));
+        }
+    }
+    diagnostics.sort_by_key(|d| (d.line, d.column));
+    diagnostics
+}
+
+pub fn report(widget: &str, file: &Path, diagnostics: Vec<WidgetDiagnostic>) -> WidgetLintReport {
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    WidgetLintReport {
+        widget: widget.to_string(),
+        file: file.to_string_lossy().to_string(),
+        errors,
+        warnings: diagnostics.len() - errors,
+        diagnostics,
+    }
+}
+
+// Lint a widget file on disk
+pub fn lint_file(widget: &str, file: &Path, compose: Option<Compose>, find_icon_set: &dyn Fn(&str) -> Option<PathBuf>) -> WidgetLintReport {
+    let diagnostics = match fs::read_to_string(file) {
+        Ok(source) => lint_source(&source, compose, find_icon_set),
+        Err(e) => vec![WidgetDiagnostic {
+            severity: Severity::Error,
+            code: "unreadable".to_string(),
+            path: String::new(),
+            line: 1,
+            column: 1,
+            message: format!("Failed to read widget file: {}", e),
+        }],
+    };
+    report(widget, file, diagnostics)
+}
+
+// One line per diagnostic, for logs
+pub fn log_report(report: &WidgetLintReport) {
+    for d in &report.diagnostics {
+        println!("{:?} {}:{}:{} [{}] {}", d.severity, report.file, d.line, d.column, d.code, d.message);
+    }
+}
@@ -0,0 +1,206 @@
+// Widget resolution
+// Finds the widget definition for a device the way WIDGET_PACKAGES.md describes:
+//   1. explicit "package/widget" reference (device.widget in config.json)
+//   2. widget-mappings.json override for the device type
+//   3. built-in widget named after the device type (then the legacy widgets/ root)
+//   4. first installed package (in package id order) that lists the device type
+//      and provides a widget whose "type" matches
+// A reference or mapping that points at a missing file falls through to the
+// next step instead of failing. Definitions using extends or fragments are
+// flattened by widget_compose; one that can't be composed counts as missing.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::floor_export::BUILT_IN_PACKAGE;
+use crate::widget_compose::{self, Conflict};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WidgetSource {
+    Explicit,
+    Mapping,
+    BuiltIn,
+    Legacy,
+    Package,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedWidget {
+    pub definition: Value,
+    pub package: String,
+    pub widget: String,
+    pub source: WidgetSource,
+    pub file: String,
+    // Composition layers in merge order ("widget" alone when nothing is inherited)
+    pub layers: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    let content = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            println!("Skipping widget {:?}: invalid JSON: {}", path, e);
+            None
+        }
+    }
+}
+
+// "package/widget" or "package:widget"
+pub fn split_reference(reference: &str) -> Option<(&str, &str)> {
+    reference
+        .split_once('/')
+        .or_else(|| reference.split_once(':'))
+        .filter(|(package, widget)| !package.is_empty() && !widget.is_empty())
+}
+
+fn load(data_path: &Path, file: PathBuf, package: &str, widget: &str, source: WidgetSource) -> Option<ResolvedWidget> {
+    let definition = read_json(&file)?;
+    let composition = match widget_compose::compose(data_path, package, Some(&file), &definition) {
+        Ok(composition) => composition,
+        Err(e) => {
+            println!("Skipping widget {:?}: {}", file, e);
+            return None;
+        }
+    };
+    for conflict in &composition.conflicts {
+        println!("Widget {:?}: {} replaces {} from {}", file, conflict.kept, conflict.path, conflict.previous);
+    }
+    Some(ResolvedWidget {
+        definition: composition.definition,
+        package: package.to_string(),
+        widget: widget.to_string(),
+        source,
+        file: file.to_string_lossy().to_string(),
+        layers: composition.layers,
+        conflicts: composition.conflicts,
+    })
+}
+
+fn package_widget(data_path: &Path, package: &str, widget: &str, source: WidgetSource) -> Option<ResolvedWidget> {
+    let file = data_path.join("widgets").join("packages").join(package).join(format!("{}.json", widget));
+    load(data_path, file, package, widget, source)
+}
+
+// Built-in widget by name, falling back to the legacy widgets/ root
+pub fn built_in_widget(data_path: &Path, widget: &str) -> Option<ResolvedWidget> {
+    let widgets_dir = data_path.join("widgets");
+    let file_name = format!("{}.json", widget);
+    load(data_path, widgets_dir.join("built-in").join(&file_name), BUILT_IN_PACKAGE, widget, WidgetSource::BuiltIn)
+        .or_else(|| load(data_path, widgets_dir.join(&file_name), BUILT_IN_PACKAGE, widget, WidgetSource::Legacy))
+}
+
+fn mapped_widget(data_path: &Path, device_type: &str) -> Option<ResolvedWidget> {
+    let mappings = read_json(&data_path.join("widget-mappings.json"))?;
+    let mapping = mappings.get("mappings")?.get(device_type)?;
+    let package = mapping.get("package").and_then(|p| p.as_str())?;
+    let widget = mapping.get("widget").and_then(|w| w.as_str()).unwrap_or(device_type);
+    let resolved = package_widget(data_path, package, widget, WidgetSource::Mapping);
+    if resolved.is_none() {
+        println!("Widget mapping for {} points to missing widget {}/{}", device_type, package, widget);
+    }
+    resolved
+}
+
+fn installed_package_widget(data_path: &Path, device_type: &str) -> Option<ResolvedWidget> {
+    let installed = read_json(&data_path.join("installed-packages.json"))?;
+    let packages = installed.get("packages")?.as_object()?;
+    for (package, info) in packages {
+        let manifest = info.get("manifest");
+        let supports_type = manifest
+            .and_then(|m| m.get("deviceTypes"))
+            .and_then(|t| t.as_array())
+            .is_some_and(|types| types.iter().any(|t| t.as_str() == Some(device_type)));
+        if !supports_type {
+            continue;
+        }
+        let widgets = manifest
+            .and_then(|m| m.get("provides"))
+            .and_then(|p| p.get("widgets"))
+            .and_then(|w| w.as_array());
+        for widget in widgets.into_iter().flatten().filter_map(|w| w.as_str()) {
+            if let Some(resolved) = package_widget(data_path, package, widget, WidgetSource::Package) {
+                if resolved.definition.get("type").and_then(|t| t.as_str()) == Some(device_type) {
+                    return Some(resolved);
+                }
+            }
+        }
+    }
+    None
+}
+
+// Resolve the widget for a device type, honouring an explicit "package/widget"
+// reference first when one is given
+pub fn resolve(data_path: &Path, device_type: Option<&str>, explicit: Option<&str>) -> Option<ResolvedWidget> {
+    if let Some((package, widget)) = explicit.and_then(split_reference) {
+        if let Some(resolved) = package_widget(data_path, package, widget, WidgetSource::Explicit) {
+            return Some(resolved);
+        }
+        println!("Widget reference {} not found, resolving by device type", explicit.unwrap_or_default());
+    }
+
+    let device_type = device_type?;
+    mapped_widget(data_path, device_type)
+        .or_else(|| built_in_widget(data_path, device_type))
+        .or_else(|| installed_package_widget(data_path, device_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("widget-resolve-test-{}-{}-{}", name, std::process::id(), chrono::Local::now().format("%H%M%S%f")));
+        fs::create_dir_all(dir.join("widgets").join("built-in")).unwrap();
+        dir
+    }
+
+    fn write_widget(file: PathBuf, label: &str) {
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, json!({ "type": "com.fibaro.binarySwitch", "label": label }).to_string()).unwrap();
+    }
+
+    fn label(resolved: &ResolvedWidget) -> &str {
+        resolved.definition["label"].as_str().unwrap()
+    }
+
+    #[test]
+    fn user_mapping_overrides_built_in() {
+        let data = data_dir("mapping");
+        write_widget(data.join("widgets/built-in/com.fibaro.binarySwitch.json"), "built-in");
+        write_widget(data.join("widgets/packages/com.user/lamp.json"), "user");
+
+        let resolved = resolve(&data, Some("com.fibaro.binarySwitch"), None).unwrap();
+        assert_eq!((resolved.source, label(&resolved)), (WidgetSource::BuiltIn, "built-in"));
+
+        let mappings = json!({ "mappings": { "com.fibaro.binarySwitch": { "package": "com.user", "widget": "lamp" } } });
+        fs::write(data.join("widget-mappings.json"), mappings.to_string()).unwrap();
+        let resolved = resolve(&data, Some("com.fibaro.binarySwitch"), None).unwrap();
+        assert_eq!((resolved.source, label(&resolved)), (WidgetSource::Mapping, "user"));
+        assert_eq!(resolved.package, "com.user");
+
+        fs::remove_dir_all(&data).unwrap();
+    }
+
+    #[test]
+    fn missing_widget_falls_through_or_resolves_to_none() {
+        let data = data_dir("missing");
+        write_widget(data.join("widgets/built-in/com.fibaro.binarySwitch.json"), "built-in");
+
+        // A reference or mapping to a missing widget falls back to the built-in
+        let mappings = json!({ "mappings": { "com.fibaro.binarySwitch": { "package": "com.user", "widget": "gone" } } });
+        fs::write(data.join("widget-mappings.json"), mappings.to_string()).unwrap();
+        let resolved = resolve(&data, Some("com.fibaro.binarySwitch"), Some("com.user/gone")).unwrap();
+        assert_eq!((resolved.source, label(&resolved)), (WidgetSource::BuiltIn, "built-in"));
+
+        assert!(resolve(&data, Some("com.fibaro.unknownDevice"), None).is_none());
+        assert!(resolve(&data, None, Some("com.user/gone")).is_none());
+
+        fs::remove_dir_all(&data).unwrap();
+    }
+}
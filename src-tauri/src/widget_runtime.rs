@@ -0,0 +1,661 @@
+// Widget runtime
+// Computes what a device looks like from its widget definition and state, and
+// keeps that state up to date from getter responses and HC3 events, following
+// the webview (widgetManager.renderDevice, hc3ApiManager getters and
+// eventManager.dispatchEvent), except that events.<type>.match is honoured:
+//   - render.icon: "static" gives icon; "conditional" evaluates the conditions in
+//     order against { property: state[property] } and takes the first match.
+//     tint and badge on the matching condition (or on render.icon) describe an
+//     icon variant (icon_variants)
+//   - render.subtext: the template with ${...} substituted, shown when there is
+//     no visible condition or it holds
+//   - render.style and render.svg.style: every value is a template
+//   - computed: derived properties (widget_computed) are recomputed whenever
+//     the state changes
+//   - events.<type>.match: a JSONPath over the event, with ${id} substituted,
+//     that has to select at least one node; events.<type>.updates maps state
+//     properties to value paths (see apply_event). The webview doesn't
+//     evaluate match; it only checks the event type and data.id
+// Expressions and templates are evaluated by widget_eval.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::floor_export::id_string;
+use crate::icon_variants::{Badge, IconVariant};
+use crate::widget_computed;
+use crate::widget_eval;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderOutput {
+    // Icon name within the icon set, if render.icon selects one
+    pub icon: Option<String>,
+    // Substituted subtext template; None when the widget has no subtext
+    pub subtext: Option<String>,
+    pub subtext_visible: bool,
+    // CSS properties for the icon element
+    pub style: Map<String, Value>,
+    // CSS properties for the render.svg.selector element of inline SVG icons
+    pub svg_style: Map<String, Value>,
+    // Tint and badge to apply to the icon, if any
+    pub variant: Option<IconVariant>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Evaluation {
+    pub state: Map<String, Value>,
+    // State properties set by the events, in the order they were first set
+    pub updated: Vec<String>,
+    pub render: RenderOutput,
+}
+
+// The render.icon rule that applies: render.icon itself for static icons, the
+// first matching condition for conditional ones
+fn icon_rule<'a>(widget: &'a Value, state: &Map<String, Value>) -> Option<&'a Value> {
+    let icon = widget.get("render")?.get("icon")?;
+    match icon.get("type").and_then(|t| t.as_str()) {
+        Some("static") => Some(icon),
+        Some("conditional") => {
+            // Conditions are evaluated against the selected property only, as in the
+            // webview; when the state lacks it, no condition holds
+            let property = icon.get("property").and_then(|p| p.as_str()).unwrap_or("value");
+            let mut context = Map::new();
+            if let Some(value) = state.get(property) {
+                context.insert(property.to_string(), value.clone());
+            }
+            icon.get("conditions")?.as_array()?.iter()
+                .find(|condition| condition.get("when")
+                    .and_then(|w| w.as_str())
+                    .map(|when| widget_eval::evaluate_condition(when, &context))
+                    .unwrap_or(false))
+        }
+        _ => None,
+    }
+}
+
+// Icon name selected by render.icon for the given state
+pub fn icon_name(widget: &Value, state: &Map<String, Value>) -> Option<String> {
+    icon_rule(widget, state)?.get("icon")?.as_str().map(|s| s.to_string())
+}
+
+// Tint and badge for the selected icon. The matching condition's settings win
+// over those on render.icon; tint and the badge's color and text are
+// templates, and a badge whose visible condition fails is left out.
+pub fn icon_variant(widget: &Value, state: &Map<String, Value>) -> Option<IconVariant> {
+    let icon = widget.get("render")?.get("icon")?;
+    let rule = icon_rule(widget, state)?;
+    let setting = |key: &str| rule.get(key).or_else(|| icon.get(key));
+    let template = |value: Option<&Value>| value
+        .and_then(|v| v.as_str())
+        .map(|t| widget_eval::evaluate_template(t, state));
+
+    let badge = setting("badge")
+        .filter(|badge| match badge.get("visible").and_then(|v| v.as_str()) {
+            Some(condition) => widget_eval::evaluate_condition(condition, state),
+            None => true,
+        })
+        .and_then(|badge| Some(Badge {
+            color: template(badge.get("color"))?,
+            text: template(badge.get("text")),
+            position: badge.get("position").and_then(|p| p.as_str()).map(|s| s.to_string()),
+        }));
+    let variant = IconVariant {
+        tint: template(setting("tint")),
+        badge,
+    };
+    (!variant.is_empty()).then_some(variant)
+}
+
+fn styles(definition: Option<&Value>, state: &Map<String, Value>) -> Map<String, Value> {
+    definition
+        .and_then(|s| s.as_object())
+        .map(|style| style.iter()
+            .filter_map(|(property, template)| {
+                let template = template.as_str()?;
+                Some((property.clone(), Value::String(widget_eval::evaluate_template(template, state))))
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+// Evaluate a widget's render section against a device state
+pub fn render(widget: &Value, state: &Map<String, Value>) -> RenderOutput {
+    let Some(render) = widget.get("render") else {
+        return RenderOutput::default();
+    };
+
+    let subtext = render.get("subtext");
+    let subtext_visible = subtext.is_some_and(|subtext| match subtext.get("visible").and_then(|v| v.as_str()) {
+        Some(condition) => widget_eval::evaluate_condition(condition, state),
+        None => true,
+    });
+
+    RenderOutput {
+        icon: icon_name(widget, state),
+        subtext: subtext
+            .and_then(|s| s.get("template"))
+            .and_then(|t| t.as_str())
+            .map(|template| widget_eval::evaluate_template(template, state)),
+        subtext_visible,
+        style: styles(render.get("style"), state),
+        svg_style: styles(render.get("svg").and_then(|svg| svg.get("style")), state),
+        variant: icon_variant(widget, state),
+    }
+}
+
+// Values from HC3 are stored the way the webview stores them: {value: X}
+// wrappers are unwrapped, and a colorComponents string "R,G,B,WW,CW" becomes an
+// object (parts that aren't numbers count as 0)
+fn normalize(property: &str, value: Value) -> Value {
+    let value = match value {
+        Value::Object(mut map) if map.contains_key("value") => map.remove("value").unwrap_or(Value::Null),
+        other => other,
+    };
+    match value {
+        Value::String(color) if property == "colorComponents" => {
+            let parts: Vec<i64> = color.split(',').map(parse_int).collect();
+            let part = |i: usize| Value::from(parts.get(i).copied().unwrap_or(0));
+            let mut components = Map::new();
+            for (i, name) in ["red", "green", "blue", "warmWhite", "coldWhite"].into_iter().enumerate() {
+                components.insert(name.to_string(), part(i));
+            }
+            Value::Object(components)
+        }
+        other => other,
+    }
+}
+
+// parseInt(): leading digits after optional whitespace and sign, 0 if none
+fn parse_int(text: &str) -> i64 {
+    let text = text.trim();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    digits[..end].parse::<i64>().map(|n| sign * n).unwrap_or(0)
+}
+
+fn dotted<'a>(root: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(root, |current, key| match current {
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => current.get(key),
+    })
+}
+
+// Store a getter response: `path` is the getter's dotted path into the response
+pub fn apply_getter(state: &mut Map<String, Value>, property: &str, path: &str, response: &Value) {
+    let value = dotted(response, path).cloned().unwrap_or(Value::Null);
+    state.insert(property.to_string(), normalize(property, value));
+}
+
+// A parsed JSONPath step
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Child(String),
+    Index(i64),
+    Wildcard,
+    Descendants,
+    Filter(String),
+}
+
+fn parse_json_path(path: &str) -> Result<Vec<Step>, String> {
+    let chars: Vec<char> = path.trim().chars().collect();
+    if chars.first() != Some(&'$') {
+        return Err(format!("JSONPath '{}' must start with $", path));
+    }
+    let name_end = |from: usize| {
+        let mut i = from;
+        while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+            i += 1;
+        }
+        i
+    };
+
+    let mut steps = Vec::new();
+    let mut i = 1;
+    while i < chars.len() {
+        if chars[i] == '.' {
+            if chars.get(i + 1) == Some(&'.') {
+                steps.push(Step::Descendants);
+                i += 2;
+            } else {
+                i += 1;
+            }
+            if i < chars.len() && chars[i] == '*' {
+                steps.push(Step::Wildcard);
+                i += 1;
+            } else if i < chars.len() && chars[i] != '[' {
+                let end = name_end(i);
+                if end == i {
+                    return Err(format!("Expected a property name at position {} of '{}'", i, path));
+                }
+                steps.push(Step::Child(chars[i..end].iter().collect()));
+                i = end;
+            }
+            continue;
+        }
+        if chars[i] != '[' {
+            return Err(format!("Unexpected '{}' at position {} of '{}'", chars[i], i, path));
+        }
+
+        // Bracket: find the closing ']' outside quotes and parentheses
+        let mut depth = 0;
+        let mut quote = None;
+        let mut end = i + 1;
+        while end < chars.len() {
+            match (quote, chars[end]) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(chars[end]),
+                (None, '(') => depth += 1,
+                (None, ')') => depth -= 1,
+                (None, ']') if depth == 0 => break,
+                _ => {}
+            }
+            end += 1;
+        }
+        if end >= chars.len() {
+            return Err(format!("Missing ']' in '{}'", path));
+        }
+        let inner: String = chars[i + 1..end].iter().collect();
+        let inner = inner.trim();
+        let step = if let Some(filter) = inner.strip_prefix("?(").and_then(|f| f.strip_suffix(')')) {
+            Step::Filter(filter.to_string())
+        } else if inner == "*" {
+            Step::Wildcard
+        } else if inner.len() >= 2 && (inner.starts_with('\'') && inner.ends_with('\'') || inner.starts_with('"') && inner.ends_with('"')) {
+            Step::Child(inner[1..inner.len() - 1].to_string())
+        } else if let Ok(index) = inner.parse::<i64>() {
+            Step::Index(index)
+        } else {
+            return Err(format!("Unsupported selector [{}] in '{}'", inner, path));
+        };
+        steps.push(step);
+        i = end + 1;
+    }
+    Ok(steps)
+}
+
+fn children(node: &Value) -> Vec<&Value> {
+    match node {
+        Value::Object(map) => map.values().collect(),
+        Value::Array(items) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn with_descendants<'a>(node: &'a Value, out: &mut Vec<&'a Value>) {
+    out.push(node);
+    for child in children(node) {
+        with_descendants(child, out);
+    }
+}
+
+// Nodes of `root` selected by a JSONPath. Supported: $, .name, ['name'], [n]
+// (negative counts from the end), .* and [*], .. (the node and all its
+// descendants) and [?(filter)], where the filter is a widget expression with
+// @ bound to each child.
+pub fn select<'a>(root: &'a Value, path: &str) -> Result<Vec<&'a Value>, String> {
+    let mut nodes = vec![root];
+    for step in parse_json_path(path)? {
+        let mut next = Vec::new();
+        for node in nodes {
+            match &step {
+                Step::Child(name) => next.extend(node.get(name.as_str())),
+                Step::Index(index) => {
+                    if let Value::Array(items) = node {
+                        let position = if *index < 0 { items.len() as i64 + index } else { *index };
+                        next.extend(usize::try_from(position).ok().and_then(|p| items.get(p)));
+                    }
+                }
+                Step::Wildcard => next.extend(children(node)),
+                Step::Descendants => with_descendants(node, &mut next),
+                Step::Filter(filter) => {
+                    for child in children(node) {
+                        let mut scope = Map::new();
+                        scope.insert("@".to_string(), child.clone());
+                        if widget_eval::truthy(&widget_eval::evaluate(filter, &scope)?) {
+                            next.push(child);
+                        }
+                    }
+                }
+            }
+        }
+        nodes = next;
+    }
+    Ok(nodes)
+}
+
+// Whether an HC3 event is for this device and widget: the widget handles the
+// event type, data.id is the device and the match path (if any) selects a node
+pub fn event_matches(widget: &Value, device_id: &str, event: &Value) -> bool {
+    let Some(event_type) = event.get("type").and_then(|t| t.as_str()) else {
+        return false;
+    };
+    let Some(definition) = widget.get("events").and_then(|e| e.get(event_type)) else {
+        return false;
+    };
+    let id = event.get("data").and_then(|d| d.get("id"));
+    if id.map(id_string).as_deref() != Some(device_id) {
+        return false;
+    }
+    let Some(path) = definition.get("match").and_then(|m| m.as_str()) else {
+        return true;
+    };
+    match select(event, &path.replace("${id}", device_id)) {
+        Ok(nodes) => !nodes.is_empty(),
+        Err(e) => {
+            println!("Failed to evaluate event match '{}': {}", path, e);
+            false
+        }
+    }
+}
+
+fn word(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// "prop == event.property" (spaces optional) -> prop
+fn property_test(text: &str) -> Option<&str> {
+    let (property, rest) = text.split_once("==")?;
+    let property = property.trim_end();
+    let property = &property[property.rfind(|c: char| !(c.is_alphanumeric() || c == '_')).map(|i| i + 1).unwrap_or(0)..];
+    (word(property) && rest.trim_start().starts_with("event.property")).then_some(property)
+}
+
+// The value path of an update, or None when its condition doesn't hold for
+// `event_property`. Conditions take the forms the webview understands:
+//   "prop == event.property ? path"
+//   "(a == event.property || b == event.property) ? path"
+fn update_path<'a>(update: &'a str, event_property: Option<&str>) -> Option<&'a str> {
+    if !update.contains("==") {
+        return Some(update);
+    }
+    if let Some((condition, path)) = update.split_once('?') {
+        let condition = condition.trim();
+        if let Some(property) = property_test(condition).filter(|p| condition.starts_with(p)) {
+            return (event_property == Some(property)).then_some(path.trim_start());
+        }
+        if let Some(inner) = condition.strip_prefix('(').and_then(|c| c.strip_suffix(')')) {
+            let matched = inner.split("||").filter_map(property_test).any(|p| event_property == Some(p));
+            return matched.then_some(path.trim_start());
+        }
+    }
+    Some(update)
+}
+
+// Apply an HC3 event to a device's state as the widget's events.<type>.updates
+// describe, and return the state properties that were set. Nothing happens when
+// the event doesn't match (see event_matches), or when it is a
+// DevicePropertyUpdatedEvent for a property that is neither in the state nor
+// mentioned in any update. Each value path is either "event.<key>" (a key of
+// event.data) or a dotted path from the event itself; a missing value is
+// stored as null.
+pub fn apply_event(widget: &Value, device_id: &str, state: &mut Map<String, Value>, event: &Value) -> Vec<String> {
+    if !event_matches(widget, device_id, event) {
+        return Vec::new();
+    }
+    let event_type = event.get("type").and_then(|t| t.as_str()).unwrap_or_default();
+    let Some(updates) = widget.get("events")
+        .and_then(|e| e.get(event_type))
+        .and_then(|d| d.get("updates"))
+        .and_then(|u| u.as_object()) else {
+        return Vec::new();
+    };
+    let data = event.get("data").unwrap_or(&Value::Null);
+
+    let event_property = if event_type == "DevicePropertyUpdatedEvent" {
+        data.get("property").and_then(|p| p.as_str())
+    } else {
+        None
+    };
+    if let Some(property) = event_property {
+        let tracked = state.contains_key(property)
+            || updates.values().any(|u| u.as_str().is_some_and(|u| u.contains(property)));
+        if !tracked {
+            return Vec::new();
+        }
+    }
+
+    let mut updated = Vec::new();
+    for (property, update) in updates {
+        let Some(path) = update.as_str().and_then(|u| update_path(u, event_property)) else {
+            continue;
+        };
+        let path = path.trim_end();
+        let value = match path.strip_prefix("event.") {
+            Some(key) => data.get(key),
+            None => dotted(event, path),
+        };
+        state.insert(property.clone(), normalize(property, value.cloned().unwrap_or(Value::Null)));
+        updated.push(property.clone());
+    }
+    updated
+}
+
+// Evaluate a device the way the webview shows it: the widget's default state
+// overlaid with `state`, `events` applied in order, computed properties
+// added, then rendered
+pub fn evaluate(widget: &Value, device_id: &str, state: &Map<String, Value>, events: &[Value]) -> Evaluation {
+    let mut current = widget.get("state").and_then(|s| s.as_object()).cloned().unwrap_or_default();
+    current.extend(state.clone());
+    let mut updated: Vec<String> = Vec::new();
+    for event in events {
+        for property in apply_event(widget, device_id, &mut current, event) {
+            if !updated.contains(&property) {
+                updated.push(property);
+            }
+        }
+    }
+    widget_computed::apply(widget, &mut current);
+    Evaluation {
+        render: render(widget, &current),
+        state: current,
+        updated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state(value: Value) -> Map<String, Value> {
+        value.as_object().cloned().unwrap()
+    }
+
+    fn light() -> Value {
+        json!({
+            "state": { "value": 0, "colorComponents": { "red": 0, "green": 0, "blue": 0 } },
+            "events": {
+                "DevicePropertyUpdatedEvent": {
+                    "match": "$..[?(@.id == ${id} && (@.property == 'value' || @.property == 'colorComponents' || @.property == 'color'))]",
+                    "updates": {
+                        "value": "value == event.property ? event.newValue",
+                        "colorComponents": "(colorComponents == event.property || color == event.property) ? event.newValue"
+                    }
+                }
+            },
+            "render": {
+                "icon": {
+                    "type": "conditional",
+                    "property": "value",
+                    "conditions": [
+                        { "when": "value == 0", "icon": "off" },
+                        { "when": "value < 99", "icon": "half" },
+                        { "when": "value >= 99", "icon": "on" }
+                    ]
+                },
+                "subtext": { "template": "${value}%", "visible": "value > 0" },
+                "style": { "filter": "rgb(${colorComponents.red}, ${colorComponents.green}, ${colorComponents.blue})" }
+            }
+        })
+    }
+
+    fn property_event(id: i64, property: &str, new_value: Value) -> Value {
+        json!({ "type": "DevicePropertyUpdatedEvent", "data": { "id": id, "property": property, "newValue": new_value } })
+    }
+
+    #[test]
+    fn renders_conditional_icon_subtext_and_style() {
+        let widget = light();
+        let output = render(&widget, &state(json!({ "value": 50, "colorComponents": { "red": 255, "green": 10 } })));
+        assert_eq!(output.icon.as_deref(), Some("half"));
+        assert_eq!(output.subtext.as_deref(), Some("50%"));
+        assert!(output.subtext_visible);
+        assert_eq!(output.style["filter"], json!("rgb(255, 10, 0)"));
+
+        let off = render(&widget, &state(json!({ "value": 0 })));
+        assert_eq!(off.icon.as_deref(), Some("off"));
+        assert!(!off.subtext_visible);
+    }
+
+    #[test]
+    fn conditional_icon_without_property_in_state_selects_nothing() {
+        assert_eq!(icon_name(&light(), &Map::new()), None);
+    }
+
+    #[test]
+    fn static_icon_and_missing_render() {
+        let widget = json!({ "render": { "icon": { "type": "static", "icon": "sensor" } } });
+        assert_eq!(render(&widget, &Map::new()).icon.as_deref(), Some("sensor"));
+        assert_eq!(render(&json!({}), &Map::new()), RenderOutput::default());
+    }
+
+    #[test]
+    fn subtext_without_visible_condition_is_shown() {
+        let widget = json!({ "render": { "subtext": { "template": "${value * 1.8 + 32}°F" } } });
+        let output = render(&widget, &state(json!({ "value": 20 })));
+        assert_eq!(output.subtext.as_deref(), Some("68°F"));
+        assert!(output.subtext_visible);
+    }
+
+    #[test]
+    fn icon_variants_come_from_the_matching_condition() {
+        let widget = json!({
+            "render": {
+                "icon": {
+                    "type": "conditional",
+                    "property": "value",
+                    "tint": "rgb(${colorComponents.red}, 0, 0)",
+                    "badge": { "color": "#e74c3c", "text": "${battery}", "visible": "battery < 20" },
+                    "conditions": [
+                        { "when": "value == 0", "icon": "off", "tint": "#888" },
+                        { "when": "value > 0", "icon": "on" }
+                    ]
+                }
+            }
+        });
+        let on = icon_variant(&widget, &state(json!({ "value": 50, "colorComponents": { "red": 200 }, "battery": 10 }))).unwrap();
+        assert_eq!(on.tint.as_deref(), Some("rgb(200, 0, 0)"));
+        assert_eq!(on.badge.unwrap().text.as_deref(), Some("10"));
+
+        let off = icon_variant(&widget, &state(json!({ "value": 0, "battery": 90 }))).unwrap();
+        assert_eq!(off.tint.as_deref(), Some("#888"));
+        assert_eq!(off.badge, None);
+
+        assert_eq!(icon_variant(&light(), &state(json!({ "value": 0 }))), None);
+    }
+
+    #[test]
+    fn selects_json_paths() {
+        let event = json!({ "type": "T", "data": { "id": 7, "list": [1, 2, 3], "nested": { "id": 7 } } });
+        assert_eq!(select(&event, "$.data.id").unwrap(), vec![&json!(7)]);
+        assert_eq!(select(&event, "$['data']['list'][-1]").unwrap(), vec![&json!(3)]);
+        assert_eq!(select(&event, "$.data.list[*]").unwrap().len(), 3);
+        assert_eq!(select(&event, "$..[?(@.id == 7)]").unwrap().len(), 2);
+        assert_eq!(select(&event, "$..list[?(@ > 1)]").unwrap(), vec![&json!(2), &json!(3)]);
+        assert!(select(&event, "data.id").is_err());
+        assert!(select(&event, "$.data[").is_err());
+    }
+
+    #[test]
+    fn event_matching_checks_type_id_and_match() {
+        let widget = light();
+        assert!(event_matches(&widget, "42", &property_event(42, "value", json!(10))));
+        assert!(event_matches(&widget, "42", &property_event(42, "color", json!("1,2,3"))));
+        assert!(!event_matches(&widget, "43", &property_event(42, "value", json!(10))));
+        assert!(!event_matches(&widget, "42", &property_event(42, "energy", json!(3))));
+        let other = json!({ "type": "CentralSceneEvent", "data": { "id": 42 } });
+        assert!(!event_matches(&widget, "42", &other));
+    }
+
+    #[test]
+    fn event_without_match_path_matches_on_id() {
+        let widget = json!({ "events": { "DeviceActionRanEvent": { "updates": { "action": "event.actionName" } } } });
+        let event = json!({ "type": "DeviceActionRanEvent", "data": { "id": "5", "actionName": "turnOn" } });
+        let mut device_state = Map::new();
+        assert_eq!(apply_event(&widget, "5", &mut device_state, &event), vec!["action"]);
+        assert_eq!(device_state["action"], json!("turnOn"));
+    }
+
+    #[test]
+    fn conditional_updates_follow_the_event_property() {
+        let widget = light();
+        let mut device_state = state(json!({ "value": 0 }));
+
+        let updated = apply_event(&widget, "42", &mut device_state, &property_event(42, "value", json!(99)));
+        assert_eq!(updated, vec!["value"]);
+        assert_eq!(device_state["value"], json!(99));
+
+        let updated = apply_event(&widget, "42", &mut device_state, &property_event(42, "color", json!("255,128,x,0")));
+        assert_eq!(updated, vec!["colorComponents"]);
+        assert_eq!(device_state["value"], json!(99));
+        assert_eq!(device_state["colorComponents"], json!({ "red": 255, "green": 128, "blue": 0, "warmWhite": 0, "coldWhite": 0 }));
+    }
+
+    #[test]
+    fn untracked_property_events_are_ignored() {
+        let widget = json!({
+            "events": { "DevicePropertyUpdatedEvent": { "updates": { "value": "event.newValue" } } }
+        });
+        let mut device_state = state(json!({ "value": 1 }));
+        assert!(apply_event(&widget, "3", &mut device_state, &property_event(3, "energy", json!(9))).is_empty());
+        assert_eq!(device_state["value"], json!(1));
+        assert_eq!(apply_event(&widget, "3", &mut device_state, &property_event(3, "value", json!(2))), vec!["value"]);
+    }
+
+    #[test]
+    fn update_values_are_unwrapped_and_missing_values_are_null() {
+        let widget = json!({
+            "events": { "DevicePropertyUpdatedEvent": { "updates": { "value": "event.newValue", "source": "data.source.kind" } } }
+        });
+        let mut device_state = state(json!({ "value": 0 }));
+        apply_event(&widget, "1", &mut device_state, &property_event(1, "value", json!({ "value": true, "path": "x" })));
+        assert_eq!(device_state["value"], json!(true));
+        assert_eq!(device_state["source"], Value::Null);
+    }
+
+    #[test]
+    fn getters_unwrap_and_parse_colors() {
+        let mut device_state = Map::new();
+        apply_getter(&mut device_state, "value", "value", &json!({ "value": { "value": 12 } }));
+        apply_getter(&mut device_state, "colorComponents", "value", &json!({ "value": " 1, 2 ,3" }));
+        apply_getter(&mut device_state, "battery", "properties.batteryLevel", &json!({}));
+        assert_eq!(device_state["value"], json!(12));
+        assert_eq!(device_state["colorComponents"]["blue"], json!(3));
+        assert_eq!(device_state["colorComponents"]["coldWhite"], json!(0));
+        assert_eq!(device_state["battery"], Value::Null);
+    }
+
+    #[test]
+    fn evaluates_default_state_and_events() {
+        let events = [property_event(42, "value", json!(20)), property_event(7, "value", json!(99)), property_event(42, "value", json!(100))];
+        let evaluation = evaluate(&light(), "42", &state(json!({ "value": 5 })), &events);
+        assert_eq!(evaluation.updated, vec!["value"]);
+        assert_eq!(evaluation.state["value"], json!(100));
+        assert_eq!(evaluation.state["colorComponents"]["red"], json!(0));
+        assert_eq!(evaluation.render.icon.as_deref(), Some("on"));
+        assert_eq!(evaluation.render.subtext.as_deref(), Some("100%"));
+    }
+
+    #[test]
+    fn parses_ints_like_javascript() {
+        assert_eq!(parse_int(" 42px"), 42);
+        assert_eq!(parse_int("-7"), -7);
+        assert_eq!(parse_int("abc"), 0);
+        assert_eq!(parse_int(""), 0);
+    }
+}
@@ -58,32 +58,19 @@ export class DeviceManagementView {
         
         console.log('[DeviceManagement] About to discover widgets...');
         
-        let builtInWidgets = [];
+        let widgets = [];
         try {
-            builtInWidgets = await packageManager.discoverBuiltInWidgets();
-            console.log('[DeviceManagement] Widgets discovered:', builtInWidgets.length);
+            widgets = await packageManager.listWidgets();
+            console.log('[DeviceManagement] Widgets discovered:', widgets.length);
         } catch (error) {
             console.error('[DeviceManagement] Error discovering widgets:', error);
         }
         
         // Create widget options array with display names and values
-        const widgetOptions = builtInWidgets.map(w => ({
-            value: w.id,
-            display: `${w.id} (com.fibaro.built-in)`
+        const widgetOptions = widgets.map(w => ({
+            value: w.reference,
+            display: packageManager.widgetLabel(w)
         }));
-        console.log('[DeviceManagement] Widget options created:', widgetOptions.length);
-        
-        // Add widgets from installed packages
-        if (packageManager.installedPackages?.packages) {
-            for (const [packageId, packageInfo] of Object.entries(packageManager.installedPackages.packages)) {
-                for (const widgetId of packageInfo.manifest.provides.widgets) {
-                    widgetOptions.push({
-                        value: `${packageId}/${widgetId}`,
-                        display: `${widgetId} (${packageId})`
-                    });
-                }
-            }
-        }
         
         console.log('Available widgets for device management:', widgetOptions);
         
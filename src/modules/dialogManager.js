@@ -4,12 +4,31 @@ import { normalizeDeviceFormat } from './deviceHelpers.js';
 export class DialogManager {
     constructor(homeMapInstance) {
         this.app = homeMapInstance;
+        // Icon registry from list_icon_sets; building it decodes every icon
+        this.iconRegistry = null;
+    }
+
+    /**
+     * Forget the cached icon registry, after icon sets were added or removed
+     */
+    invalidateIconSets() {
+        this.iconRegistry = null;
     }
 
     /**
      * Discover available icon sets
+     * Uses the icon registry (list_icon_sets), which adds each set's states and broken icons
      */
     async discoverIconSets() {
+        try {
+            if (!this.iconRegistry) {
+                this.iconRegistry = await this.app.invoke('list_icon_sets');
+            }
+            return this.iconRegistry.sets;
+        } catch (error) {
+            console.error('list_icon_sets failed, falling back to directory discovery:', error);
+        }
+
         const iconSets = [];
         const dataPath = this.app.dataPath;
         
@@ -97,6 +116,44 @@ export class DialogManager {
         return iconSets;
     }
 
+    /**
+     * Picker label for a discoverIconSets() entry, flagged when icons are broken
+     */
+    iconSetLabel(set) {
+        return set.errors > 0 ? `${set.name} ⚠️` : set.name;
+    }
+
+    /**
+     * Tooltip listing the states (icon names) of an icon set
+     */
+    iconSetOptionAttributes(set) {
+        if (!set.states) return '';
+        const states = set.states.length > 0 ? set.states.join(', ') : 'no icons';
+        const broken = set.errors > 0 ? ` (${set.errors} broken)` : '';
+        return `title="States: ${states}${broken}"`;
+    }
+
+    /**
+     * Widget <optgroup>s for the widget pickers, built-in first
+     */
+    widgetOptionGroups(widgets, currentWidget) {
+        const packageManager = this.app.widgetManager.packageManager;
+        const builtIn = widgets.filter(w => w.package === 'com.fibaro.built-in');
+        const packaged = widgets.filter(w => w.package !== 'com.fibaro.built-in');
+        let html = '';
+        if (builtIn.length > 0) {
+            html += '<optgroup label="Built-in Widgets">';
+            html += builtIn.map(w => packageManager.widgetOptionHtml(w, w.reference === currentWidget)).join('');
+            html += '</optgroup>';
+        }
+        if (packaged.length > 0) {
+            html += '<optgroup label="Installed Packages">';
+            html += packaged.map(w => packageManager.widgetOptionHtml(w, w.reference === currentWidget)).join('');
+            html += '</optgroup>';
+        }
+        return html;
+    }
+
     /**
      * Show dialog to add a new device
      */
@@ -128,39 +185,9 @@ export class DialogManager {
         console.log('Package manager:', packageManager);
         console.log('Installed packages:', packageManager.installedPackages);
         
-        // Add all built-in widgets
-        const builtInWidgets = await packageManager.discoverBuiltInWidgets();
-        if (builtInWidgets.length > 0) {
-            widgetOptions += '<optgroup label="Built-in Widgets">';
-            builtInWidgets.forEach(w => {
-                widgetOptions += `<option value="${w.id}">${w.id} (com.fibaro.built-in)</option>`;
-            });
-            widgetOptions += '</optgroup>';
-        }
-        
-        // Add widgets from installed packages
-        if (packageManager.installedPackages?.packages) {
-            const availableWidgets = [];
-            
-            for (const [packageId, packageInfo] of Object.entries(packageManager.installedPackages.packages)) {
-                for (const widgetId of packageInfo.manifest.provides.widgets) {
-                    // Always add package version with full reference
-                    availableWidgets.push({
-                        id: widgetId,
-                        package: packageId,
-                        fullRef: `${packageId}/${widgetId}`
-                    });
-                }
-            }
-            
-            if (availableWidgets.length > 0) {
-                widgetOptions += '<optgroup label="Installed Packages">';
-                availableWidgets.forEach(w => {
-                    widgetOptions += `<option value="${w.fullRef}">${w.id} (${w.package})</option>`;
-                });
-                widgetOptions += '</optgroup>';
-            }
-        }
+        // Built-in and package widgets
+        const widgets = await packageManager.listWidgets();
+        widgetOptions += this.widgetOptionGroups(widgets, null);
         
         // Discover available icon sets
         const iconSets = await this.discoverIconSets();
@@ -175,7 +202,7 @@ export class DialogManager {
             for (const [location, sets] of Object.entries(grouped)) {
                 iconSetOptions += `<optgroup label="${location.charAt(0).toUpperCase() + location.slice(1)}">`;
                 sets.forEach(set => {
-                    iconSetOptions += `<option value="${set.name}">${set.name}</option>`;
+                    iconSetOptions += `<option value="${set.name}" ${this.iconSetOptionAttributes(set)}>${this.iconSetLabel(set)}</option>`;
                 });
                 iconSetOptions += '</optgroup>';
             }
@@ -388,46 +415,9 @@ export class DialogManager {
         // Determine current widget reference (could be "type" or explicit "widget")
         const currentWidget = device.widget || device.type;
         
-        // Get loaded widget types to avoid duplicates
-        const loadedWidgetTypes = new Set(Object.keys(this.app.widgetManager.widgets));
-        
-        // Add built-in widgets (including both loaded and unloaded)
-        const builtInWidgets = await packageManager.discoverBuiltInWidgets();
-        if (builtInWidgets.length > 0) {
-            widgetOptions += '<optgroup label="Built-in Widgets">';
-            builtInWidgets.forEach(w => {
-                const selected = w.id === currentWidget ? 'selected' : '';
-                widgetOptions += `<option value="${w.id}" ${selected}>${w.id} (com.fibaro.built-in)</option>`;
-            });
-            widgetOptions += '</optgroup>';
-        }
-        
-        // Add widgets from installed packages that aren't loaded yet
-        if (packageManager.installedPackages?.packages) {
-            const availableWidgets = [];
-            
-            for (const [packageId, packageInfo] of Object.entries(packageManager.installedPackages.packages)) {
-                for (const widgetId of packageInfo.manifest.provides.widgets) {
-                    const fullRef = `${packageId}/${widgetId}`;
-                    // Always add package version with full reference
-                    availableWidgets.push({
-                        id: widgetId,
-                        fullRef: fullRef,
-                        package: packageId,
-                        selected: fullRef === currentWidget
-                    });
-                }
-            }
-            
-            if (availableWidgets.length > 0) {
-                widgetOptions += '<optgroup label="Installed Packages">';
-                availableWidgets.forEach(w => {
-                    const selected = w.selected ? 'selected' : '';
-                    widgetOptions += `<option value="${w.fullRef}" ${selected}>${w.id} (${w.package})</option>`;
-                });
-                widgetOptions += '</optgroup>';
-            }
-        }
+        // Built-in and package widgets
+        const widgets = await packageManager.listWidgets();
+        widgetOptions += this.widgetOptionGroups(widgets, currentWidget);
         
         // Discover available icon sets
         const iconSets = await this.discoverIconSets();
@@ -454,7 +444,7 @@ export class DialogManager {
                     // Use qualified name: packageId:iconSetName (or just iconSetName if no package)
                     const qualifiedValue = set.packageId ? `${set.packageId}:${set.name}` : set.name;
                     const selected = qualifiedValue === currentQualified ? 'selected' : '';
-                    iconSetOptions += `<option value="${qualifiedValue}" ${selected}>${set.name}</option>`;
+                    iconSetOptions += `<option value="${qualifiedValue}" ${selected} ${this.iconSetOptionAttributes(set)}>${this.iconSetLabel(set)}</option>`;
                 });
                 iconSetOptions += '</optgroup>';
             }
@@ -706,6 +696,8 @@ export class DialogManager {
     async showComposableDialog(device, widget, ui) {
         const modal = document.createElement('div');
         modal.className = 'slider-modal';
+        // Includes computed properties, so elements can bind to them
+        const state = await this.app.widgetManager.getDeviceState(device, widget);
         
         // Build HTML for each row
         let rowsHtml = '';
@@ -723,7 +715,7 @@ export class DialogManager {
                         break;
                     
                     case 'slider':
-                        const currentValue = device.state?.[element.property] || element.min || 0;
+                        const currentValue = state[element.property] || element.min || 0;
                         elementsHtml += `
                             <div class="slider-container-inline" data-property="${element.property}" data-action="${element.action}" data-min="${element.min}" data-max="${element.max}">
                                 <input type="range" min="${element.min}" max="${element.max}" value="${currentValue}" class="inline-slider">
@@ -733,7 +725,7 @@ export class DialogManager {
                         break;
                     
                     case 'colorSelect':
-                        const colorState = device.state?.colorComponents || { red: 0, green: 0, blue: 0 };
+                        const colorState = state.colorComponents || { red: 0, green: 0, blue: 0 };
                         const hexColor = this.rgbToHex(colorState.red || 0, colorState.green || 0, colorState.blue || 0);
                         elementsHtml += `
                             <div class="color-select-container" data-property="${element.property}" data-action="${element.action}">
@@ -959,8 +951,9 @@ export class DialogManager {
         const modal = document.createElement('div');
         modal.className = 'slider-modal';
         
-        // Get current device value from state
-        let currentValue = device.state?.[ui.property] || ui.min || 0;
+        // Get current device value from state, computed properties included
+        const state = await this.app.widgetManager.getDeviceState(device, widget);
+        let currentValue = state[ui.property] || ui.min || 0;
         
         modal.innerHTML = `
             <div class="slider-content">
@@ -69,9 +69,6 @@ export class EventManager {
                 
                 const response = await this.homeMap.hc3ApiManager.fetch(url, {
                     method: 'GET',
-                    headers: {
-                        'Authorization': `Basic ${btoa(`${config.user}:${config.password}`)}`
-                    },
                     timeout: 35000
                 });
 
@@ -8,40 +8,32 @@ export class HC3ApiManager {
     }
 
     /**
-     * Fetch with support for self-signed certificates (HTTPS)
-     * Uses custom Rust command for HTTPS to accept invalid certs
+     * Fetch from the HC3 through the backend
+     * The backend adds the Authorization header from the credential vault and
+     * accepts self-signed certs, so only the API path is passed along
      */
     async fetch(url, options = {}) {
-        const config = this.homeMap.config;
+        const parsed = new URL(url, 'http://hc3');
+        const path = parsed.pathname + parsed.search;
         
-        // For HTTPS, use our custom command that accepts self-signed certs
-        if (config.protocol === 'https') {
-            try {
-                const headers = {};
-                if (options.headers) {
-                    Object.assign(headers, options.headers);
-                }
-                
-                const response = await this.homeMap.invoke('http_fetch_insecure', {
-                    url,
-                    method: options.method || 'GET',
-                    headers,
-                    body: options.body || null
-                });
-                
-                // Return fetch-like response object
-                return {
-                    ok: response.ok,
-                    status: response.status,
-                    text: async () => response.body,
-                    json: async () => JSON.parse(response.body)
-                };
-            } catch (error) {
-                throw new Error(error);
-            }
-        } else {
-            // For HTTP, use normal Tauri fetch
-            return await this.homeMap.http.fetch(url, options);
+        try {
+            const response = await this.homeMap.invoke('hc3_request', {
+                path,
+                method: options.method || 'GET',
+                headers: options.headers || {},
+                body: options.body || null,
+                timeoutMs: options.timeout || null
+            });
+            
+            // Return fetch-like response object
+            return {
+                ok: response.ok,
+                status: response.status,
+                text: async () => response.body,
+                json: async () => JSON.parse(response.body)
+            };
+        } catch (error) {
+            throw new Error(error);
         }
     }
 
@@ -104,10 +96,7 @@ export class HC3ApiManager {
             console.log('Testing connection to:', url);
             
             const response = await this.fetch(url, {
-                method: 'GET',
-                headers: {
-                    'Authorization': `Basic ${btoa(`${config.user}:${config.password}`)}`
-                }
+                method: 'GET'
             });
 
             console.log('Response:', response);
@@ -143,10 +132,7 @@ export class HC3ApiManager {
         
         try {
             const response = await this.fetch(url, {
-                method: 'GET',
-                headers: {
-                    'Authorization': 'Basic ' + btoa(`${config.user}:${config.password}`)
-                }
+                method: 'GET'
             });
 
             if (!response.ok) {
@@ -231,7 +217,6 @@ export class HC3ApiManager {
         const fetchOptions = {
             method: action.method || 'GET',
             headers: {
-                'Authorization': 'Basic ' + btoa(`${config.user}:${config.password}`),
                 'X-Fibaro-Version': '2',
                 'Accept-Language': 'en'
             }
@@ -328,10 +313,7 @@ export class HC3ApiManager {
                         const url = `${config.protocol}://${config.host}${api}`;
                         
                         const response = await this.fetch(url, {
-                            method: 'GET',
-                            headers: {
-                                'Authorization': `Basic ${btoa(`${config.user}:${config.password}`)}`
-                            }
+                            method: 'GET'
                         });
 
                         if (response.ok) {
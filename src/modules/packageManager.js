@@ -125,6 +125,9 @@ class PackageManager {
      * Install a widget package from .hwp file
      */
     async installPackage(hwpPath = null) {
+        // Extracted package folder; removed unless the backend installs from it
+        let tempDir = null;
+        let installed = false;
         try {
             // If no path provided, show file picker
             if (!hwpPath) {
@@ -152,9 +155,45 @@ class PackageManager {
                 hwpPath: hwpPath,
                 dataPath: this.dataPath
             });
-            const { manifest, tempDir } = extracted;
+            const { manifest } = extracted;
+            tempDir = extracted.tempDir;
 
             console.log('Package extracted:', manifest);
+            if (extracted.skippedFiles?.length > 0) {
+                console.warn('Package files skipped because of their type:', extracted.skippedFiles);
+                const files = extracted.skippedFiles;
+                await this.dialog.message(
+                    `Package "${manifest.name}" contains files that will not be installed because of their type:\n\n` +
+                    files.slice(0, 10).join('\n') + (files.length > 10 ? `\n...and ${files.length - 10} more` : ''), {
+                    title: 'Files Skipped',
+                    kind: 'warning'
+                });
+            }
+
+            // Packages can carry actions that call the HC3, so say who they come from
+            if (!(await this.confirmSignature(manifest, extracted.signature))) {
+                console.log('Installation cancelled by user');
+                return null;
+            }
+
+            // Widgets with errors may not render; let the user decide
+            const broken = (extracted.widgetDiagnostics || []).filter(report => report.errors > 0);
+            if (broken.length > 0) {
+                const lines = broken.flatMap(report => report.diagnostics
+                    .filter(d => d.severity === 'error')
+                    .map(d => `${report.widget}.json:${d.line}:${d.column} ${d.message}`));
+                const proceed = await this.dialog.confirm(
+                    `Package "${manifest.name}" has widget definition errors:\n\n${lines.slice(0, 10).join('\n')}` +
+                    (lines.length > 10 ? `\n...and ${lines.length - 10} more` : '') +
+                    `\n\nInstall it anyway?`, {
+                    title: 'Widget Errors',
+                    kind: 'warning'
+                });
+                if (!proceed) {
+                    console.log('Installation cancelled by user');
+                    return null;
+                }
+            }
 
             // Check if package is already installed
             const existingPackage = this.installedPackages.packages[manifest.id];
@@ -197,23 +236,87 @@ class PackageManager {
                 // For now, proceed with installation (namespaced)
             }
 
-            // Install package files
-            await this.installPackageFiles(manifest, tempDir);
-
-            // Register package
-            await this.registerPackage(manifest, tempDir);
-
-            // Cleanup temp directory
-            // Note: We might want to keep it for now during development
-            // await this.fs.remove(tempDir, { recursive: true });
+            // Copy files and update both registries in one step; the backend
+            // rolls everything back if any part fails
+            const change = await this.invoke(existingPackage ? 'upgrade_package' : 'install_package', { tempDir });
+            installed = true;
+            await this.loadInstalledPackages();
+            await this.loadWidgetMappings();
+
+            if (change.previousVersion && (change.removedMappings.length > 0 || change.affectedDevices.length > 0)) {
+                await this.dialog.message(
+                    `Version ${manifest.version} of "${manifest.name}" no longer provides some widgets.\n\n` +
+                    (change.removedMappings.length > 0 ? `Removed widget mappings: ${change.removedMappings.join(', ')}\n` : '') +
+                    (change.affectedDevices.length > 0 ? `Devices that will use another widget: ${change.affectedDevices.join(', ')}` : ''), {
+                    title: 'Package Upgraded',
+                    kind: 'warning'
+                });
+            }
 
             console.log('Package installed successfully:', manifest.id);
             return manifest;
 
         } catch (error) {
             console.error('Error installing package:', error);
-            throw error;
+            // Backend commands reject with plain strings
+            throw error instanceof Error ? error : new Error(String(error));
+        } finally {
+            if (tempDir && !installed) {
+                try {
+                    await this.invoke('discard_extracted_package', { tempDir });
+                } catch (error) {
+                    console.warn('Failed to remove extracted package:', error);
+                }
+            }
+        }
+    }
+
+    /**
+     * Warn about packages that aren't signed by a trusted publisher.
+     * Returns whether to go on with the installation.
+     */
+    async confirmSignature(manifest, signature) {
+        const status = signature?.status || 'unsigned';
+        console.log(`Package signature: ${status}`, signature?.message);
+
+        if (status === 'valid') {
+            return true;
         }
+        if (status === 'tampered') {
+            await this.dialog.message(
+                `Package "${manifest.name}" was modified after it was signed and will not be installed.\n\n${signature.message}`, {
+                title: 'Package Signature Invalid',
+                kind: 'error'
+            });
+            return false;
+        }
+
+        const risk = 'Widget packages can contain actions that send commands to your HC3. ' +
+            'Only install packages from sources you trust.';
+        if (status === 'unknownSigner') {
+            const proceed = await this.dialog.confirm(
+                `${signature.message}.\n\nKey fingerprint: ${signature.keyId}\n\n${risk}\n\nInstall it anyway?`, {
+                title: 'Unknown Publisher',
+                kind: 'warning'
+            });
+            if (proceed && await this.dialog.confirm(
+                `Trust ${signature.publisher} (${signature.keyId}) for future packages?`, {
+                title: 'Trust Publisher',
+                kind: 'info'
+            })) {
+                try {
+                    await this.invoke('trust_publisher', { name: signature.publisher, publicKey: signature.publicKey });
+                } catch (error) {
+                    console.error('Failed to trust publisher:', error);
+                }
+            }
+            return proceed;
+        }
+        return await this.dialog.confirm(
+            `Package "${manifest.name}" is not signed, so its publisher can't be verified.\n\n${risk}\n\nInstall it anyway?`, {
+            title: 'Unsigned Package',
+            kind: 'warning'
+        });
     }
 
     /**
@@ -291,144 +394,38 @@ class PackageManager {
     }
 
     /**
-     * Install package files to homemapdata
-     */
-    async installPackageFiles(manifest, tempDir) {
-        const packageId = manifest.id;
-        const packageDir = joinPath(this.dataPath, 'widgets', 'packages', packageId);
-
-        // Create package directory using Rust command
-        await this.invoke('create_dir', { path: packageDir });
-
-        // Copy manifest
-        const manifestSrc = joinPath(tempDir, 'manifest.json');
-        const manifestDst = joinPath(packageDir, 'manifest.json');
-        await this.invoke('copy_file', { src: manifestSrc, dst: manifestDst });
-
-        // Copy widgets
-        const widgetsSrc = joinPath(tempDir, 'widgets');
-        const widgetsSrcAlt = joinPath(tempDir, 'widget.json');
-        
-        // Check if widgets/ directory exists
-        const widgetsDirExists = await this.invoke('path_exists', { path: widgetsSrc });
-        const widgetFileExists = await this.invoke('path_exists', { path: widgetsSrcAlt });
-
-        if (widgetsDirExists && await this.invoke('is_directory', { path: widgetsSrc })) {
-            // Copy all widget files from widgets/ directory
-            const entries = await this.invoke('list_directory', { path: widgetsSrc });
-            for (const entry of entries) {
-                if (entry.endsWith('.json')) {
-                    const src = joinPath(widgetsSrc, entry);
-                    const dst = joinPath(packageDir, entry);
-                    await this.invoke('copy_file', { src, dst });
-                }
-            }
-        } else if (widgetFileExists) {
-            // Single widget.json file - rename to first widget ID
-            const widgetId = manifest.provides.widgets[0];
-            const dst = joinPath(packageDir, `${widgetId}.json`);
-            await this.invoke('copy_file', { src: widgetsSrcAlt, dst });
-        }
-
-        // Copy icon sets
-        const iconsSrc = joinPath(tempDir, 'icons');
-        const iconsExists = await this.invoke('path_exists', { path: iconsSrc });
-        
-        if (iconsExists && await this.invoke('is_directory', { path: iconsSrc })) {
-            const iconsPackageDir = joinPath(this.dataPath, 'icons', 'packages', packageId);
-            await this.invoke('create_dir', { path: iconsPackageDir });
-
-            const entries = await this.invoke('list_directory', { path: iconsSrc });
-            for (const entry of entries) {
-                const srcPath = joinPath(iconsSrc, entry);
-                if (await this.invoke('is_directory', { path: srcPath })) {
-                    const dstDir = joinPath(iconsPackageDir, entry);
-                    await this.copyDirectory(srcPath, dstDir);
-                }
-            }
-        }
-    }
-
-    /**
-     * Recursively copy a directory
+     * Uninstall a package. If devices still use it, asks before removing it anyway.
+     * Returns null when the user cancels.
      */
-    async copyDirectory(src, dst) {
-        await this.invoke('create_dir', { path: dst });
-        
-        const entries = await this.invoke('list_directory', { path: src });
-        for (const entry of entries) {
-            const srcPath = joinPath(src, entry);
-            const dstPath = joinPath(dst, entry);
-            
-            if (await this.invoke('is_directory', { path: srcPath })) {
-                await this.copyDirectory(srcPath, dstPath);
-            } else {
-                await this.invoke('copy_file', { src: srcPath, dst: dstPath });
+    async uninstallPackage(packageId) {
+        const invokeUninstall = async (force) => {
+            try {
+                return await this.invoke('uninstall_package', { packageId, force });
+            } catch (error) {
+                // Backend commands reject with plain strings
+                throw error instanceof Error ? error : new Error(String(error));
             }
-        }
-    }
-
-    /**
-     * Register installed package in registry
-     */
-    async registerPackage(manifest, tempDir) {
-        const packageId = manifest.id;
-        
-        // Build file list
-        const files = {
-            widgets: [],
-            icons: []
-        };
-
-        // Add widget files
-        for (const widgetId of manifest.provides.widgets) {
-            const widgetPath = `widgets/packages/${packageId}/${widgetId}.json`;
-            files.widgets.push(widgetPath);
-        }
-
-        // Add icon set directories
-        for (const iconSet of manifest.provides.iconSets) {
-            const iconPath = `icons/packages/${packageId}/${iconSet}/`;
-            files.icons.push(iconPath);
-        }
-
-        // Register in installed packages
-        this.installedPackages.packages[packageId] = {
-            version: manifest.version,
-            installedAt: new Date().toISOString(),
-            installedFrom: 'local',
-            manifest: manifest,
-            files: files
         };
 
-        await this.saveInstalledPackages();
-    }
-
-    /**
-     * Uninstall a package
-     */
-    async uninstallPackage(packageId) {
-        const packageInfo = this.installedPackages.packages[packageId];
-        if (!packageInfo) {
-            throw new Error(`Package not found: ${packageId}`);
-        }
-
-        // Remove widget files
-        const packageDir = joinPath(this.dataPath, 'widgets', 'packages', packageId);
-        await this.fs.remove(packageDir, { recursive: true });
-
-        // Remove icon files
-        const iconsDir = joinPath(this.dataPath, 'icons', 'packages', packageId);
-        const iconsExists = await this.fs.exists(iconsDir);
-        if (iconsExists) {
-            await this.fs.remove(iconsDir, { recursive: true });
+        let change = await invokeUninstall(false);
+        if (!change.committed) {
+            const devices = change.affectedDevices;
+            const force = await this.dialog.confirm(
+                `Package ${packageId} is still used by ${devices.length} device(s): ${devices.join(', ')}\n\n` +
+                `These devices will use another widget or icon set. Uninstall anyway?`, {
+                title: 'Package In Use',
+                kind: 'warning'
+            });
+            if (!force) {
+                return null;
+            }
+            change = await invokeUninstall(true);
         }
 
-        // Remove from registry
-        delete this.installedPackages.packages[packageId];
-        await this.saveInstalledPackages();
-
+        await this.loadInstalledPackages();
+        await this.loadWidgetMappings();
         console.log('Package uninstalled:', packageId);
+        return change;
     }
 
     /**
@@ -444,105 +441,24 @@ class PackageManager {
     /**
      * Resolve widget for a device type
      * Priority: explicit > mapping > built-in > packages > generic
+     * Resolution happens in the backend (resolve_widget) so it matches floor export
      */
     async resolveWidget(deviceType, explicitWidget = null) {
-        // 1. Explicit widget reference
-        if (explicitWidget) {
-            const parts = explicitWidget.split('/');
-            if (parts.length === 2) {
-                const [packageId, widgetId] = parts;
-                return await this.loadPackageWidget(packageId, widgetId);
-            }
-        }
-
-        // 2. Check widget mappings
-        if (this.widgetMappings.mappings[deviceType]) {
-            const mapping = this.widgetMappings.mappings[deviceType];
-            return await this.loadPackageWidget(mapping.package, mapping.widget);
-        }
-
-        // 3. Try built-in widget by device type name
-        const builtInWidget = await this.loadBuiltInWidget(deviceType);
-        if (builtInWidget) return builtInWidget;
-
-        // 4. Search installed packages
-        const matches = await this.findWidgetsByDeviceType(deviceType);
-        if (matches.length > 0) {
-            return matches[0]; // Return first match for now
-        }
-
-        // 5. Return null - let widgetManager handle generic fallback
-        return null;
-    }
-
-    /**
-     * Load widget from a specific package
-     */
-    async loadPackageWidget(packageId, widgetId) {
         try {
-            const widgetPath = joinPath(this.dataPath, 'widgets', 'packages', packageId, `${widgetId}.json`);
-            const content = await this.invoke('read_file_as_text', { filePath: widgetPath });
-            const widget = JSON.parse(content);
-            widget._package = packageId;
+            const resolved = await this.invoke('resolve_widget', {
+                widget: explicitWidget || deviceType,
+                deviceType
+            });
+            const widget = resolved.definition;
+            widget._package = resolved.package;
             return widget;
         } catch (error) {
-            console.error(`Failed to load package widget ${packageId}/${widgetId}:`, error);
+            // Not found - let widgetManager handle generic fallback
+            console.warn(`Failed to resolve widget ${explicitWidget || deviceType}:`, error);
             return null;
         }
     }
 
-    /**
-     * Load built-in widget
-     */
-    async loadBuiltInWidget(widgetId) {
-        try {
-            // Try built-in directory first
-            let widgetPath = joinPath(this.dataPath, 'widgets', 'built-in', `${widgetId}.json`);
-            
-            try {
-                const content = await this.invoke('read_file_as_text', { filePath: widgetPath });
-                const widget = JSON.parse(content);
-                widget._package = 'com.fibaro.built-in';
-                return widget;
-            } catch (error) {
-                // Fallback to root widgets directory for backward compatibility
-                widgetPath = joinPath(this.dataPath, 'widgets', `${widgetId}.json`);
-                try {
-                    const content = await this.invoke('read_file_as_text', { filePath: widgetPath });
-                    const widget = JSON.parse(content);
-                    widget._package = 'com.fibaro.built-in';
-                    return widget;
-                } catch (error2) {
-                    return null;
-                }
-            }
-        } catch (error) {
-            console.error(`Failed to load built-in widget ${widgetId}:`, error);
-            return null;
-        }
-    }
-
-    /**
-     * Find widgets that support a device type
-     */
-    async findWidgetsByDeviceType(deviceType) {
-        const matches = [];
-
-        for (const [packageId, packageInfo] of Object.entries(this.installedPackages.packages)) {
-            if (packageInfo.manifest.deviceTypes?.includes(deviceType)) {
-                // Load all widgets from this package
-                for (const widgetId of packageInfo.manifest.provides.widgets) {
-                    const widget = await this.loadPackageWidget(packageId, widgetId);
-                    if (widget && widget.type === deviceType) {
-                        matches.push(widget);
-                    }
-                }
-            }
-        }
-
-        return matches;
-    }
-
     /**
      * Discover available built-in widgets
      * Returns list of widget IDs found in widgets/built-in directory (or legacy widgets/ directory)
@@ -624,6 +540,60 @@ class PackageManager {
         return widgets;
     }
 
+    /**
+     * List built-in and package widgets with metadata (list_widgets command)
+     * Each entry: { id, package, reference, name, widgetVersion, iconSet, iconPreview,
+     *               packageVersion, deviceTypes, errors, warnings }
+     */
+    async listWidgets() {
+        try {
+            return await this.invoke('list_widgets');
+        } catch (error) {
+            console.error('list_widgets failed, falling back to directory discovery:', error);
+        }
+
+        // Fallback: names only, from the built-in folder and the package registry
+        const entry = (id, packageId, reference, packageVersion = null) => ({
+            id, package: packageId, reference, packageVersion,
+            widgetVersion: null, iconSet: null, iconPreview: null,
+            deviceTypes: [], errors: [], warnings: 0
+        });
+        const widgets = (await this.discoverBuiltInWidgets())
+            .map(w => entry(w.id, 'com.fibaro.built-in', w.id));
+        for (const [packageId, packageInfo] of Object.entries(this.installedPackages?.packages || {})) {
+            for (const widgetId of packageInfo.manifest.provides.widgets) {
+                widgets.push(entry(widgetId, packageId, `${packageId}/${widgetId}`, packageInfo.version));
+            }
+        }
+        return widgets;
+    }
+
+    /**
+     * Picker label for a listWidgets() entry
+     */
+    widgetLabel(widget) {
+        const version = widget.packageVersion ? ` v${widget.packageVersion}` : '';
+        const broken = widget.errors?.length > 0 ? ' ⚠️' : '';
+        return `${widget.id} (${widget.package}${version})${broken}`;
+    }
+
+    /**
+     * Picker <option> for a listWidgets() entry, with details in the tooltip
+     */
+    widgetOptionHtml(widget, selected = false) {
+        const escape = text => String(text)
+            .replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/"/g, '&quot;');
+        const lines = [];
+        if (widget.description) lines.push(widget.description);
+        if (widget.deviceTypes?.length) lines.push(`Device types: ${widget.deviceTypes.join(', ')}`);
+        if (widget.widgetVersion) lines.push(`Widget format: ${widget.widgetVersion}`);
+        for (const error of widget.errors || []) {
+            lines.push(`Error (line ${error.line}): ${error.message}`);
+        }
+        return `<option value="${escape(widget.reference)}" title="${escape(lines.join('\n'))}" ${selected ? 'selected' : ''}>` +
+            `${escape(this.widgetLabel(widget))}</option>`;
+    }
+
     /**
      * Set widget mapping for a device type
      */
@@ -9,6 +9,68 @@ export class WidgetManager {
         this.widgets = {};
         this.iconSets = new Map();
         this.packageManager = packageManager;
+        // Last computed values per device: { key, values }
+        this.computedCache = new WeakMap();
+        this.clockTimer = null;
+    }
+
+    /**
+     * Whether a computed expression depends on the current time
+     */
+    usesClock(widget) {
+        return Object.values(widget?.computed || {}).some(expr => /\b(now|since)\s*\(/.test(String(expr)));
+    }
+
+    /**
+     * The state a device is rendered and shown in dialogs with: device.state
+     * plus the widget's computed properties, without changing device.state
+     */
+    async getDeviceState(device, widget) {
+        const state = device.state || {};
+        return widget.computed ? this.getComputedState(device, widget, state) : state;
+    }
+
+    /**
+     * Re-render devices whose computed state depends on the time every 30
+     * seconds, since no device event arrives to refresh "open for 5 min"
+     */
+    startClockRefresh(deviceIcons) {
+        if (this.clockTimer) {
+            return;
+        }
+        this.clockTimer = setInterval(async () => {
+            for (const { device, element, textElement } of deviceIcons.values()) {
+                const widget = this.getWidget(device.type);
+                if (device.state && this.usesClock(widget)) {
+                    await this.renderDevice(device, widget, element, textElement);
+                }
+            }
+        }, 30000);
+    }
+
+    /**
+     * State with the widget's computed properties added, as a new object.
+     * The backend is only asked again when the state changed, or always when
+     * an expression depends on the current time.
+     */
+    async getComputedState(device, widget, state) {
+        const key = JSON.stringify(state);
+        const timed = this.usesClock(widget);
+        const cached = this.computedCache.get(device);
+        if (cached && cached.key === key && !timed) {
+            return { ...state, ...cached.values };
+        }
+        try {
+            const values = await this.invoke('compute_widget_state', {
+                computed: widget.computed,
+                deviceState: state
+            });
+            this.computedCache.set(device, { key, values });
+            return { ...state, ...values };
+        } catch (error) {
+            console.error(`Failed to compute state for device ${device.id}:`, error);
+            return state;
+        }
     }
 
     /**
@@ -164,10 +226,10 @@ export class WidgetManager {
                 
                 // Load icon set if specified
                 if (widget.iconSet) {
-                    widget.iconSetMap = await this.loadIconSet(widget.iconSet, widget._package);
+                    widget.iconSetMap = await this.loadIconSet(widget.iconSet, widget.iconPackage || widget._package);
                 } else if (widget.render?.icon?.set) {
                     // New render format
-                    widget.iconSetMap = await this.loadIconSet(widget.render.icon.set, widget._package);
+                    widget.iconSetMap = await this.loadIconSet(widget.render.icon.set, widget.iconPackage || widget._package);
                 }
                 
                 this.widgets[type] = widget;
@@ -218,9 +280,9 @@ export class WidgetManager {
                         
                         // Load icon set if specified
                         if (widget.iconSet) {
-                            widget.iconSetMap = await this.loadIconSet(widget.iconSet, widget._package);
+                            widget.iconSetMap = await this.loadIconSet(widget.iconSet, widget.iconPackage || widget._package);
                         } else if (widget.render?.icon?.set) {
-                            widget.iconSetMap = await this.loadIconSet(widget.render.icon.set, widget._package);
+                            widget.iconSetMap = await this.loadIconSet(widget.render.icon.set, widget.iconPackage || widget._package);
                         }
                         
                         this.widgets[widgetType] = widget;
@@ -243,9 +305,9 @@ export class WidgetManager {
                                 
                                 // Load icon set if specified
                                 if (widget.iconSet) {
-                                    widget.iconSetMap = await this.loadIconSet(widget.iconSet, widget._package);
+                                    widget.iconSetMap = await this.loadIconSet(widget.iconSet, widget.iconPackage || widget._package);
                                 } else if (widget.render?.icon?.set) {
-                                    widget.iconSetMap = await this.loadIconSet(widget.render.icon.set, widget._package);
+                                    widget.iconSetMap = await this.loadIconSet(widget.render.icon.set, widget.iconPackage || widget._package);
                                 }
                                 
                                 this.widgets[widgetType] = widget;
@@ -284,8 +346,10 @@ export class WidgetManager {
             return;
         }
         
-        const state = device.state || {};
-        
+        // Derived properties are computed by the backend and read like ordinary
+        // state, without being stored in device.state
+        const state = await this.getDeviceState(device, widget);
+
         // Check if device has custom parameters that override widget settings
         let effectiveIconSetMap = widget.iconSetMap;
         if (device.params?.iconSet) {
@@ -305,7 +369,7 @@ export class WidgetManager {
                     if (needsInlineSvg) {
                         await this.loadInlineSvg(iconElement, iconPath, widget, state);
                     } else {
-                        await this.loadDeviceIcon(iconElement, iconPath);
+                        await this.loadDeviceIcon(iconElement, await this.getIconVariantPath(iconPath, state, widget.render.icon));
                     }
                 } else {
                     console.warn(`Icon "${iconName}" not found in icon set for device ${device.id}`);
@@ -344,9 +408,18 @@ export class WidgetManager {
      * Determine which icon to use based on render definition
      */
     getIconFromRenderDef(state, iconDef) {
+        const rule = this.getIconRule(state, iconDef);
+        return rule ? rule.icon : null;
+    }
+
+    /**
+     * The render rule that picks the icon: the icon definition itself for static
+     * icons, the first matching condition for conditional ones
+     */
+    getIconRule(state, iconDef) {
         switch (iconDef.type) {
             case 'static':
-                return iconDef.icon;
+                return iconDef;
             
             case 'conditional':
                 const propValue = state[iconDef.property];
@@ -356,7 +429,7 @@ export class WidgetManager {
                     // Create evaluation context with the specific property value
                     const evalContext = { [iconDef.property]: propValue };
                     if (this.evaluateCondition(evalContext, condition.when)) {
-                        return condition.icon;
+                        return condition;
                     }
                 }
                 return null;
@@ -367,6 +440,46 @@ export class WidgetManager {
         }
     }
 
+    /**
+     * Tint and badge for the icon, with templates filled in from state.
+     * A matching condition's tint and badge replace the icon-level ones.
+     */
+    getIconVariant(state, iconDef) {
+        const rule = this.getIconRule(state, iconDef) || {};
+        const tint = rule.tint ?? iconDef.tint;
+        const badge = rule.badge ?? iconDef.badge;
+        const variant = {};
+
+        if (tint) {
+            variant.tint = this.interpolateTemplate(tint, state);
+        }
+        if (badge?.color && (!badge.visible || this.evaluateCondition(state, badge.visible))) {
+            variant.badge = {
+                color: this.interpolateTemplate(badge.color, state),
+                text: badge.text ? this.interpolateTemplate(badge.text, state) : null,
+                position: badge.position || null
+            };
+        }
+        return Object.keys(variant).length > 0 ? variant : null;
+    }
+
+    /**
+     * Path of the icon to show: a tinted/badged variant generated by the backend
+     * when the render definition asks for one, otherwise the icon itself
+     */
+    async getIconVariantPath(iconPath, state, iconDef) {
+        const variant = iconPath.endsWith('.svg') ? this.getIconVariant(state, iconDef) : null;
+        if (!variant) {
+            return iconPath;
+        }
+        try {
+            return await this.invoke('generate_icon_variant', { iconPath, variant });
+        } catch (error) {
+            console.error(`Failed to generate icon variant for ${iconPath}:`, error);
+            return iconPath;
+        }
+    }
+
     /**
      * Evaluate a condition string against state
      */
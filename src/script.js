@@ -155,6 +155,17 @@ class HomeMap {
             await this.saveSettings();
         };
 
+        // Home profiles
+        document.getElementById('switchProfileBtn').onclick = async () => {
+            await this.switchProfile(document.getElementById('profileSelect').value);
+        };
+        document.getElementById('saveProfileBtn').onclick = async () => {
+            await this.saveProfile();
+        };
+        document.getElementById('deleteProfileBtn').onclick = async () => {
+            await this.deleteProfile(document.getElementById('profileSelect').value);
+        };
+
         // Browse for homemapdata folder (desktop only)
         // Use immediate mobile detection to avoid triggering folder dialogs
         this.checkIfMobile().then(isMobile => {
@@ -219,6 +230,36 @@ class HomeMap {
             };
         }
         
+        // Data integrity check button
+        document.getElementById('checkDataIntegrityBtn').onclick = async () => {
+            await this.checkDataIntegrity();
+        };
+        
+        // Storage cleanup button
+        document.getElementById('storageCleanupBtn').onclick = async () => {
+            await this.showStorageCleanupDialog();
+        };
+        
+        // Move data button (desktop only)
+        document.getElementById('moveHomemapDataBtn').onclick = async () => {
+            await this.moveHomemapData();
+        };
+        this.checkIfMobile().then(isMobile => {
+            if (isMobile) {
+                document.getElementById('moveHomemapDataGroup').style.display = 'none';
+            }
+        });
+        
+        // Portable mode button (desktop only)
+        document.getElementById('portableModeBtn').onclick = async () => {
+            await this.togglePortableMode();
+        };
+        this.checkIfMobile().then(isMobile => {
+            if (isMobile) {
+                document.getElementById('portableModeGroup').style.display = 'none';
+            }
+        });
+        
         // Install package button
         const installPackageBtn = document.getElementById('installPackageBtn');
         installPackageBtn.onclick = async () => {
@@ -349,9 +390,17 @@ class HomeMap {
             // Populate form
             document.getElementById('hc3Host').value = settings.hc3_host || '';
             document.getElementById('hc3User').value = settings.hc3_user || '';
-            document.getElementById('hc3Password').value = settings.hc3_password || '';
+            // The password stays in the backend vault; an empty field keeps it unchanged
+            document.getElementById('hc3Password').value = '';
+            const hasPassword = !!settings.hc3_host;
+            document.getElementById('hc3Password').placeholder = hasPassword ? '••••••••' : 'Enter password';
+            document.getElementById('hc3PasswordHint').style.display = hasPassword ? 'block' : 'none';
             document.getElementById('hc3Protocol').value = settings.hc3_protocol || 'http';
             
+            // Populate home profiles
+            await this.loadProfiles();
+            await this.loadPortableStatus();
+            
             // Show the actual path being used (from this.dataPath which was loaded at startup)
             // This is more accurate than settings.homemap_path which might be empty for default location
             document.getElementById('homemapPath').value = this.dataPath || settings.homemap_path || '';
@@ -420,6 +469,432 @@ class HomeMap {
         }
     }
     
+    async loadProfiles() {
+        try {
+            const profiles = await this.invoke('list_profiles');
+            const select = document.getElementById('profileSelect');
+            // Profile names are user input, so build the options as text
+            select.replaceChildren(...profiles.map(profile => {
+                const option = document.createElement('option');
+                option.value = profile.name;
+                option.textContent = profile.name + (profile.hc3_host ? ` (${profile.hc3_host})` : '');
+                option.selected = profile.active;
+                return option;
+            }));
+        } catch (error) {
+            console.error('Failed to load profiles:', error);
+        }
+    }
+    
+    async saveProfile() {
+        const name = document.getElementById('newProfileName').value.trim();
+        if (!name) {
+            await window.__TAURI__.dialog.message('Please enter a profile name.', { title: 'Save Profile', kind: 'warning' });
+            return;
+        }
+        
+        try {
+            await this.invoke('save_profile', {
+                profile: {
+                    name,
+                    hc3_host: document.getElementById('hc3Host').value,
+                    hc3_user: document.getElementById('hc3User').value,
+                    hc3_password: document.getElementById('hc3Password').value,
+                    hc3_protocol: document.getElementById('hc3Protocol').value,
+                    homemap_path: document.getElementById('homemapPath').value.trim()
+                }
+            });
+            document.getElementById('newProfileName').value = '';
+            await this.loadProfiles();
+        } catch (error) {
+            console.error('Failed to save profile:', error);
+            await window.__TAURI__.dialog.message(`Failed to save profile: ${error}`, { title: 'Save Profile', kind: 'error' });
+        }
+    }
+    
+    async loadPortableStatus() {
+        try {
+            this.portableStatus = await this.invoke('get_portable_status');
+            const button = document.getElementById('portableModeBtn');
+            const status = document.getElementById('portableModeStatus');
+            if (this.portableStatus.enabled) {
+                button.textContent = '🖥️ Convert to Regular Install...';
+                status.textContent = `Portable mode: settings and data are kept in ${this.portableStatus.root}`;
+            } else {
+                button.textContent = '💼 Make Portable...';
+                status.textContent = 'Keep settings and data next to the app (USB stick or network share)';
+            }
+        } catch (error) {
+            console.error('Failed to get portable status:', error);
+        }
+    }
+    
+    formatIntegrityIssues(issues, limit = 12) {
+        const lines = issues.slice(0, limit).map(issue => {
+            const mark = issue.repaired ? '✅' : (issue.severity === 'error' ? '❌' : '⚠️');
+            return `${mark} ${issue.message}\n    → ${issue.suggestion}`;
+        });
+        if (issues.length > limit) {
+            lines.push(`…and ${issues.length - limit} more (see the log for the full list)`);
+        }
+        return lines.join('\n');
+    }
+    
+    async checkDataIntegrity() {
+        const title = 'Check HomeMap Data';
+        try {
+            const report = await this.invoke('check_data_integrity', { repair: false });
+            console.log('Data integrity report:', report);
+            if (report.issues.length === 0) {
+                await window.__TAURI__.dialog.message('No problems found.', { title, kind: 'info' });
+                return;
+            }
+            
+            const summary = `${report.errors} errors, ${report.warnings} warnings\n\n${this.formatIntegrityIssues(report.issues)}`;
+            const repairable = report.issues.filter(issue => issue.repairable).length;
+            if (repairable === 0) {
+                await window.__TAURI__.dialog.message(summary, { title, kind: report.errors > 0 ? 'error' : 'warning' });
+                return;
+            }
+            
+            const confirmed = await window.__TAURI__.dialog.confirm(
+                `${summary}\n\nApply ${repairable} safe fix${repairable === 1 ? '' : 'es'}? config.json is not changed.`,
+                { title, kind: 'warning' }
+            );
+            if (!confirmed) return;
+            
+            const repaired = await this.invoke('check_data_integrity', { repair: true });
+            console.log('Data integrity repair:', repaired);
+            const remaining = repaired.issues.filter(issue => !issue.repaired);
+            const message = remaining.length === 0
+                ? `Repaired ${repaired.repaired} problem${repaired.repaired === 1 ? '' : 's'}. Reload HomeMap to pick up the changes.`
+                : `Repaired ${repaired.repaired}. Still to fix by hand:\n\n${this.formatIntegrityIssues(remaining)}`;
+            await window.__TAURI__.dialog.message(message, { title, kind: 'info' });
+        } catch (error) {
+            console.error('Data integrity check failed:', error);
+            await window.__TAURI__.dialog.message(`Data integrity check failed: ${error}`, { title, kind: 'error' });
+        }
+    }
+    
+    async showStorageCleanupDialog() {
+        const dialog = document.getElementById('storageCleanupDialog');
+        const trashBtn = document.getElementById('storageCleanupTrashBtn');
+        const emptyBtn = document.getElementById('storageCleanupEmptyBtn');
+        
+        trashBtn.onclick = async () => {
+            const paths = [...dialog.querySelectorAll('#storageCleanupCandidates input:checked')].map(box => box.value);
+            if (paths.length === 0) return;
+            try {
+                await this.invoke('move_to_trash', { paths });
+                this.dialogManager.invalidateIconSets();
+                await this.refreshStorageCleanup();
+            } catch (error) {
+                console.error('Failed to move items to trash:', error);
+                await window.__TAURI__.dialog.message(`Failed to move items to the trash: ${error}`, { title: 'Clean Up Storage', kind: 'error' });
+            }
+        };
+        emptyBtn.onclick = async () => {
+            const confirmed = await window.__TAURI__.dialog.confirm(
+                'Permanently delete everything in the trash?', { title: 'Empty Trash', kind: 'warning' });
+            if (!confirmed) return;
+            try {
+                const freed = await this.invoke('empty_trash');
+                console.log(`Emptied trash, freed ${this.formatFileSize(freed)}`);
+                await this.refreshStorageCleanup();
+            } catch (error) {
+                console.error('Failed to empty trash:', error);
+                await window.__TAURI__.dialog.message(`Failed to empty the trash: ${error}`, { title: 'Empty Trash', kind: 'error' });
+            }
+        };
+        document.getElementById('storageCleanupCloseBtn').onclick = () => {
+            dialog.style.display = 'none';
+        };
+        
+        dialog.style.display = 'flex';
+        await this.refreshStorageCleanup();
+    }
+    
+    async refreshStorageCleanup() {
+        const summary = document.getElementById('storageCleanupSummary');
+        const candidatesContainer = document.getElementById('storageCleanupCandidates');
+        const trashContainer = document.getElementById('storageCleanupTrash');
+        
+        try {
+            const scan = await this.invoke('scan_storage_cleanup');
+            const trash = await this.invoke('list_trash');
+            console.log('Storage cleanup scan:', scan, 'trash:', trash);
+            
+            const removed = scan.temp_removed.length > 0
+                ? `Removed ${scan.temp_removed.length} leftover temp entries (${this.formatFileSize(scan.temp_freed)}). `
+                : '';
+            summary.textContent = `${removed}${scan.candidates.length} unused items (${this.formatFileSize(scan.candidates_size)}), ${this.formatFileSize(scan.trash_size)} in the trash.`;
+            
+            candidatesContainer.innerHTML = '';
+            if (scan.candidates.length === 0) {
+                candidatesContainer.innerHTML = '<p style="color: #888; text-align: center;">Nothing to clean up.</p>';
+            }
+            scan.candidates.forEach(candidate => {
+                const item = document.createElement('label');
+                item.className = 'backup-file-item';
+                const box = document.createElement('input');
+                box.type = 'checkbox';
+                box.value = candidate.path;
+                const info = document.createElement('div');
+                info.className = 'backup-file-content';
+                info.innerHTML = `
+                    <div class="backup-file-name"></div>
+                    <div class="backup-file-info">${this.formatFileSize(candidate.size)} | ${candidate.reason}</div>
+                `;
+                info.querySelector('.backup-file-name').textContent = candidate.path;
+                item.appendChild(box);
+                item.appendChild(info);
+                candidatesContainer.appendChild(item);
+            });
+            
+            trashContainer.innerHTML = '';
+            if (trash.length === 0) {
+                trashContainer.innerHTML = '<p style="color: #888; text-align: center;">The trash is empty.</p>';
+            }
+            trash.forEach(batch => {
+                const item = document.createElement('div');
+                item.className = 'backup-file-item';
+                const info = document.createElement('div');
+                info.className = 'backup-file-content';
+                info.innerHTML = `
+                    <div class="backup-file-name">${new Date(batch.trashed_at).toLocaleString()}</div>
+                    <div class="backup-file-info">${batch.items.length} items | ${this.formatFileSize(batch.size)}</div>
+                `;
+                info.title = batch.items.map(i => i.path).join('\n');
+                const restoreBtn = document.createElement('button');
+                restoreBtn.className = 'secondary-button';
+                restoreBtn.textContent = 'Restore';
+                restoreBtn.onclick = async () => {
+                    try {
+                        const result = await this.invoke('restore_from_trash', { batchId: batch.id });
+                        this.dialogManager.invalidateIconSets();
+                        if (result.conflicts.length > 0) {
+                            await window.__TAURI__.dialog.message(
+                                `Not restored because something now exists at:\n${result.conflicts.join('\n')}`,
+                                { title: 'Restore from Trash', kind: 'warning' });
+                        }
+                        await this.refreshStorageCleanup();
+                    } catch (error) {
+                        console.error('Failed to restore from trash:', error);
+                        await window.__TAURI__.dialog.message(`Failed to restore: ${error}`, { title: 'Restore from Trash', kind: 'error' });
+                    }
+                };
+                item.appendChild(info);
+                item.appendChild(restoreBtn);
+                trashContainer.appendChild(item);
+            });
+            
+            document.getElementById('storageCleanupTrashBtn').disabled = scan.candidates.length === 0;
+            document.getElementById('storageCleanupEmptyBtn').disabled = trash.length === 0;
+        } catch (error) {
+            console.error('Storage cleanup scan failed:', error);
+            summary.textContent = `Scan failed: ${error}`;
+        }
+    }
+    
+    async togglePortableMode() {
+        const enable = !this.portableStatus?.enabled;
+        const message = enable
+            ? 'Copy settings and HomeMap data next to the app and run in portable mode?\n\nThe current files are kept. HomeMap restarts afterwards.'
+            : 'Copy the portable settings and data back to the standard locations and leave portable mode?\n\nThe portable folder is kept. HomeMap restarts afterwards.';
+        const confirmed = await window.__TAURI__.dialog.confirm(message, { title: 'Portable Mode', kind: 'warning' });
+        if (!confirmed) return;
+        
+        try {
+            await this.invoke(enable ? 'enable_portable_mode' : 'disable_portable_mode');
+            if (window.__TAURI__?.process?.relaunch) {
+                await window.__TAURI__.process.relaunch();
+            } else {
+                await window.__TAURI__.dialog.message('Done. Please restart HomeMap.', { title: 'Portable Mode', kind: 'info' });
+            }
+        } catch (error) {
+            console.error('Failed to change portable mode:', error);
+            await window.__TAURI__.dialog.message(`Failed to change portable mode: ${error}`, { title: 'Portable Mode', kind: 'error' });
+        }
+    }
+    
+    async moveHomemapData() {
+        const picked = await this.invoke('select_homemap_folder');
+        if (!picked) return;
+        const destination = picked.endsWith('homemapdata') ? picked : `${picked}/homemapdata`;
+        const confirmed = await window.__TAURI__.dialog.confirm(
+            `Move the HomeMap data folder to:\n${destination}\n\n` +
+            `Everything is copied and verified first; the old folder is only removed afterwards. HomeMap reloads when done.`,
+            { title: 'Move HomeMap Data', kind: 'warning' }
+        );
+        if (!confirmed) return;
+        
+        const button = document.getElementById('moveHomemapDataBtn');
+        const status = document.getElementById('moveHomemapDataStatus');
+        const originalStatus = status.textContent;
+        const unlisten = await window.__TAURI__.event.listen('data-relocate-progress', (event) => {
+            const p = event.payload;
+            const percent = p.bytes_total > 0 ? Math.round(p.bytes_done * 100 / p.bytes_total) : 0;
+            status.textContent = p.files_total > 0 && p.phase !== 'done'
+                ? `${p.phase}... ${p.files_done}/${p.files_total} files (${percent}%)`
+                : `${p.phase}...`;
+        });
+        
+        button.disabled = true;
+        try {
+            this.stopEventPolling();
+            const result = await this.invoke('relocate_data_directory', { destination });
+            let message = `Moved ${result.files} files to ${result.to}.`;
+            if (result.old_remove_error) {
+                message += `\n\nThe old folder could not be removed (${result.old_remove_error}). You can delete ${result.from} yourself.`;
+            }
+            await window.__TAURI__.dialog.message(message, { title: 'Move HomeMap Data', kind: 'info' });
+            window.location.reload();
+        } catch (error) {
+            console.error('Failed to move HomeMap data:', error);
+            status.textContent = originalStatus;
+            await window.__TAURI__.dialog.message(`Failed to move HomeMap data: ${error}\n\nThe data was left in its current location.`, { title: 'Move HomeMap Data', kind: 'error' });
+            this.startEventPolling();
+        } finally {
+            unlisten();
+            button.disabled = false;
+        }
+    }
+    
+    async deleteProfile(name) {
+        if (!name) return;
+        const confirmed = await window.__TAURI__.dialog.confirm(
+            `Delete profile "${name}"? The HomeMap data folder is not removed.`,
+            { title: 'Delete Profile', kind: 'warning' }
+        );
+        if (!confirmed) return;
+        
+        try {
+            await this.invoke('delete_profile', { name });
+            await this.loadProfiles();
+        } catch (error) {
+            console.error('Failed to delete profile:', error);
+            await window.__TAURI__.dialog.message(`Failed to delete profile: ${error}`, { title: 'Delete Profile', kind: 'error' });
+        }
+    }
+    
+    async switchProfile(name) {
+        if (!name) return;
+        try {
+            console.log(`Switching to profile "${name}"...`);
+            this.stopEventPolling();
+            // Don't show the startup picker again after the reload
+            sessionStorage.setItem('homemap.profileChosen', '1');
+            // The backend reloads the window once the new home is ready
+            await this.invoke('switch_profile', { name });
+        } catch (error) {
+            console.error('Failed to switch profile:', error);
+            await window.__TAURI__.dialog.message(`Failed to switch profile: ${error}`, { title: 'Switch Profile', kind: 'error' });
+        }
+    }
+    
+    // Ask for the vault passphrase when credentials are kept in the encrypted file store
+    async unlockVaultAtStartup() {
+        let status;
+        try {
+            status = await this.invoke('get_vault_status');
+        } catch (error) {
+            console.error('Failed to get vault status:', error);
+            return;
+        }
+        if (!status.locked) {
+            return;
+        }
+        
+        await new Promise(resolve => {
+            const dialog = document.getElementById('vaultUnlockDialog');
+            const input = document.getElementById('vaultPassphrase');
+            const errorEl = document.getElementById('vaultUnlockError');
+            document.getElementById('vaultUnlockMessage').textContent = status.initialized
+                ? 'Enter the vault passphrase to connect to the HC3.'
+                : 'No OS keyring is available. Choose a passphrase to encrypt your HC3 password.';
+            errorEl.style.display = 'none';
+            input.value = '';
+            
+            const close = () => {
+                dialog.style.display = 'none';
+                resolve();
+            };
+            const unlock = async () => {
+                try {
+                    await this.invoke('unlock_vault', { passphrase: input.value });
+                    close();
+                } catch (error) {
+                    errorEl.textContent = `${error}`;
+                    errorEl.style.display = 'block';
+                }
+            };
+            document.getElementById('vaultUnlockBtn').onclick = unlock;
+            document.getElementById('vaultSkipBtn').onclick = close;
+            input.onkeydown = (e) => {
+                if (e.key === 'Enter') {
+                    unlock();
+                }
+            };
+            
+            dialog.style.display = 'flex';
+            input.focus();
+        });
+    }
+
+    /**
+     * Let the user choose a home at startup when more than one profile exists.
+     * Returns true when a switch (and reload) was started.
+     */
+    async pickProfileAtStartup() {
+        if (sessionStorage.getItem('homemap.profileChosen')) {
+            return false;
+        }
+        sessionStorage.setItem('homemap.profileChosen', '1');
+        
+        let profiles;
+        try {
+            profiles = await this.invoke('list_profiles');
+        } catch (error) {
+            console.error('Failed to list profiles:', error);
+            return false;
+        }
+        if (profiles.length < 2) {
+            return false;
+        }
+        
+        const chosen = await new Promise(resolve => {
+            const dialog = document.getElementById('profilePickerDialog');
+            const list = document.getElementById('profilePickerList');
+            list.innerHTML = '';
+            
+            profiles.forEach(profile => {
+                const item = document.createElement('div');
+                item.className = `backup-file-item${profile.active ? ' selected' : ''}`;
+                item.innerHTML = `
+                    <div class="backup-file-content">
+                        <div class="backup-file-name"></div>
+                        <small></small>
+                    </div>
+                `;
+                item.querySelector('.backup-file-name').textContent = profile.name;
+                item.querySelector('small').textContent = profile.hc3_host || 'HC3 not configured';
+                item.onclick = () => {
+                    dialog.style.display = 'none';
+                    resolve(profile);
+                };
+                list.appendChild(item);
+            });
+            
+            dialog.style.display = 'flex';
+        });
+        
+        if (chosen.active) {
+            return false;
+        }
+        await this.switchProfile(chosen.name);
+        return true;
+    }
+    
     async loadInstalledPackages() {
         try {
             const packagesList = document.getElementById('installedPackagesList');
@@ -450,6 +925,7 @@ class HomeMap {
             console.log('Starting package installation...');
             const manifest = await packageManager.installPackage();
             if (manifest) {
+                this.dialogManager.invalidateIconSets();
                 console.log('Package installed, manifest:', manifest);
                 
                 // Reload installed packages list first
@@ -495,7 +971,11 @@ class HomeMap {
         }
         
         try {
-            await packageManager.uninstallPackage(packageId);
+            const change = await packageManager.uninstallPackage(packageId);
+            if (!change) {
+                return;
+            }
+            this.dialogManager.invalidateIconSets();
             await window.__TAURI__.dialog.message('Package uninstalled successfully!', {
                 title: 'Uninstall Complete',
                 kind: 'info'
@@ -716,10 +1196,14 @@ class HomeMap {
             const now = new Date();
             const timestamp = now.toISOString().replace(/[:.]/g, '-').split('T')[0];
             const defaultFilename = `homemap-backup-${timestamp}.zip`;
+            
+            // Portable installs keep their backups next to the app
+            const portable = await this.invoke('get_portable_status').catch(() => null);
+            const defaultPath = portable?.enabled ? `${portable.backups_dir}/${defaultFilename}` : defaultFilename;
 
             // Show save dialog
             const savePath = await window.__TAURI__.dialog.save({
-                defaultPath: defaultFilename,
+                defaultPath,
                 filters: [{
                     name: 'Zip Archive',
                     extensions: ['zip']
@@ -1295,6 +1779,13 @@ class HomeMap {
 
     async init() {
         try {
+            // Multiple homes: ask which one to open
+            if (await this.pickProfileAtStartup()) {
+                return;
+            }
+            
+            await this.unlockVaultAtStartup();
+            
             console.log('Getting HC3 config...');
             this.config = await this.invoke('get_hc3_config');
             console.log('Config received:', this.config);
@@ -1348,203 +1839,36 @@ You can also configure floor plans and manage devices once connected!`;
         });
     }
 
-    async syncBuiltinResourcesIfNeeded() {
-        try {
-            console.log('=== Checking if built-in resources need syncing ===');
-            
-            // Get app version from Rust
-            const appVersion = await this.invoke('get_app_version');
-            console.log('App version:', appVersion);
-            
-            // Check stored version
-            const versionFile = `${this.dataPath}/.builtin-version`;
-            let storedVersion = null;
-            
-            try {
-                storedVersion = await this.invoke('read_file_as_text', { filePath: versionFile });
-                storedVersion = storedVersion.trim();
-                console.log('Stored built-in version:', storedVersion);
-            } catch (err) {
-                console.log('No version file found - first run or needs sync');
-            }
-            
-            // Check if sync is needed
-            if (storedVersion === appVersion) {
-                console.log('Built-in resources already synced for version', appVersion);
-                return;
-            }
-            
-            console.log('Syncing built-in resources from bundled assets...');
-            
-            // Read manifest to find built-in resources
-            const manifestB64 = await this.invoke('read_bundled_asset', { 
-                assetPath: 'asset-manifest.json' 
-            });
-            const manifestJson = atob(manifestB64);
-            const manifest = JSON.parse(manifestJson);
-            
-            // Filter for built-in resources only (widgets/built-in/* and icons/built-in/*)
-            const builtinFiles = manifest.files.filter(file => 
-                file.startsWith('widgets/built-in/') || file.startsWith('icons/built-in/')
-            );
-            
-            console.log(`Found ${builtinFiles.length} built-in files to sync`);
-            
-            let successCount = 0;
-            let errorCount = 0;
-            
-            // Sync each built-in file
-            for (const file of builtinFiles) {
-                try {
-                    const fileB64 = await this.invoke('read_bundled_asset', { 
-                        assetPath: file 
-                    });
-                    
-                    const targetPath = `${this.dataPath}/${file}`;
-                    await this.invoke('write_file_base64', { 
-                        filePath: targetPath,
-                        b64: fileB64 
-                    });
-                    
-                    successCount++;
-                } catch (err) {
-                    console.error(`Error syncing ${file}:`, err);
-                    errorCount++;
-                }
-            }
-            
-            console.log(`Built-in sync complete: ${successCount} succeeded, ${errorCount} failed`);
-            
-            // Update version file
-            if (successCount > 0 || builtinFiles.length === 0) {
-                await this.invoke('write_file_as_text', { 
-                    filePath: versionFile,
-                    content: appVersion 
-                });
-                console.log('Updated built-in version to', appVersion);
-            }
-            
-        } catch (error) {
-            console.error('Failed to sync built-in resources:', error);
+    // Tell the user once when an upgrade touched built-in widgets or icons they had edited
+    async showBuiltinSyncReport() {
+        const report = await this.invoke('get_builtin_sync_report');
+        if (!report || localStorage.getItem('homemap.builtinSyncShown') === report.synced_at) {
+            return;
         }
-    }
-
-    async copyBundledAssetsIfNeeded() {
-        try {
-            console.log('=== copyBundledAssetsIfNeeded STARTING ===');
-            const manifestVersion = '1.0.1';
-            const versionKey = 'bundled_assets_version';
-            
-            // Check if we've already copied this version
-            const copiedVersion = localStorage.getItem(versionKey);
-            console.log('Copied version from storage:', copiedVersion);
-            if (copiedVersion === manifestVersion) {
-                console.log('Bundled assets already copied for version', manifestVersion);
-                // Still check if built-in resources need syncing (for app upgrades)
-                await this.syncBuiltinResourcesIfNeeded();
-                return;
-            }
-            
-            // Check if assets already exist (check widgets directory instead of config.json)
-            try {
-                const testPath = `${this.dataPath}/widgets/built-in`;
-                const { readDir } = window.__TAURI__.fs;
-                const entries = await readDir(testPath);
-                if (entries && entries.length > 0) {
-                    console.log('Assets already exist (likely copied by Rust), skipping JS copy');
-                    localStorage.setItem(versionKey, manifestVersion);
-                    // Still check if built-in resources need syncing
-                    await this.syncBuiltinResourcesIfNeeded();
-                    return;
-                }
-            } catch (err) {
-                // Assets don't exist, continue with copy
-                console.log('Assets not found, will copy from bundled resources');
-            }
-            
-            console.log('Copying bundled assets to data directory...');
-            console.log('Data path:', this.dataPath);
-            
-            // Fetch manifest from bundled assets using Rust command (embedded at compile time)
-            console.log('Reading asset-manifest.json from bundled assets...');
-            let manifestB64;
-            try {
-                manifestB64 = await this.invoke('read_bundled_asset', { 
-                    assetPath: 'asset-manifest.json' 
-                });
-            } catch (manifestError) {
-                const errorMsg = `CRITICAL: Failed to read asset-manifest.json from bundled resources. This means assets were not properly bundled in the APK. Error: ${manifestError.message || manifestError}`;
-                console.error(errorMsg);
-                alert(errorMsg);
-                throw new Error(errorMsg);
-            }
-            
-            try {
-                const manifestJson = atob(manifestB64);
-                const manifest = JSON.parse(manifestJson);
-                console.log(`Found ${manifest.files.length} files to copy`);
-                
-                let successCount = 0;
-                let errorCount = 0;
-                let firstError = null;
-                
-                // Copy each file from manifest
-                for (const file of manifest.files) {
-                try {
-                    // Read file from bundled assets using Rust command
-                    const fileB64 = await this.invoke('read_bundled_asset', { 
-                        assetPath: file 
-                    });
-                    
-                    // Write to data directory
-                    const targetPath = `${this.dataPath}/${file}`;
-                    await this.invoke('write_file_base64', { 
-                        filePath: targetPath,
-                        b64: fileB64 
-                    });
-                    
-                    successCount++;
-                    if (successCount % 10 === 0) {
-                        console.log(`Progress: ${successCount}/${manifest.files.length} files copied`);
-                    }
-                } catch (err) {
-                    console.error(`Error copying ${file}:`, err);
-                    errorCount++;
-                    if (!firstError) {
-                        firstError = `${file}: ${err.message || err}`;
-                    }
-                }
-            }
-            
-            console.log(`Asset copy complete: ${successCount} succeeded, ${errorCount} failed`);
-            
-            if (errorCount > 0) {
-                const errorMsg = `Warning: ${errorCount} assets failed to copy. First error: ${firstError}`;
-                console.warn(errorMsg);
-                alert(errorMsg);
-            }
-            
-            // Store version to avoid re-copying
-            if (successCount > 0) {
-                localStorage.setItem(versionKey, manifestVersion);
-            } else {
-                const errorMsg = 'CRITICAL: No assets were successfully copied. App may not function correctly.';
-                console.error(errorMsg);
-                alert(errorMsg);
-                throw new Error(errorMsg);
-            }
-            } catch (parseError) {
-                const errorMsg = `Failed to parse asset manifest: ${parseError.message || parseError}`;
-                console.error(errorMsg);
-                alert(errorMsg);
-                throw parseError;
-            }
-            
-        } catch (error) {
-            console.error('Failed to copy bundled assets:', error);
-            alert(`Asset copy failed: ${error.message || error}`);
-            throw error; // Re-throw to prevent app from starting with missing assets
+        localStorage.setItem('homemap.builtinSyncShown', report.synced_at);
+        console.log('Built-in sync report:', report);
+        
+        const lines = [];
+        if (report.moved_aside.length > 0) {
+            lines.push(`Edited built-ins replaced by the new version (your copies were backed up):\n${report.moved_aside.map(f => `  ${f.path} → ${f.backup}`).join('\n')}`);
+        }
+        if (report.kept_modified.length > 0) {
+            lines.push(`Edited built-ins kept (not updated):\n${report.kept_modified.map(p => `  ${p}`).join('\n')}`);
+        }
+        if (report.removed.length > 0) {
+            lines.push(`Built-ins no longer shipped and removed:\n${report.removed.map(p => `  ${p}`).join('\n')}`);
+        }
+        if (report.kept_stale.length > 0) {
+            lines.push(`Built-ins no longer shipped but kept because you edited them:\n${report.kept_stale.map(p => `  ${p}`).join('\n')}`);
+        }
+        if (lines.length === 0) {
+            return;
         }
+        
+        await window.__TAURI__.dialog.message(
+            `Built-in widgets and icons were updated to version ${report.version}.\n\n${lines.join('\n\n')}`,
+            { title: 'Built-in Resources Updated', kind: 'info' }
+        );
     }
 
     async loadHomeMapConfig() {
@@ -1553,44 +1877,16 @@ You can also configure floor plans and manage devices once connected!`;
             this.dataPath = await this.invoke('get_data_path');
             console.log('Data path:', this.dataPath);
             
-            // Sync bundled resources (widgets, icons) on mobile platforms
+            // Built-in widgets and icons are synced from the template embedded in the app
             try {
                 console.log('Syncing bundled resources...');
                 await this.invoke('sync_resources');
                 console.log('Resource sync completed');
+                await this.showBuiltinSyncReport();
             } catch (err) {
                 console.warn('Resource sync warning:', err);
             }
             
-            // Check if this is first run (no bundled assets copied yet)
-            const versionKey = 'bundled_assets_version';
-            const copiedVersion = localStorage.getItem(versionKey);
-            const isFirstRun = !copiedVersion;
-            
-            // On Android/iOS, always wait for asset copy to ensure widgets are available
-            // Check if we're on a mobile platform by looking at __TAURI_INTERNALS__
-            const isMobile = window.__TAURI_INTERNALS__?.metadata?.currentTarget?.includes('android') || 
-                           window.__TAURI_INTERNALS__?.metadata?.currentTarget?.includes('ios') ||
-                           navigator.userAgent.toLowerCase().includes('android') ||
-                           navigator.userAgent.toLowerCase().includes('iphone') ||
-                           navigator.userAgent.toLowerCase().includes('ipad');
-            
-            console.log('Platform check - isMobile:', isMobile, 'isFirstRun:', isFirstRun, 'userAgent:', navigator.userAgent);
-            
-            if (isFirstRun || isMobile) {
-                // Wait for asset copy to complete before continuing
-                console.log(isFirstRun ? 'First run detected, waiting for asset copy...' : 'Mobile platform detected, ensuring assets are up to date...');
-                await this.copyBundledAssetsIfNeeded();
-                console.log('Asset copy completed, continuing startup...');
-            } else {
-                // On desktop subsequent runs, copy in background (updates)
-                this.copyBundledAssetsIfNeeded().then(() => {
-                    console.log('Background asset copy completed');
-                }).catch(err => {
-                    console.error('Background asset copy failed:', err);
-                });
-            }
-            
             // Initialize widget manager now that we have dataPath
             this.widgetManager = new WidgetManager(this.dataPath, this.invoke);
             
@@ -1634,6 +1930,9 @@ You can also configure floor plans and manage devices once connected!`;
             
             this.floorManager.renderFloors();
             
+            // Keep computed values that depend on the time current
+            this.widgetManager.startClockRefresh(this.deviceIcons);
+            
             // Apply widget background settings
             this.applyWidgetBackgroundSettings();
             